@@ -27,7 +27,7 @@ fn main() {
             pb.close_path();
         });
 
-        cb.push(cmd::Cmd::FillPathSolid { path, color: 42 });
+        cb.push(cmd::Cmd::FillPath { path, fill_rule: rasterizer::FillRule::NonZero, paint: cmd::Paint::Solid(42), opacity: 1.0, blend: blend::BlendMode::SrcOver });
     });
     println!("{:?}", cmds.num_cmds());
 }
@@ -5,6 +5,8 @@ use rug::image::*;
 use rug::renderer::*;
 use rug::cmd::*;
 use rug::color::*;
+use rug::rasterizer::FillRule;
+use rug::blend::BlendMode;
 
 fn draw_svg(name: &str, svg: &str, w: u32, h: u32, s: f32, flip: bool) {
     println!("drawing {:?}", name);
@@ -24,6 +26,7 @@ fn draw_svg(name: &str, svg: &str, w: u32, h: u32, s: f32, flip: bool) {
         else {
             Transform::scale([s, s].into())
         },
+        load: false,
     };
 
     let t0 = std::time::Instant::now();
@@ -115,7 +118,7 @@ fn main() {
                 pb.close_path();
             });
 
-            cb.push(Cmd::FillPathRadialGradient { path, gradient, opacity: 1.0 });
+            cb.push(Cmd::FillPath { path, fill_rule: FillRule::NonZero, paint: Paint::RadialGradient(gradient), opacity: 1.0, blend: BlendMode::SrcOver });
         });
 
 
@@ -143,6 +146,7 @@ fn main() {
         let params = RenderParams {
             clear: 0xffffffff,
             tfx: Transform::scale1(s),// * rotation,
+            load: false,
         };
 
         let iters = 1;
@@ -170,6 +174,7 @@ fn main() {
             clear: 0xffffffff,
             tfx: Transform::translate([0.0, h as f32].into()) *
                  Transform::scale([s, -s].into()),
+            load: false,
         };
 
         let iters = 1000;
@@ -195,6 +200,7 @@ fn main() {
             clear: 0xffffffff,
             tfx: Transform::translate([0.0, h as f32].into()) *
                  Transform::scale([s, -s].into()),
+            load: false,
         };
 
         let iters = 100;
@@ -219,6 +225,7 @@ fn main() {
             clear: 0xffffffff,
             tfx: Transform::scale([s, -s].into()) *
                  Transform::translate([0.0, -510.0].into()),
+            load: false,
         };
 
         let iters = 500;
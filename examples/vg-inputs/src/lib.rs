@@ -11,7 +11,7 @@ pub mod svg {
 }
 
 
-use rug::{cmd::*, color::*, geometry::Transform};
+use rug::{cmd::*, color::*, geometry::Transform, stroke::*, rasterizer::FillRule, blend::BlendMode, path::PathBuilder};
 use xmlparser::*;
 use core::str::FromStr;
 use std::collections::HashMap;
@@ -31,21 +31,117 @@ pub fn parse_svg(svg: &str) -> CmdBuf {
 }
 
 
-enum Def {
-    LinearGradient(LinearGradientId),
-    RadialGradient(RadialGradientId),
+/// returns `false` on unsupported path data.
+fn parse_path_data(pb: &mut PathBuilder, data: &str) -> bool {
+    let mut error = false;
+
+    for e in svgtypes::PathParser::from(data) {
+        use svgtypes::PathSegment::*;
+        match e.unwrap() {
+            MoveTo { abs, x, y } => {
+                if abs {
+                    pb.move_to([x as f32, y as f32]);
+                }
+                else {
+                    println!("abs not implemented");
+                    error = true;
+                    break;
+                }
+            }
+
+            LineTo { abs, x, y } => {
+                if abs {
+                    pb.line_to([x as f32, y as f32]);
+                }
+                else {
+                    println!("abs not implemented");
+                    error = true;
+                    break;
+                }
+            }
+
+            Quadratic { abs, x1, y1, x, y } => {
+                if abs {
+                    pb.quad_to([x1 as f32, y1 as f32], [x as f32, y as f32]);
+                }
+                else {
+                    println!("abs not implemented");
+                    error = true;
+                    break;
+                }
+            }
+
+            CurveTo { abs, x1, y1, x2, y2, x, y } => {
+                if abs {
+                    pb.cubic_to([x1 as f32, y1 as f32], [x2 as f32, y2 as f32], [x as f32, y as f32]);
+                }
+                else {
+                    println!("abs not implemented");
+                    error = true;
+                    break;
+                }
+            }
+
+            ClosePath { abs: _ } => {
+                pb.close_path();
+            }
+
+            e => {
+                println!("unsupported path cmd {:?}", e);
+                error = true;
+            }
+        }
+    }
+
+    return !error;
+}
+
+/// parses `url(#id)`.
+fn parse_url(value: &str) -> Option<&str> {
+    value.trim().strip_prefix("url(#")?.strip_suffix(')')
+}
+
+fn parse_blend_mode(value: &str) -> BlendMode {
+    match value.trim() {
+        "multiply"    => BlendMode::Multiply,
+        "screen"      => BlendMode::Screen,
+        "overlay"     => BlendMode::Overlay,
+        "darken"      => BlendMode::Darken,
+        "lighten"     => BlendMode::Lighten,
+        "color-dodge" => BlendMode::ColorDodge,
+        "color-burn"  => BlendMode::ColorBurn,
+        "hard-light"  => BlendMode::HardLight,
+        "soft-light"  => BlendMode::SoftLight,
+        "difference"  => BlendMode::Difference,
+        "exclusion"   => BlendMode::Exclusion,
+        "hue"         => BlendMode::Hue,
+        "saturation"  => BlendMode::Saturation,
+        "color"       => BlendMode::Color,
+        "luminosity"  => BlendMode::Luminosity,
+        _             => BlendMode::SrcOver,
+    }
+}
+
+fn parse_transform(value: &str) -> Transform {
+    let t = svgtypes::Transform::from_str(value).unwrap();
+    Transform { columns: [
+        [t.a as f32, t.b as f32].into(),
+        [t.c as f32, t.d as f32].into(),
+        [t.e as f32, t.f as f32].into(),
+    ]}
 }
 
-enum Paint {
-    Solid(u32),
+
+enum Def<'cb> {
     LinearGradient(LinearGradientId),
     RadialGradient(RadialGradientId),
+    ClipPath(rug::path::Path<'cb>, FillRule),
 }
 
 struct SvgParser<'a, 'cb> {
     toker: Tokenizer<'a>,
     cb: &'a mut CmdBufBuilder<'cb>,
-    defs: HashMap<&'a str, Def>,
+    defs: HashMap<&'a str, Def<'cb>>,
 }
 
 impl<'a, 'cb> SvgParser<'a, 'cb> {
@@ -128,8 +224,55 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
             }
 
             "g" => {
-                if self.skip_attrs() {
+                let mut tfx:  Option<Transform> = None;
+                let mut clip: Option<&'a str> = None;
+                let mut opacity = 1.0;
+                let mut blend = BlendMode::SrcOver;
+
+                let children = loop {
+                    match self.toker.next().unwrap().unwrap() {
+                        Token::Attribute { prefix, local, value, .. } => {
+                            if !prefix.is_empty() { continue; }
+
+                            match local.as_str() {
+                                "transform" => tfx = Some(parse_transform(&*value)),
+                                "clip-path" => clip = parse_url(value.as_str()),
+                                "opacity"   => opacity = (svgtypes::Number::from_str(&*value).unwrap().0 as f32).clamp(0.0, 1.0),
+                                "mix-blend-mode" => blend = parse_blend_mode(value.as_str()),
+                                _ => (),
+                            }
+                        }
+
+                        Token::Text {..} => (),
+
+                        Token::ElementEnd { end: ElementEnd::Empty, span: _ } => break false,
+                        Token::ElementEnd { end: ElementEnd::Open, span: _ } => break true,
+
+                        _ => unimplemented!()
+                    }
+                };
+
+                if children {
+                    if let Some(tfx) = tfx {
+                        self.cb.push(Cmd::PushTransform { tfx });
+                    }
+                    let clipped = self.push_clip(clip);
+                    let grouped = opacity < 1.0 || blend != BlendMode::SrcOver;
+                    if grouped {
+                        self.cb.push(Cmd::BeginGroup { opacity, blend, filter: None });
+                    }
+
                     self.visit_children(|this, at| this.parse_element(at));
+
+                    if grouped {
+                        self.cb.push(Cmd::EndGroup);
+                    }
+                    if clipped {
+                        self.cb.push(Cmd::PopClip);
+                    }
+                    if tfx.is_some() {
+                        self.cb.push(Cmd::PopTransform);
+                    }
                 }
             }
 
@@ -139,9 +282,16 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
                 let mut path:           Option<rug::path::Path> = None;
                 let mut fill:           Option<Paint> = None;
                 let mut fill_opacity:   Option<f32> = None;
+                let mut fill_rule:      Option<FillRule> = None;
+                let mut clip:           Option<&'a str> = None;
                 let mut stroke:         Option<Paint> = None;
                 let mut stroke_width:   Option<f32> = None;
                 let mut stroke_opacity: Option<f32> = None;
+                let mut stroke_cap:     Option<LineCap> = None;
+                let mut stroke_join:    Option<LineJoin> = None;
+                let mut stroke_miter:   Option<f32> = None;
+                let mut stroke_dashes:  Option<Vec<f32>> = None;
+                let mut stroke_dash_offset: Option<f32> = None;
 
                 loop {
                     match self.toker.next().unwrap().unwrap() {
@@ -151,67 +301,7 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
                             path = Some(self.cb.build_path(|pb| {
                                 match local.as_str() {
                                     "d" => {
-                                        let mut error = false;
-
-                                        for e in svgtypes::PathParser::from(&*value) {
-                                            use svgtypes::PathSegment::*;
-                                            match e.unwrap() {
-                                                MoveTo { abs, x, y } => {
-                                                    if abs {
-                                                        pb.move_to([x as f32, y as f32]);
-                                                    }
-                                                    else {
-                                                        println!("abs not implemented");
-                                                        error = true;
-                                                        break;
-                                                    }
-                                                }
-
-                                                LineTo { abs, x, y } => {
-                                                    if abs {
-                                                        pb.line_to([x as f32, y as f32]);
-                                                    }
-                                                    else {
-                                                        println!("abs not implemented");
-                                                        error = true;
-                                                        break;
-                                                    }
-                                                }
-
-                                                Quadratic { abs, x1, y1, x, y } => {
-                                                    if abs {
-                                                        pb.quad_to([x1 as f32, y1 as f32], [x as f32, y as f32]);
-                                                    }
-                                                    else {
-                                                        println!("abs not implemented");
-                                                        error = true;
-                                                        break;
-                                                    }
-                                                }
-
-                                                CurveTo { abs, x1, y1, x2, y2, x, y } => {
-                                                    if abs {
-                                                        pb.cubic_to([x1 as f32, y1 as f32], [x2 as f32, y2 as f32], [x as f32, y as f32]);
-                                                    }
-                                                    else {
-                                                        println!("abs not implemented");
-                                                        error = true;
-                                                        break;
-                                                    }
-                                                }
-
-                                                ClosePath { abs: _ } => {
-                                                    pb.close_path();
-                                                }
-
-                                                e => {
-                                                    println!("unsupported path cmd {:?}", e);
-                                                    error = true;
-                                                }
-                                            }
-                                        }
-
-                                        if error {
+                                        if !parse_path_data(pb, &*value) {
                                             pb.clear();
                                         }
                                     }
@@ -234,6 +324,9 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
                                                         Def::RadialGradient(g) => {
                                                             fill = Some(Paint::RadialGradient(g));
                                                         }
+                                                        Def::ClipPath(..) => {
+                                                            println!("clip path used as fill {:?}", uri);
+                                                        }
                                                     }
                                                 }
                                                 else {
@@ -251,6 +344,14 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
                                         fill_opacity = Some(svgtypes::Number::from_str(&*value).unwrap().0 as f32);
                                     }
 
+                                    "fill-rule" => {
+                                        match value.as_str() {
+                                            "nonzero" => fill_rule = Some(FillRule::NonZero),
+                                            "evenodd" => fill_rule = Some(FillRule::EvenOdd),
+                                            _ => println!("unknown fill rule {:?}", &*value),
+                                        }
+                                    }
+
                                     "stroke" => {
                                         let paint = svgtypes::Paint::from_str(&*value).unwrap();
                                         match paint {
@@ -278,6 +379,53 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
                                         stroke_opacity = Some(svgtypes::Number::from_str(&*value).unwrap().0 as f32);
                                     }
 
+                                    "stroke-linecap" => {
+                                        match value.as_str() {
+                                            "butt"   => stroke_cap = Some(LineCap::Butt),
+                                            "round"  => stroke_cap = Some(LineCap::Round),
+                                            "square" => stroke_cap = Some(LineCap::Square),
+                                            _ => println!("unknown line cap {:?}", &*value),
+                                        }
+                                    }
+
+                                    "stroke-linejoin" => {
+                                        match value.as_str() {
+                                            "miter" => stroke_join = Some(LineJoin::Miter),
+                                            "round" => stroke_join = Some(LineJoin::Round),
+                                            "bevel" => stroke_join = Some(LineJoin::Bevel),
+                                            _ => println!("unknown line join {:?}", &*value),
+                                        }
+                                    }
+
+                                    "stroke-miterlimit" => {
+                                        stroke_miter = Some(svgtypes::Number::from_str(&*value).unwrap().0 as f32);
+                                    }
+
+                                    "stroke-dasharray" => {
+                                        if value.as_str() != "none" {
+                                            let mut dashes = Vec::new();
+                                            for d in svgtypes::NumberListParser::from(&*value) {
+                                                match d {
+                                                    Ok(d) => dashes.push(d as f32),
+                                                    Err(e) => {
+                                                        println!("unsupported dash array {:?}: {:?}", &*value, e);
+                                                        dashes.clear();
+                                                        break;
+                                                    }
+                                                }
+                                            }
+                                            stroke_dashes = Some(dashes);
+                                        }
+                                    }
+
+                                    "stroke-dashoffset" => {
+                                        stroke_dash_offset = Some(svgtypes::Number::from_str(&*value).unwrap().0 as f32);
+                                    }
+
+                                    "clip-path" => {
+                                        clip = parse_url(value.as_str());
+                                    }
+
                                     "id" => {
                                         //println!("{:?}", &*value);
                                     }
@@ -296,43 +444,31 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
 
                 let path = path.unwrap();
 
+                let clipped = self.push_clip(clip);
+
                 if let Some(paint) = fill {
                     let opacity = fill_opacity.unwrap_or(1.0);
-                    match paint {
-                        Paint::Solid(color) => {
-                            let [r, g, b, a] = *argb_unpack(color);
-                            let color = argb_pack([r, g, b, a*opacity].into());
-                            self.cb.push(Cmd::FillPathSolid { path, color });
-                        }
-
-                        Paint::LinearGradient(gradient) => {
-                            self.cb.push(Cmd::FillPathLinearGradient { path, gradient, opacity });
-                        }
-
-                        Paint::RadialGradient(gradient) => {
-                            self.cb.push(Cmd::FillPathRadialGradient { path, gradient, opacity });
-                        }
-                    }
+                    let fill_rule = fill_rule.unwrap_or(FillRule::NonZero);
+                    self.cb.push(Cmd::FillPath { path, fill_rule, paint, opacity, blend: BlendMode::SrcOver });
                 }
 
                 if let Some(paint) = stroke {
                     let opacity = stroke_opacity.unwrap_or(1.0);
-                    let width = stroke_width.unwrap_or(1.0);
-                    match paint {
-                        Paint::Solid(color) => {
-                            let [r, g, b, a] = *argb_unpack(color);
-                            let color = argb_pack([r, g, b, a*opacity].into());
-                            self.cb.push(Cmd::StrokePathSolid { path, color, width });
-                        }
-
-                        Paint::LinearGradient(_) => {
-                            unimplemented!()
-                        }
-
-                        Paint::RadialGradient(_) => {
-                            unimplemented!()
-                        }
+                    let mut style = StrokeStyle::new(stroke_width.unwrap_or(1.0));
+                    if let Some(cap)   = stroke_cap   { style.cap  = cap }
+                    if let Some(join)  = stroke_join  { style.join = join }
+                    if let Some(limit) = stroke_miter { style.miter_limit = limit }
+                    if let Some(dashes) = stroke_dashes {
+                        style.dashes = self.cb.build_dashes(|db| {
+                            for d in dashes { db.push(d) }
+                        });
                     }
+                    if let Some(offset) = stroke_dash_offset { style.dash_offset = offset }
+                    self.cb.push(Cmd::StrokePath { path, style, paint, opacity, blend: BlendMode::SrcOver });
+                }
+
+                if clipped {
+                    self.cb.push(Cmd::PopClip);
                 }
             }
 
@@ -425,12 +561,7 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
 
                                 "gradientTransform" => {
                                     if value.as_str() != "reset" {
-                                        let t = svgtypes::Transform::from_str(&*value).unwrap();
-                                        tfx = Transform { columns: [
-                                            [t.a as f32, t.b as f32].into(),
-                                            [t.c as f32, t.d as f32].into(),
-                                            [t.e as f32, t.f as f32].into(),
-                                        ]};
+                                        tfx = parse_transform(&*value);
                                     }
                                 }
 
@@ -513,12 +644,7 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
 
                                 "gradientTransform" => {
                                     if value.as_str() != "reset" {
-                                        let t = svgtypes::Transform::from_str(&*value).unwrap();
-                                        tfx = Transform { columns: [
-                                            [t.a as f32, t.b as f32].into(),
-                                            [t.c as f32, t.d as f32].into(),
-                                            [t.e as f32, t.f as f32].into(),
-                                        ]};
+                                        tfx = parse_transform(&*value);
                                     }
                                 }
 
@@ -554,6 +680,94 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
                 }
             }
 
+            "clipPath" => {
+                let mut id: Option<&'a str> = None;
+
+                let children = loop {
+                    match self.toker.next().unwrap().unwrap() {
+                        Token::Attribute { prefix, local, value, .. } => {
+                            if !prefix.is_empty() { continue; }
+
+                            match &*local {
+                                "id" => id = Some(value.as_str()),
+
+                                _ => {
+                                    println!("unknown clip path attr {:?}", local);
+                                }
+                            }
+                        }
+
+                        Token::ElementEnd { end: ElementEnd::Empty, span: _ } => break false,
+                        Token::ElementEnd { end: ElementEnd::Open, span: _ } => break true,
+
+                        _ => unimplemented!()
+                    }
+                };
+
+                // the clip path is the union of its child paths.
+                let mut datas: Vec<&'a str> = Vec::new();
+                let mut clip_rule = FillRule::NonZero;
+
+                if children {
+                    loop {
+                        match self.toker.next().unwrap().unwrap() {
+                            Token::ElementStart { prefix, local, span: _ } => {
+                                if !prefix.is_empty() || local.as_str() != "path" {
+                                    println!("unsupported clip path element {:?}", local);
+                                    if self.skip_attrs() {
+                                        self.skip_children();
+                                    }
+                                    continue;
+                                }
+
+                                loop {
+                                    match self.toker.next().unwrap().unwrap() {
+                                        Token::Attribute { prefix, local, value, .. } => {
+                                            if !prefix.is_empty() { continue; }
+
+                                            match &*local {
+                                                "d" => datas.push(value.as_str()),
+
+                                                "clip-rule" => {
+                                                    match value.as_str() {
+                                                        "nonzero" => clip_rule = FillRule::NonZero,
+                                                        "evenodd" => clip_rule = FillRule::EvenOdd,
+                                                        _ => println!("unknown clip rule {:?}", &*value),
+                                                    }
+                                                }
+
+                                                _ => ()
+                                            }
+                                        }
+
+                                        Token::ElementEnd { end: ElementEnd::Empty, span: _ } => break,
+
+                                        _ => unimplemented!()
+                                    }
+                                }
+                            }
+
+                            Token::ElementEnd { end: ElementEnd::Close(_, _), span: _ } => break,
+
+                            // ignored.
+                            _ => ()
+                        }
+                    }
+                }
+
+                if let Some(name) = id {
+                    let path = self.cb.build_path(|pb| {
+                        for data in &datas {
+                            if !parse_path_data(pb, data) {
+                                pb.clear();
+                                break;
+                            }
+                        }
+                    });
+                    self.defs.insert(name, Def::ClipPath(path, clip_rule));
+                }
+            }
+
             _ => {
                 println!("unknown def {:?}", kind);
                 if self.skip_attrs() {
@@ -634,6 +848,24 @@ impl<'a, 'cb> SvgParser<'a, 'cb> {
     }
 
 
+    /// returns whether a clip was pushed.
+    #[must_use]
+    fn push_clip(&mut self, clip: Option<&str>) -> bool {
+        let Some(name) = clip else { return false };
+
+        match self.defs.get(name) {
+            Some(Def::ClipPath(path, fill_rule)) => {
+                self.cb.push(Cmd::PushClipPath { path: *path, fill_rule: *fill_rule });
+                true
+            }
+
+            _ => {
+                println!("unknown clip path {:?}", name);
+                false
+            }
+        }
+    }
+
     #[must_use]
     fn skip_attrs(&mut self) -> bool {
         loop {
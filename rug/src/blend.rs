@@ -0,0 +1,391 @@
+use sti::simd::*;
+
+
+/// how a source color is combined with the destination.
+///
+/// - the porter-duff operators and blend modes follow the
+///   w3c "compositing and blending" spec.
+/// - fills only affect the area covered by their mask,
+///   even for operators like `Src` or `DstIn`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendMode {
+    // porter-duff.
+    Clear,
+    Src,
+    Dst,
+    SrcOver,
+    DstOver,
+    SrcIn,
+    DstIn,
+    SrcOut,
+    DstOut,
+    SrcAtop,
+    DstAtop,
+    Xor,
+    Plus,
+
+    // separable blend modes.
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+
+    // non-separable blend modes.
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl BlendMode {
+    /// whether a transparent source changes the destination.
+    pub fn is_unbounded(self) -> bool {
+        use BlendMode::*;
+        match self {
+            Clear | Src | SrcIn | DstIn | SrcOut | DstAtop => true,
+            _ => false,
+        }
+    }
+}
+
+
+/// a blend mode, that's known at compile time.
+/// - kernels are generic over it, so the per-pixel `composite`
+///   call is inlined for each mode, instead of matching on the mode.
+///   see `with_blend_mode!`.
+pub trait StaticBlend: Copy {
+    const MODE: BlendMode;
+}
+
+/// the `StaticBlend` types.
+pub mod modes {
+    use super::{BlendMode, StaticBlend};
+
+    #[derive(Clone, Copy)] pub struct Clear;
+    #[derive(Clone, Copy)] pub struct Src;
+    #[derive(Clone, Copy)] pub struct Dst;
+    #[derive(Clone, Copy)] pub struct SrcOver;
+    #[derive(Clone, Copy)] pub struct DstOver;
+    #[derive(Clone, Copy)] pub struct SrcIn;
+    #[derive(Clone, Copy)] pub struct DstIn;
+    #[derive(Clone, Copy)] pub struct SrcOut;
+    #[derive(Clone, Copy)] pub struct DstOut;
+    #[derive(Clone, Copy)] pub struct SrcAtop;
+    #[derive(Clone, Copy)] pub struct DstAtop;
+    #[derive(Clone, Copy)] pub struct Xor;
+    #[derive(Clone, Copy)] pub struct Plus;
+    #[derive(Clone, Copy)] pub struct Multiply;
+    #[derive(Clone, Copy)] pub struct Screen;
+    #[derive(Clone, Copy)] pub struct Overlay;
+    #[derive(Clone, Copy)] pub struct Darken;
+    #[derive(Clone, Copy)] pub struct Lighten;
+    #[derive(Clone, Copy)] pub struct ColorDodge;
+    #[derive(Clone, Copy)] pub struct ColorBurn;
+    #[derive(Clone, Copy)] pub struct HardLight;
+    #[derive(Clone, Copy)] pub struct SoftLight;
+    #[derive(Clone, Copy)] pub struct Difference;
+    #[derive(Clone, Copy)] pub struct Exclusion;
+    #[derive(Clone, Copy)] pub struct Hue;
+    #[derive(Clone, Copy)] pub struct Saturation;
+    #[derive(Clone, Copy)] pub struct Color;
+    #[derive(Clone, Copy)] pub struct Luminosity;
+
+    impl StaticBlend for Clear      { const MODE: BlendMode = BlendMode::Clear; }
+    impl StaticBlend for Src        { const MODE: BlendMode = BlendMode::Src; }
+    impl StaticBlend for Dst        { const MODE: BlendMode = BlendMode::Dst; }
+    impl StaticBlend for SrcOver    { const MODE: BlendMode = BlendMode::SrcOver; }
+    impl StaticBlend for DstOver    { const MODE: BlendMode = BlendMode::DstOver; }
+    impl StaticBlend for SrcIn      { const MODE: BlendMode = BlendMode::SrcIn; }
+    impl StaticBlend for DstIn      { const MODE: BlendMode = BlendMode::DstIn; }
+    impl StaticBlend for SrcOut     { const MODE: BlendMode = BlendMode::SrcOut; }
+    impl StaticBlend for DstOut     { const MODE: BlendMode = BlendMode::DstOut; }
+    impl StaticBlend for SrcAtop    { const MODE: BlendMode = BlendMode::SrcAtop; }
+    impl StaticBlend for DstAtop    { const MODE: BlendMode = BlendMode::DstAtop; }
+    impl StaticBlend for Xor        { const MODE: BlendMode = BlendMode::Xor; }
+    impl StaticBlend for Plus       { const MODE: BlendMode = BlendMode::Plus; }
+    impl StaticBlend for Multiply   { const MODE: BlendMode = BlendMode::Multiply; }
+    impl StaticBlend for Screen     { const MODE: BlendMode = BlendMode::Screen; }
+    impl StaticBlend for Overlay    { const MODE: BlendMode = BlendMode::Overlay; }
+    impl StaticBlend for Darken     { const MODE: BlendMode = BlendMode::Darken; }
+    impl StaticBlend for Lighten    { const MODE: BlendMode = BlendMode::Lighten; }
+    impl StaticBlend for ColorDodge { const MODE: BlendMode = BlendMode::ColorDodge; }
+    impl StaticBlend for ColorBurn  { const MODE: BlendMode = BlendMode::ColorBurn; }
+    impl StaticBlend for HardLight  { const MODE: BlendMode = BlendMode::HardLight; }
+    impl StaticBlend for SoftLight  { const MODE: BlendMode = BlendMode::SoftLight; }
+    impl StaticBlend for Difference { const MODE: BlendMode = BlendMode::Difference; }
+    impl StaticBlend for Exclusion  { const MODE: BlendMode = BlendMode::Exclusion; }
+    impl StaticBlend for Hue        { const MODE: BlendMode = BlendMode::Hue; }
+    impl StaticBlend for Saturation { const MODE: BlendMode = BlendMode::Saturation; }
+    impl StaticBlend for Color      { const MODE: BlendMode = BlendMode::Color; }
+    impl StaticBlend for Luminosity { const MODE: BlendMode = BlendMode::Luminosity; }
+}
+
+/// evaluates `$body` with the type `$b` set to the `StaticBlend` for `$mode`.
+macro_rules! with_blend_mode {
+    ($mode:expr, $b:ident => $body:expr) => {
+        match $mode {
+            $crate::blend::BlendMode::Clear      => { type $b = $crate::blend::modes::Clear;      $body }
+            $crate::blend::BlendMode::Src        => { type $b = $crate::blend::modes::Src;        $body }
+            $crate::blend::BlendMode::Dst        => { type $b = $crate::blend::modes::Dst;        $body }
+            $crate::blend::BlendMode::SrcOver    => { type $b = $crate::blend::modes::SrcOver;    $body }
+            $crate::blend::BlendMode::DstOver    => { type $b = $crate::blend::modes::DstOver;    $body }
+            $crate::blend::BlendMode::SrcIn      => { type $b = $crate::blend::modes::SrcIn;      $body }
+            $crate::blend::BlendMode::DstIn      => { type $b = $crate::blend::modes::DstIn;      $body }
+            $crate::blend::BlendMode::SrcOut     => { type $b = $crate::blend::modes::SrcOut;     $body }
+            $crate::blend::BlendMode::DstOut     => { type $b = $crate::blend::modes::DstOut;     $body }
+            $crate::blend::BlendMode::SrcAtop    => { type $b = $crate::blend::modes::SrcAtop;    $body }
+            $crate::blend::BlendMode::DstAtop    => { type $b = $crate::blend::modes::DstAtop;    $body }
+            $crate::blend::BlendMode::Xor        => { type $b = $crate::blend::modes::Xor;        $body }
+            $crate::blend::BlendMode::Plus       => { type $b = $crate::blend::modes::Plus;       $body }
+            $crate::blend::BlendMode::Multiply   => { type $b = $crate::blend::modes::Multiply;   $body }
+            $crate::blend::BlendMode::Screen     => { type $b = $crate::blend::modes::Screen;     $body }
+            $crate::blend::BlendMode::Overlay    => { type $b = $crate::blend::modes::Overlay;    $body }
+            $crate::blend::BlendMode::Darken     => { type $b = $crate::blend::modes::Darken;     $body }
+            $crate::blend::BlendMode::Lighten    => { type $b = $crate::blend::modes::Lighten;    $body }
+            $crate::blend::BlendMode::ColorDodge => { type $b = $crate::blend::modes::ColorDodge; $body }
+            $crate::blend::BlendMode::ColorBurn  => { type $b = $crate::blend::modes::ColorBurn;  $body }
+            $crate::blend::BlendMode::HardLight  => { type $b = $crate::blend::modes::HardLight;  $body }
+            $crate::blend::BlendMode::SoftLight  => { type $b = $crate::blend::modes::SoftLight;  $body }
+            $crate::blend::BlendMode::Difference => { type $b = $crate::blend::modes::Difference; $body }
+            $crate::blend::BlendMode::Exclusion  => { type $b = $crate::blend::modes::Exclusion;  $body }
+            $crate::blend::BlendMode::Hue        => { type $b = $crate::blend::modes::Hue;        $body }
+            $crate::blend::BlendMode::Saturation => { type $b = $crate::blend::modes::Saturation; $body }
+            $crate::blend::BlendMode::Color      => { type $b = $crate::blend::modes::Color;      $body }
+            $crate::blend::BlendMode::Luminosity => { type $b = $crate::blend::modes::Luminosity; $body }
+        }
+    };
+}
+pub(crate) use with_blend_mode;
+
+
+/// - `coverage` interpolates between `dst` and the blend result.
+/// - input pre-multiplied alpha: yes.
+#[inline(always)]
+pub fn composite<const N: usize>(mode: BlendMode, src: [F32x<N>; 4], dst: [F32x<N>; 4], coverage: F32x<N>) -> [F32x<N>; 4]
+where (): SimdLanes<N>
+{
+    // fast path.
+    if mode == BlendMode::SrcOver {
+        let [sr, sg, sb, sa] = src;
+        let [dr, dg, db, da] = dst;
+
+        let sr = sr * coverage;
+        let sg = sg * coverage;
+        let sb = sb * coverage;
+        let sa = sa * coverage;
+
+        let one = F32x::splat(1.0);
+        return [
+            sr + (one - sa)*dr,
+            sg + (one - sa)*dg,
+            sb + (one - sa)*db,
+            sa + (one - sa)*da,
+        ];
+    }
+
+    let b = blend(mode, src, dst);
+    core::array::from_fn(|i| dst[i] + (b[i] - dst[i])*coverage)
+}
+
+
+/// - input pre-multiplied alpha: yes.
+#[inline(always)]
+pub fn blend<const N: usize>(mode: BlendMode, src: [F32x<N>; 4], dst: [F32x<N>; 4]) -> [F32x<N>; 4]
+where (): SimdLanes<N>
+{
+    use BlendMode::*;
+
+    let [sr, sg, sb, sa] = src;
+    let [dr, dg, db, da] = dst;
+
+    let zero = F32x::ZERO();
+    let one  = F32x::ONE();
+
+    // `s*fs + d*fd`.
+    let porter_duff = |fs: F32x<N>, fd: F32x<N>| -> [F32x<N>; 4] {
+        [sr*fs + dr*fd,
+         sg*fs + dg*fd,
+         sb*fs + db*fd,
+         sa*fs + da*fd]
+    };
+
+    match mode {
+        Clear   => [zero; 4],
+        Src     => src,
+        Dst     => dst,
+        SrcOver => porter_duff(one,      one - sa),
+        DstOver => porter_duff(one - da, one),
+        SrcIn   => porter_duff(da,       zero),
+        DstIn   => porter_duff(zero,     sa),
+        SrcOut  => porter_duff(one - da, zero),
+        DstOut  => porter_duff(zero,     one - sa),
+        SrcAtop => porter_duff(da,       one - sa),
+        DstAtop => porter_duff(one - da, sa),
+        Xor     => porter_duff(one - da, one - sa),
+        Plus    => core::array::from_fn(|i| (src[i] + dst[i]).min(one)),
+
+        Multiply => separable(src, dst, |s, d| s*d),
+
+        Screen => {
+            core::array::from_fn(|i| src[i] + dst[i] - src[i]*dst[i])
+        }
+
+        Overlay => separable(src, dst, |s, d| hard_light(d, da, s, sa)),
+
+        Darken => {
+            let c = |s: F32x<N>, d: F32x<N>| s + d - (s*da).max(d*sa);
+            [c(sr, dr), c(sg, dg), c(sb, db), sa + da - sa*da]
+        }
+
+        Lighten => {
+            let c = |s: F32x<N>, d: F32x<N>| s + d - (s*da).min(d*sa);
+            [c(sr, dr), c(sg, dg), c(sb, db), sa + da - sa*da]
+        }
+
+        ColorDodge => separable(src, dst, |s, d| {
+            let cs = unpremultiply(s, sa);
+            let cd = unpremultiply(d, da);
+            let b = (cd / (one - cs)).min(one);
+            let b = cs.ge(one).select(one, b);
+            let b = cd.le(zero).select(zero, b);
+            sa*da*b
+        }),
+
+        ColorBurn => separable(src, dst, |s, d| {
+            let cs = unpremultiply(s, sa);
+            let cd = unpremultiply(d, da);
+            let b = one - ((one - cd) / cs).min(one);
+            let b = cs.le(zero).select(zero, b);
+            let b = cd.ge(one).select(one, b);
+            sa*da*b
+        }),
+
+        HardLight => separable(src, dst, |s, d| hard_light(s, sa, d, da)),
+
+        SoftLight => separable(src, dst, |s, d| {
+            let cs = unpremultiply(s, sa);
+            let cd = unpremultiply(d, da);
+
+            let dark = ((F32x::splat(16.0)*cd - F32x::splat(12.0))*cd + F32x::splat(4.0))*cd;
+            let dd = cd.le(F32x::splat(0.25)).select(dark, cd.sqrt());
+
+            let two = F32x::splat(2.0);
+            let b = cs.le(F32x::splat(0.5)).select(
+                cd - (one - two*cs)*cd*(one - cd),
+                cd + (two*cs - one)*(dd - cd));
+            sa*da*b
+        }),
+
+        Difference => {
+            let two = F32x::splat(2.0);
+            let c = |s: F32x<N>, d: F32x<N>| s + d - two*(s*da).min(d*sa);
+            [c(sr, dr), c(sg, dg), c(sb, db), sa + da - sa*da]
+        }
+
+        Exclusion => {
+            let two = F32x::splat(2.0);
+            let c = |s: F32x<N>, d: F32x<N>| s + d - two*s*d;
+            [c(sr, dr), c(sg, dg), c(sb, db), sa + da - sa*da]
+        }
+
+        Hue | Saturation | Color | Luminosity => {
+            let cs = [unpremultiply(sr, sa), unpremultiply(sg, sa), unpremultiply(sb, sa)];
+            let cd = [unpremultiply(dr, da), unpremultiply(dg, da), unpremultiply(db, da)];
+
+            let b = match mode {
+                Hue        => set_lum(set_sat(cs, sat(cd)), lum(cd)),
+                Saturation => set_lum(set_sat(cd, sat(cs)), lum(cd)),
+                Color      => set_lum(cs, lum(cd)),
+                Luminosity => set_lum(cd, lum(cs)),
+                _ => unreachable!(),
+            };
+
+            let ao = sa + da - sa*da;
+            let c = |s: F32x<N>, d: F32x<N>, b: F32x<N>| s*(one - da) + d*(one - sa) + sa*da*b;
+            [c(sr, dr, b[0]), c(sg, dg, b[1]), c(sb, db, b[2]), ao]
+        }
+    }
+}
+
+
+/// source-over with the blended color.
+///  `co = s*(1 - da) + d*(1 - sa) + f(s, d)`, where `f` is pre-multiplied.
+#[inline(always)]
+fn separable<const N: usize, F: Fn(F32x<N>, F32x<N>) -> F32x<N>>(src: [F32x<N>; 4], dst: [F32x<N>; 4], f: F) -> [F32x<N>; 4]
+where (): SimdLanes<N>
+{
+    let [sr, sg, sb, sa] = src;
+    let [dr, dg, db, da] = dst;
+
+    let one = F32x::ONE();
+    let ao = sa + da - sa*da;
+    let channel = |s: F32x<N>, d: F32x<N>| s*(one - da) + d*(one - sa) + f(s, d);
+    [channel(sr, dr), channel(sg, dg), channel(sb, db), ao]
+}
+
+#[inline(always)]
+pub(crate) fn unpremultiply<const N: usize>(c: F32x<N>, a: F32x<N>) -> F32x<N>  where (): SimdLanes<N> {
+    a.gt(F32x::ZERO()).select(c / a, F32x::ZERO())
+}
+
+/// pre-multiplied hard light of `s` onto `d`.
+#[inline(always)]
+fn hard_light<const N: usize>(s: F32x<N>, sa: F32x<N>, d: F32x<N>, da: F32x<N>) -> F32x<N>  where (): SimdLanes<N> {
+    let two = F32x::splat(2.0);
+    (two*s).le(sa).select(
+        two*s*d,
+        sa*da - two*(da - d)*(sa - s))
+}
+
+
+#[inline(always)]
+fn lum<const N: usize>(c: [F32x<N>; 3]) -> F32x<N>  where (): SimdLanes<N> {
+    F32x::splat(0.3)*c[0] + F32x::splat(0.59)*c[1] + F32x::splat(0.11)*c[2]
+}
+
+#[inline(always)]
+fn sat<const N: usize>(c: [F32x<N>; 3]) -> F32x<N>  where (): SimdLanes<N> {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+#[inline(always)]
+fn set_lum<const N: usize>(c: [F32x<N>; 3], l: F32x<N>) -> [F32x<N>; 3]  where (): SimdLanes<N> {
+    let d = l - lum(c);
+    clip_color([c[0] + d, c[1] + d, c[2] + d])
+}
+
+#[inline(always)]
+fn clip_color<const N: usize>(c: [F32x<N>; 3]) -> [F32x<N>; 3]  where (): SimdLanes<N> {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+
+    let zero = F32x::ZERO();
+    let one  = F32x::ONE();
+
+    let below = n.lt(zero) & (l - n).gt(zero);
+    let above = x.gt(one)  & (x - l).gt(zero);
+
+    c.map(|c| {
+        let c = below.select(l + (c - l)*l / (l - n), c);
+        above.select(l + (c - l)*(one - l) / (x - l), c)
+    })
+}
+
+#[inline(always)]
+fn set_sat<const N: usize>(c: [F32x<N>; 3], s: F32x<N>) -> [F32x<N>; 3]  where (): SimdLanes<N> {
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+
+    // maps max to `s`, min to zero, and mid proportionally.
+    let range = x - n;
+    let valid = range.gt(F32x::ZERO());
+    c.map(|c| valid.select((c - n)*s / range, F32x::ZERO()))
+}
@@ -0,0 +1,162 @@
+use sti::simd::*;
+
+use crate::image::*;
+
+
+/*  gaussian blurs are approximated by three successive box blurs,
+    each of which is separable into a horizontal and a vertical pass.
+    the box blurs use a running sum, so their cost doesn't depend on the radius.
+
+    values outside of the image are treated as zero.
+    that's correct for coverage masks and pre-multiplied colors.
+*/
+
+
+/// radii of the three box blurs, that approximate a gaussian
+/// with standard deviation `sigma`.
+pub fn gaussian_box_radii(sigma: f32) -> [u32; 3] {
+    if sigma.is_nan() || sigma <= 0.0 {
+        return [0; 3];
+    }
+
+    // "fast almost-gaussian filtering", kovesi.
+    let n = 3.0;
+    let w_ideal = (12.0*sigma*sigma/n + 1.0).sqrt();
+    let mut wl = w_ideal.floor() as i32;
+    if wl % 2 == 0 { wl -= 1 }
+    let wu = wl + 2;
+
+    let wl_f = wl as f32;
+    let m_ideal = (12.0*sigma*sigma - n*wl_f*wl_f - 4.0*n*wl_f - 3.0*n) / (-4.0*wl_f - 4.0);
+    let m = m_ideal.round() as i32;
+
+    [0, 1, 2].map(|i| {
+        let w = if i < m { wl } else { wu };
+        ((w - 1) / 2).max(0) as u32
+    })
+}
+
+/// the distance, over which a blur with `radii` spreads.
+#[inline]
+pub fn blur_extent(radii: [u32; 3]) -> u32 {
+    radii[0] + radii[1] + radii[2]
+}
+
+
+/// blurs the coverage `mask` in place.
+pub fn blur_mask(mask: &mut ImgMut<f32>, radii: [u32; 3], line: &mut Vec<f32>) {
+    let w = mask.width()  as usize;
+    let h = mask.height() as usize;
+    let stride = mask.stride();
+    let data = mask.data_mut();
+
+    for r in radii {
+        let r = r as usize;
+        if r == 0 { continue }
+
+        for y in 0..h {
+            box_blur_f32(data, y*stride, w, 1, r, line);
+        }
+        for x in 0..w {
+            box_blur_f32(data, x, h, stride, r, line);
+        }
+    }
+}
+
+/// blurs the pre-multiplied `layer` in place.
+/// - the vertical passes process `N` pixels at a time.
+/// - `channels` hold the de-interleaved rows of the horizontal passes.
+pub fn blur_layer<const N: usize>(layer: &mut ImgMut<[F32x<N>; 4]>, radii: [u32; 3], line: &mut Vec<f32>, simd_line: &mut Vec<[F32x<N>; 4]>, channels: &mut [Vec<f32>; 4])
+where (): SimdLanes<N>
+{
+    let w = layer.width()  as usize;
+    let h = layer.height() as usize;
+
+    for r in radii {
+        let r = r as usize;
+        if r == 0 { continue }
+
+        // horizontal, on de-interleaved channels.
+        for y in 0..h {
+            for (c, channel) in channels.iter_mut().enumerate() {
+                channel.clear();
+                for u in 0..w {
+                    channel.extend_from_slice(&*layer[(u, y)][c]);
+                }
+                box_blur_f32(channel, 0, w*N, 1, r, line);
+            }
+
+            for u in 0..w {
+                layer[(u, y)] = core::array::from_fn(|c|
+                    F32x::from_array(core::array::from_fn(|i| channels[c][u*N + i])));
+            }
+        }
+
+        // vertical.
+        for u in 0..w {
+            box_blur_column(layer, u, r, simd_line);
+        }
+    }
+}
+
+
+/// box blur with radius `r` of the `n` values in `data`,
+/// starting at `start`, spaced `stride` apart.
+fn box_blur_f32(data: &mut [f32], start: usize, n: usize, stride: usize, r: usize, src: &mut Vec<f32>) {
+    if n == 0 { return }
+
+    src.clear();
+    for i in 0..n {
+        src.push(data[start + i*stride]);
+    }
+
+    let scale = 1.0 / (2*r + 1) as f32;
+
+    let mut sum = 0.0;
+    for v in &src[..(r + 1).min(n)] {
+        sum += v;
+    }
+
+    for i in 0..n {
+        data[start + i*stride] = (sum*scale).max(0.0);
+
+        if i + r + 1 < n { sum += src[i + r + 1] }
+        if i >= r        { sum -= src[i - r] }
+    }
+}
+
+fn box_blur_column<const N: usize>(layer: &mut ImgMut<[F32x<N>; 4]>, u: usize, r: usize, src: &mut Vec<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let n = layer.height() as usize;
+    if n == 0 { return }
+
+    src.clear();
+    for y in 0..n {
+        src.push(layer[(u, y)]);
+    }
+
+    let scale = F32x::splat(1.0 / (2*r + 1) as f32);
+
+    let mut sum = [F32x::ZERO(); 4];
+    for v in &src[..(r + 1).min(n)] {
+        sum = add(sum, *v);
+    }
+
+    for y in 0..n {
+        layer[(u, y)] = sum.map(|s| (s*scale).at_least(F32x::ZERO()));
+
+        if y + r + 1 < n { sum = add(sum, src[y + r + 1]) }
+        if y >= r        { sum = sub(sum, src[y - r]) }
+    }
+}
+
+#[inline(always)]
+fn add<const N: usize>(a: [F32x<N>; 4], b: [F32x<N>; 4]) -> [F32x<N>; 4]  where (): SimdLanes<N> {
+    core::array::from_fn(|c| a[c] + b[c])
+}
+
+#[inline(always)]
+fn sub<const N: usize>(a: [F32x<N>; 4], b: [F32x<N>; 4]) -> [F32x<N>; 4]  where (): SimdLanes<N> {
+    core::array::from_fn(|c| a[c] - b[c])
+}
@@ -5,14 +5,55 @@ use sti::simd::*;
 
 use crate::geometry::Transform;
 use crate::path::{Path, PathBuilder};
+use crate::stroke::StrokeStyle;
+use crate::rasterizer::FillRule;
+use crate::blend::BlendMode;
+use crate::filter::{Filter, FilterPrimitive, FilterInput};
+use crate::font::Font;
 
 
 #[derive(Clone, Copy, Debug)]
 pub enum Cmd<'a> {
-    FillPathSolid   { path: Path<'a>, color: u32 },
-    FillPathLinearGradient { path: Path<'a>, gradient: LinearGradientId, opacity: f32 },
-    FillPathRadialGradient { path: Path<'a>, gradient: RadialGradientId, opacity: f32 },
-    StrokePathSolid { path: Path<'a>, color: u32, width: f32 },
+    FillPath   { path: Path<'a>, fill_rule: FillRule,    paint: Paint, opacity: f32, blend: BlendMode },
+    StrokePath { path: Path<'a>, style: StrokeStyle<'a>, paint: Paint, opacity: f32, blend: BlendMode },
+
+    /// fills `path`, moved by `offset` and blurred, with `color`.
+    /// - `offset` and `blur_radius` are in path coordinates.
+    /// - the blur's standard deviation is `blur_radius/2`, like css.
+    DropShadow { path: Path<'a>, offset: F32x2, blur_radius: f32, color: u32 },
+
+    /// blurs the current layer, within the current clip.
+    BlurLayer { blur_radius: f32 },
+
+    /// concatenates `tfx` with the current transform, until the matching `PopTransform`.
+    PushTransform { tfx: Transform },
+    PopTransform,
+
+    /// intersects the current clip with `path`, until the matching `PopClip`.
+    PushClipPath { path: Path<'a>, fill_rule: FillRule },
+    PopClip,
+
+    /// the commands until the matching `EndGroup` are rendered into
+    /// a separate layer, which is then composited as a unit.
+    /// - the `filter` is applied to the layer before compositing.
+    ///   filtered layers cover the entire current clip.
+    BeginGroup { opacity: f32, blend: BlendMode, filter: Option<FilterId> },
+    EndGroup,
+}
+
+
+
+/// what a command fills its coverage with.
+#[derive(Clone, Copy, Debug)]
+pub enum Paint {
+    /// argb, *not* pre-multiplied.
+    Solid(u32),
+    LinearGradient(LinearGradientId),
+    RadialGradient(RadialGradientId),
+    ConicGradient(ConicGradientId),
+
+    /// `tfx` maps image pixel coordinates to path coordinates.
+    Image { image: ImageId, tfx: Transform, sampling: ImageSampling, extend: ImageExtend },
 }
 
 
@@ -33,11 +74,43 @@ pub enum SpreadMethod {
 
 #[derive(Clone, Copy, Debug)]
 pub enum GradientUnits {
+    /// in path coordinates.
     Absolute,
+
+    /// in the unit square of the filled path's `aabb()`.
     Relative,
 }
 
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageSampling {
+    Nearest,
+    Bilinear,
+    Bicubic,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageExtend {
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+
+sti::define_key!(u32, pub ImageId);
+
+#[derive(Clone, Debug)]
+pub struct ImageSource<'a> {
+    pub size:   [u32; 2],
+
+    /// row major, argb, *not* pre-multiplied.
+    pub pixels: &'a [u32],
+}
+
+
+sti::define_key!(u32, pub FilterId);
+
+
 sti::define_key!(u32, pub LinearGradientId);
 
 #[derive(Clone, Debug)]
@@ -66,6 +139,23 @@ pub struct RadialGradient<'a> {
 }
 
 
+sti::define_key!(u32, pub ConicGradientId);
+
+/// sweeps the stops around `center`.
+/// - angles are in radians, measured from the positive x axis towards the positive y axis.
+/// - offset 0 is at `start_angle`, offset 1 at `start_angle + angle_range`.
+#[derive(Clone, Debug)]
+pub struct ConicGradient<'a> {
+    pub center:      F32x2,
+    pub start_angle: f32,
+    pub angle_range: f32,
+    pub spread: SpreadMethod,
+    pub units:  GradientUnits,
+    pub tfx:    Transform,
+    pub stops:  &'a [GradientStop],
+}
+
+
 pub struct CmdBuf {
     // this doesn't need to be boxed, cause the user can't create
     // persistent refs to it.
@@ -76,8 +166,15 @@ pub struct CmdBuf {
 
     linear_gradients: KVec<LinearGradientId, LinearGradient<'static>>,
     radial_gradients: KVec<RadialGradientId, RadialGradient<'static>>,
+    conic_gradients:  KVec<ConicGradientId,  ConicGradient<'static>>,
+    images:           KVec<ImageId, ImageSource<'static>>,
+    filters:          KVec<FilterId, Filter<'static>>,
 }
 
+// the arena is only used during construction,
+// the command buffer is immutable after that.
+unsafe impl Sync for CmdBuf {}
+
 impl CmdBuf {
     pub fn new<F: FnOnce(&mut CmdBufBuilder)>(f: F) -> Self {
         let arena = Box::new(Arena::new());
@@ -86,8 +183,14 @@ impl CmdBuf {
             arena: arena.as_ref(),
             path_builder: PathBuilder::new(),
             gradient_stops_builder: Vec::new(),
+            dashes_builder: Vec::new(),
+            image_pixels_builder: Vec::new(),
+            filter_primitives_builder: Vec::new(),
             linear_gradients: KVec::new(),
             radial_gradients: KVec::new(),
+            conic_gradients: KVec::new(),
+            images: KVec::new(),
+            filters: KVec::new(),
             cmds: Vec::new(),
         };
 
@@ -100,6 +203,9 @@ impl CmdBuf {
             cmds: builder.cmds,
             linear_gradients: builder.linear_gradients,
             radial_gradients: builder.radial_gradients,
+            conic_gradients: builder.conic_gradients,
+            images: builder.images,
+            filters: builder.filters,
         }
     }
 
@@ -122,6 +228,47 @@ impl CmdBuf {
     pub fn radial_gradient(&self, id: RadialGradientId) -> &RadialGradient {
         &self.radial_gradients[id]
     }
+
+    #[inline(always)]
+    pub fn conic_gradient(&self, id: ConicGradientId) -> &ConicGradient {
+        &self.conic_gradients[id]
+    }
+
+    #[inline(always)]
+    pub fn image(&self, id: ImageId) -> &ImageSource {
+        &self.images[id]
+    }
+
+    #[inline(always)]
+    pub fn filter(&self, id: FilterId) -> &Filter {
+        &self.filters[id]
+    }
+
+
+    #[inline(always)]
+    pub fn images(&self) -> &[ImageSource] {
+        self.images.inner()
+    }
+
+    #[inline(always)]
+    pub fn linear_gradients(&self) -> &[LinearGradient] {
+        self.linear_gradients.inner()
+    }
+
+    #[inline(always)]
+    pub fn radial_gradients(&self) -> &[RadialGradient] {
+        self.radial_gradients.inner()
+    }
+
+    #[inline(always)]
+    pub fn conic_gradients(&self) -> &[ConicGradient] {
+        self.conic_gradients.inner()
+    }
+
+    #[inline(always)]
+    pub fn filters(&self) -> &[Filter] {
+        self.filters.inner()
+    }
 }
 
 
@@ -132,8 +279,14 @@ pub struct CmdBufBuilder<'a> {
     path_builder: PathBuilder,
 
     gradient_stops_builder: Vec<GradientStop>,
+    dashes_builder: Vec<f32>,
+    image_pixels_builder: Vec<u32>,
+    filter_primitives_builder: Vec<FilterPrimitive<'a>>,
     linear_gradients: KVec<LinearGradientId, LinearGradient<'a>>,
     radial_gradients: KVec<RadialGradientId, RadialGradient<'a>>,
+    conic_gradients: KVec<ConicGradientId, ConicGradient<'a>>,
+    images: KVec<ImageId, ImageSource<'a>>,
+    filters: KVec<FilterId, Filter<'a>>,
 
     cmds: Vec<Cmd<'a>>,
 }
@@ -156,6 +309,27 @@ impl<'a> CmdBufBuilder<'a> {
         Vec::leak(self.gradient_stops_builder.clone_in(self.arena))
     }
 
+    #[inline(always)]
+    pub fn build_dashes<F: FnOnce(&mut Vec<f32>)>(&mut self, f: F) -> &'a [f32] {
+        self.dashes_builder.clear();
+        f(&mut self.dashes_builder);
+        Vec::leak(self.dashes_builder.clone_in(self.arena))
+    }
+
+    #[inline(always)]
+    pub fn build_image_pixels<F: FnOnce(&mut Vec<u32>)>(&mut self, f: F) -> &'a [u32] {
+        self.image_pixels_builder.clear();
+        f(&mut self.image_pixels_builder);
+        Vec::leak(self.image_pixels_builder.clone_in(self.arena))
+    }
+
+    #[inline(always)]
+    pub fn build_filter_primitives<F: FnOnce(&mut Vec<FilterPrimitive<'a>>)>(&mut self, f: F) -> &'a [FilterPrimitive<'a>] {
+        self.filter_primitives_builder.clear();
+        f(&mut self.filter_primitives_builder);
+        Vec::leak(self.filter_primitives_builder.clone_in(self.arena))
+    }
+
     #[inline(always)]
     pub fn push_linear_gradient(&mut self, gradient: LinearGradient<'a>) -> LinearGradientId {
         self.linear_gradients.push(gradient)
@@ -166,10 +340,45 @@ impl<'a> CmdBufBuilder<'a> {
         self.radial_gradients.push(gradient)
     }
 
+    #[inline(always)]
+    pub fn push_conic_gradient(&mut self, gradient: ConicGradient<'a>) -> ConicGradientId {
+        self.conic_gradients.push(gradient)
+    }
+
+    #[inline(always)]
+    pub fn push_image(&mut self, image: ImageSource<'a>) -> ImageId {
+        assert!(image.pixels.len() == image.size[0] as usize * image.size[1] as usize);
+        self.images.push(image)
+    }
+
+    pub fn push_filter(&mut self, filter: Filter<'a>) -> FilterId {
+        // the primitives must form a dag.
+        for (i, primitive) in filter.primitives.iter().enumerate() {
+            primitive.for_each_input(|input| {
+                if let FilterInput::Result(j) = input {
+                    assert!((j as usize) < i);
+                }
+            });
+        }
+        self.filters.push(filter)
+    }
+
     #[inline(always)]
     pub fn push(&mut self, cmd: Cmd<'a>) {
         self.cmds.push(cmd);
     }
+
+    /// fills the glyphs of `text` as a single path.
+    /// - see `Font::text_outline`.
+    /// - returns the advance of the run.
+    pub fn push_text(&mut self, font: &Font, text: &str, origin: F32x2, size: f32, paint: Paint, opacity: f32, blend: BlendMode) -> f32 {
+        let mut advance = 0.0;
+        let path = self.build_path(|pb| {
+            advance = font.text_outline(text, origin, size, pb);
+        });
+        self.push(Cmd::FillPath { path, fill_rule: FillRule::NonZero, paint, opacity, blend });
+        advance
+    }
 }
 
 
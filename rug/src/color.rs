@@ -123,3 +123,98 @@ pub fn abgr_u8x_pack<const N: usize>(v: [F32x<N>; 4]) -> U32x<N> where (): SimdL
 }
 
 
+#[inline(always)]
+pub fn argb_u8x_pack<const N: usize>(v: [F32x<N>; 4]) -> U32x<N> where (): SimdLanes<N> {
+    let [r, g, b, a] = v;
+    // same as `abgr_u8x_pack` with red & blue swapped.
+    abgr_u8x_pack([b, g, r, a])
+}
+
+/// packs to `0bRRRRRGGGGGGBBBBB`.
+#[inline(always)]
+pub fn rgb565_u16x_pack<const N: usize>(v: [F32x<N>; 4]) -> [u16; N] where (): SimdLanes<N> {
+    let offset = F32x::splat(0.5);
+    let min = F32x::splat(0.0);
+    let [r, g, b, _] = v;
+    let r = unsafe { (F32x::splat(31.0)*r + offset).clamp(min, F32x::splat(31.0)).to_i32_unck() };
+    let g = unsafe { (F32x::splat(63.0)*g + offset).clamp(min, F32x::splat(63.0)).to_i32_unck() };
+    let b = unsafe { (F32x::splat(31.0)*b + offset).clamp(min, F32x::splat(31.0)).to_i32_unck() };
+    let v = (r << 11) | (g << 5) | b;
+    (*v).map(|v| v as u16)
+}
+
+#[inline(always)]
+pub fn a8_u8x_pack<const N: usize>(v: [F32x<N>; 4]) -> [u8; N] where (): SimdLanes<N> {
+    let a = v[3];
+    let a = unsafe { (F32x::splat(255.0)*a + F32x::splat(0.5)).clamp(F32x::splat(0.0), F32x::splat(255.0)).to_i32_unck() };
+    (*a).map(|a| a as u8)
+}
+
+#[inline(always)]
+pub fn rgba_u16x_pack<const N: usize>(v: [F32x<N>; 4]) -> [[u16; 4]; N] where (): SimdLanes<N> {
+    let offset = F32x::splat(0.5);
+    let scale = F32x::splat(65535.0);
+    let min = F32x::splat(0.0);
+    let max = F32x::splat(65535.0);
+    let [r, g, b, a] = v.map(|c| unsafe { (scale*c + offset).clamp(min, max).to_i32_unck() });
+    core::array::from_fn(|i| [r[i] as u16, g[i] as u16, b[i] as u16, a[i] as u16])
+}
+
+#[inline(always)]
+pub fn rgba_f32x_pack<const N: usize>(v: [F32x<N>; 4]) -> [[f32; 4]; N] where (): SimdLanes<N> {
+    let [r, g, b, a] = v;
+    core::array::from_fn(|i| [r[i], g[i], b[i], a[i]])
+}
+
+
+#[inline(always)]
+pub fn abgr_u8x_unpack<const N: usize>(v: [u32; N]) -> [F32x<N>; 4] where (): SimdLanes<N> {
+    let v = U32x::from_array(v);
+    let mask = U32x::splat(0xff);
+    let a = (v >> 24) & mask;
+    let b = (v >> 16) & mask;
+    let g = (v >>  8) & mask;
+    let r = (v >>  0) & mask;
+
+    let scale = F32x::splat(255.0);
+    [r.as_i32().to_f32() / scale,
+     g.as_i32().to_f32() / scale,
+     b.as_i32().to_f32() / scale,
+     a.as_i32().to_f32() / scale]
+}
+
+#[inline(always)]
+pub fn argb_u8x_unpack<const N: usize>(v: [u32; N]) -> [F32x<N>; 4] where (): SimdLanes<N> {
+    let [b, g, r, a] = abgr_u8x_unpack(v);
+    [r, g, b, a]
+}
+
+#[inline(always)]
+pub fn rgb565_u16x_unpack<const N: usize>(v: [u16; N]) -> [F32x<N>; 4] where (): SimdLanes<N> {
+    let v = U32x::from_array(v.map(|v| v as u32));
+    let r = (v >> 11) & U32x::splat(31);
+    let g = (v >>  5) & U32x::splat(63);
+    let b = (v >>  0) & U32x::splat(31);
+    [r.as_i32().to_f32() / F32x::splat(31.0),
+     g.as_i32().to_f32() / F32x::splat(63.0),
+     b.as_i32().to_f32() / F32x::splat(31.0),
+     F32x::splat(1.0)]
+}
+
+#[inline(always)]
+pub fn a8_u8x_unpack<const N: usize>(v: [u8; N]) -> [F32x<N>; 4] where (): SimdLanes<N> {
+    let a = F32x::from_array(v.map(|a| a as f32)) / F32x::splat(255.0);
+    [F32x::splat(0.0), F32x::splat(0.0), F32x::splat(0.0), a]
+}
+
+#[inline(always)]
+pub fn rgba_u16x_unpack<const N: usize>(v: [[u16; 4]; N]) -> [F32x<N>; 4] where (): SimdLanes<N> {
+    core::array::from_fn(|c| F32x::from_array(v.map(|p| p[c] as f32)) / F32x::splat(65535.0))
+}
+
+#[inline(always)]
+pub fn rgba_f32x_unpack<const N: usize>(v: [[f32; 4]; N]) -> [F32x<N>; 4] where (): SimdLanes<N> {
+    core::array::from_fn(|c| F32x::from_array(v.map(|p| p[c])))
+}
+
+
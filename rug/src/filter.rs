@@ -0,0 +1,649 @@
+use sti::simd::*;
+
+use crate::geometry::Transform;
+use crate::image::*;
+use crate::color::argb_unpack_premultiply;
+use crate::blend::{BlendMode, blend, unpremultiply};
+use crate::blur::{gaussian_box_radii, blur_layer};
+use crate::renderer::tfx_scale;
+
+
+/*  filters are dags of primitives, modeled after svg's filter effects.
+
+    each primitive reads the group's layer, or the results of earlier
+    primitives, and produces an image of the same size as the layer.
+    the result of the last primitive replaces the layer, before it is
+    composited onto its parent.
+
+    images use the same pre-multiplied `[F32x<N>; 4]` layout as the
+    renderer's layers.
+    values outside of the layer are treated as transparent.
+*/
+
+
+/// a dag of filter primitives.
+/// - lengths are in the coordinates of the group, ie: they are
+///   transformed by the transform at its `BeginGroup`.
+#[derive(Clone, Copy, Debug)]
+pub struct Filter<'a> {
+    pub primitives: &'a [FilterPrimitive<'a>],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterInput {
+    /// the group's layer.
+    Source,
+
+    /// the alpha channel of the group's layer, with black color.
+    SourceAlpha,
+
+    /// the result of an earlier primitive.
+    Result(u32),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum FilterPrimitive<'a> {
+    /// `[r, g, b, a] = matrix * [r, g, b, a, 1]`, with a row major 4x5 `matrix`.
+    /// - input pre-multiplied alpha: no.
+    ColorMatrix { input: FilterInput, matrix: [f32; 20] },
+
+    /// maps the r, g, b, a channels through their respective function.
+    /// - input pre-multiplied alpha: no.
+    ComponentTransfer { input: FilterInput, functions: [TransferFunction<'a>; 4] },
+
+    /// blends `input` onto `input2`.
+    Composite { input: FilterInput, input2: FilterInput, blend: BlendMode },
+
+    /// `k[0]*i1*i2 + k[1]*i1 + k[2]*i2 + k[3]`, per channel.
+    /// - input pre-multiplied alpha: yes.
+    Arithmetic { input: FilterInput, input2: FilterInput, k: [f32; 4] },
+
+    /// max (dilate) or min (erode) over a `2*radius` rectangle.
+    /// - `radius` is rounded to whole pixels.
+    Morphology { input: FilterInput, op: MorphologyOp, radius: F32x2 },
+
+    /// - `offset` is rounded to whole pixels.
+    Offset { input: FilterInput, offset: F32x2 },
+
+    GaussianBlur { input: FilterInput, std_deviation: f32 },
+
+    /// source-over of the `inputs`, the first one at the bottom.
+    Merge { inputs: &'a [FilterInput] },
+
+    /// argb, *not* pre-multiplied.
+    Flood { color: u32 },
+
+    /// perlin noise, as specified by svg's `feTurbulence`.
+    /// - tiles are not stitched.
+    Turbulence { base_frequency: F32x2, num_octaves: u32, seed: i32, kind: TurbulenceKind },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum TransferFunction<'a> {
+    Identity,
+
+    /// piecewise linear interpolation between the `values`.
+    Table(&'a [f32]),
+
+    /// step function with the `values`.
+    Discrete(&'a [f32]),
+
+    Linear { slope: f32, intercept: f32 },
+
+    Gamma { amplitude: f32, exponent: f32, offset: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MorphologyOp {
+    Erode,
+    Dilate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TurbulenceKind {
+    FractalNoise,
+    Turbulence,
+}
+
+
+impl<'a> FilterPrimitive<'a> {
+    /// calls `f` for each input of the primitive.
+    pub fn for_each_input<F: FnMut(FilterInput)>(&self, mut f: F) {
+        use FilterPrimitive::*;
+        match *self {
+            ColorMatrix { input, .. } |
+            ComponentTransfer { input, .. } |
+            Morphology { input, .. } |
+            Offset { input, .. } |
+            GaussianBlur { input, .. } => f(input),
+
+            Composite  { input, input2, .. } |
+            Arithmetic { input, input2, .. } => {
+                f(input);
+                f(input2);
+            }
+
+            Merge { inputs } => {
+                for input in inputs {
+                    f(*input);
+                }
+            }
+
+            Flood { .. } |
+            Turbulence { .. } => (),
+        }
+    }
+}
+
+impl<'a> TransferFunction<'a> {
+    pub fn apply(&self, c: f32) -> f32 {
+        match *self {
+            TransferFunction::Identity => c,
+
+            TransferFunction::Table(values) => {
+                if values.len() < 2 {
+                    return values.first().copied().unwrap_or(c);
+                }
+
+                let n = values.len() - 1;
+                let x = c.clamp(0.0, 1.0) * n as f32;
+                let k = (x as usize).min(n - 1);
+                values[k] + (x - k as f32)*(values[k + 1] - values[k])
+            }
+
+            TransferFunction::Discrete(values) => {
+                if values.is_empty() {
+                    return c;
+                }
+
+                let n = values.len();
+                let k = ((c.clamp(0.0, 1.0) * n as f32) as usize).min(n - 1);
+                values[k]
+            }
+
+            TransferFunction::Linear { slope, intercept } => {
+                slope*c + intercept
+            }
+
+            TransferFunction::Gamma { amplitude, exponent, offset } => {
+                amplitude*c.powf(exponent) + offset
+            }
+        }
+    }
+}
+
+
+/// the allocations of `apply_filter`.
+pub struct FilterBuffers<const N: usize>  where (): SimdLanes<N> {
+    /// images, that aren't in use.
+    images:  Vec<Image<[F32x<N>; 4]>>,
+
+    // empty between filters.
+    results: Vec<Image<[F32x<N>; 4]>>,
+
+    source_alpha: Image<[F32x<N>; 4]>,
+
+    line:      Vec<f32>,
+    simd_line: Vec<[F32x<N>; 4]>,
+    channels:  [Vec<f32>; 4],
+}
+
+impl<const N: usize> FilterBuffers<N>  where (): SimdLanes<N> {
+    pub fn new() -> Self {
+        FilterBuffers {
+            images:  Vec::new(),
+            results: Vec::new(),
+            source_alpha: Image::with_clear([0, 0], [F32x::ZERO(); 4]),
+            line:      Vec::new(),
+            simd_line: Vec::new(),
+            channels:  Default::default(),
+        }
+    }
+
+    /// the number of bytes held by the buffers.
+    pub fn memory(&self) -> usize {
+        fn vec<T>(vec: &Vec<T>) -> usize {
+            vec.capacity() * core::mem::size_of::<T>()
+        }
+
+        vec(&self.images) + self.images.iter().map(|i| i.memory()).sum::<usize>()
+        + vec(&self.results)
+        + self.source_alpha.memory()
+        + vec(&self.line)
+        + vec(&self.simd_line)
+        + self.channels.iter().map(vec).sum::<usize>()
+    }
+}
+
+
+/// replaces `layer` with the result of `filter`.
+/// - `origin` is the position of `layer` in target pixels.
+/// - `tfx` maps group coordinates to target pixels.
+/// - the inputs of each primitive must refer to earlier primitives.
+/// - `layer`'s previous image is kept in `buffers`.
+pub fn apply_filter<const N: usize>(filter: &Filter, layer: &mut Image<[F32x<N>; 4]>, origin: U32x2, tfx: Transform, buffers: &mut FilterBuffers<N>)
+where (): SimdLanes<N>
+{
+    if filter.primitives.is_empty() {
+        return;
+    }
+
+    let FilterBuffers { images, results, source_alpha, line, simd_line, channels } = buffers;
+
+    let scale = tfx_scale(tfx);
+    let size = [layer.width(), layer.height()];
+
+    let zero = F32x::ZERO();
+    source_alpha.resize_and_clear(size, [zero; 4]);
+    map_pixels(layer, source_alpha, |[_, _, _, a]| [zero, zero, zero, a]);
+
+    for primitive in filter.primitives {
+        let mut result = images.pop().unwrap_or_else(|| Image::with_clear([0, 0], [zero; 4]));
+        result.resize_and_clear(size, [zero; 4]);
+
+        let get = |input: FilterInput| -> &Image<[F32x<N>; 4]> {
+            match input {
+                FilterInput::Source      => &*layer,
+                FilterInput::SourceAlpha => &*source_alpha,
+                FilterInput::Result(i)   => &results[i as usize],
+            }
+        };
+
+        match *primitive {
+            FilterPrimitive::ColorMatrix { input, matrix } => {
+                color_matrix(get(input), &matrix, &mut result);
+            }
+
+            FilterPrimitive::ComponentTransfer { input, functions } => {
+                component_transfer(get(input), &functions, &mut result);
+            }
+
+            FilterPrimitive::Composite { input, input2, blend: mode } => {
+                zip_pixels(get(input), get(input2), &mut result, |s, d| blend(mode, s, d));
+            }
+
+            FilterPrimitive::Arithmetic { input, input2, k } => {
+                let k = k.map(F32x::splat);
+                zip_pixels(get(input), get(input2), &mut result, |i1, i2| {
+                    let one = F32x::ONE();
+                    let c: [F32x<N>; 4] = core::array::from_fn(|c|
+                        (k[0]*i1[c]*i2[c] + k[1]*i1[c] + k[2]*i2[c] + k[3]).clamp(zero, one));
+                    let a = c[3];
+                    [c[0].min(a), c[1].min(a), c[2].min(a), a]
+                });
+            }
+
+            FilterPrimitive::Morphology { input, op, radius } => {
+                let r = [radius.x(), radius.y()].map(|r| (r*scale).abs().round() as usize);
+                morphology(get(input), op, r, line, simd_line, &mut result);
+            }
+
+            FilterPrimitive::Offset { input, offset } => {
+                let d = tfx.mul_normal(offset);
+                offset_image(get(input), d.x().round() as i32, d.y().round() as i32, &mut result);
+            }
+
+            FilterPrimitive::GaussianBlur { input, std_deviation } => {
+                map_pixels(get(input), &mut result, |p| p);
+                let radii = gaussian_box_radii(std_deviation * scale);
+                blur_layer(&mut result.img_mut(), radii, line, simd_line, channels);
+            }
+
+            FilterPrimitive::Merge { inputs } => {
+                for input in inputs {
+                    let input = get(*input);
+                    for y in 0..size[1] as usize {
+                        for u in 0..size[0] as usize {
+                            result[(u, y)] = blend(BlendMode::SrcOver, input[(u, y)], result[(u, y)]);
+                        }
+                    }
+                }
+            }
+
+            FilterPrimitive::Flood { color } => {
+                let c = argb_unpack_premultiply(color);
+                result.clear([0, 1, 2, 3].map(|i| F32x::splat(c[i])));
+            }
+
+            FilterPrimitive::Turbulence { base_frequency, num_octaves, seed, kind } => {
+                // transparent, if the transform isn't invertible.
+                if let Some(inv_tfx) = tfx.invert(0.00001) {
+                    let noise = Turbulence::new(seed);
+                    let origin = origin.as_i32().to_f32();
+
+                    from_pixels(&mut result, |x, y| {
+                        let p = origin + F32x2::new(x as f32 + 0.5, y as f32 + 0.5);
+                        let p = inv_tfx * p;
+                        let p = [p.x() * base_frequency.x(), p.y() * base_frequency.y()];
+
+                        let [r, g, b, a] = [0, 1, 2, 3].map(|channel| {
+                            let sum = noise.sum(channel, p, num_octaves, kind);
+                            let c = match kind {
+                                TurbulenceKind::FractalNoise => (sum + 1.0) / 2.0,
+                                TurbulenceKind::Turbulence   => sum,
+                            };
+                            c.clamp(0.0, 1.0)
+                        });
+                        [r*a, g*a, b*a, a]
+                    });
+                }
+            }
+        }
+
+        results.push(result);
+    }
+
+    let mut result = results.pop().unwrap();
+    core::mem::swap(layer, &mut result);
+    images.push(result);
+    images.append(results);
+}
+
+
+/// `result` must have the size of `src`.
+fn map_pixels<const N: usize, F: Fn([F32x<N>; 4]) -> [F32x<N>; 4]>(src: &Image<[F32x<N>; 4]>, result: &mut Image<[F32x<N>; 4]>, f: F)
+where (): SimdLanes<N>
+{
+    assert!(src.size() == result.size());
+
+    for y in 0..src.height() as usize {
+        for u in 0..src.width() as usize {
+            result[(u, y)] = f(src[(u, y)]);
+        }
+    }
+}
+
+/// `result` must have the size of `a` and `b`.
+fn zip_pixels<const N: usize, F: Fn([F32x<N>; 4], [F32x<N>; 4]) -> [F32x<N>; 4]>(
+    a: &Image<[F32x<N>; 4]>,
+    b: &Image<[F32x<N>; 4]>,
+    result: &mut Image<[F32x<N>; 4]>,
+    f: F)
+where (): SimdLanes<N>
+{
+    assert!(a.size() == b.size() && a.size() == result.size());
+
+    for y in 0..a.height() as usize {
+        for u in 0..a.width() as usize {
+            result[(u, y)] = f(a[(u, y)], b[(u, y)]);
+        }
+    }
+}
+
+/// writes the individual pixels of `result`.
+/// - `f` is called with pixel coordinates, and returns pre-multiplied rgba.
+fn from_pixels<const N: usize, F: FnMut(usize, usize) -> [f32; 4]>(result: &mut Image<[F32x<N>; 4]>, mut f: F)
+where (): SimdLanes<N>
+{
+    for y in 0..result.height() as usize {
+        for u in 0..result.width() as usize {
+            let pixels: [[f32; 4]; N] = core::array::from_fn(|i| f(u*N + i, y));
+            result[(u, y)] = core::array::from_fn(|c|
+                F32x::from_array(core::array::from_fn(|i| pixels[i][c])));
+        }
+    }
+}
+
+#[inline(always)]
+fn pixel<const N: usize>(image: &Image<[F32x<N>; 4]>, x: usize, y: usize) -> [f32; 4]
+where (): SimdLanes<N>
+{
+    let v = image[(x / N, y)];
+    core::array::from_fn(|c| v[c][x % N])
+}
+
+
+fn color_matrix<const N: usize>(src: &Image<[F32x<N>; 4]>, m: &[f32; 20], result: &mut Image<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let m = m.map(F32x::splat);
+    map_pixels(src, result, |[r, g, b, a]| {
+        let c = [unpremultiply(r, a), unpremultiply(g, a), unpremultiply(b, a), a];
+
+        let zero = F32x::ZERO();
+        let one  = F32x::ONE();
+        let row = |i: usize| {
+            let m = &m[5*i .. 5*i + 5];
+            (m[0]*c[0] + m[1]*c[1] + m[2]*c[2] + m[3]*c[3] + m[4]).clamp(zero, one)
+        };
+
+        let a = row(3);
+        [row(0)*a, row(1)*a, row(2)*a, a]
+    });
+}
+
+fn component_transfer<const N: usize>(src: &Image<[F32x<N>; 4]>, functions: &[TransferFunction; 4], result: &mut Image<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    map_pixels(src, result, |[r, g, b, a]| {
+        let c = [unpremultiply(r, a), unpremultiply(g, a), unpremultiply(b, a), a];
+
+        let [r, g, b, a]: [F32x<N>; 4] = core::array::from_fn(|i| {
+            let f = &functions[i];
+            let v = F32x::from_array(core::array::from_fn(|lane| f.apply(c[i][lane])));
+            v.clamp(F32x::ZERO(), F32x::ONE())
+        });
+        [r*a, g*a, b*a, a]
+    });
+}
+
+/// pixels outside of the image are transparent,
+/// so shapes touching the borders are eroded there.
+fn morphology<const N: usize>(src: &Image<[F32x<N>; 4]>, op: MorphologyOp, radius: [usize; 2],
+    line: &mut Vec<f32>, column: &mut Vec<[F32x<N>; 4]>, result: &mut Image<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    map_pixels(src, result, |p| p);
+
+    let w = src.width()  as usize;
+    let h = src.height() as usize;
+
+    let pick = |a: f32, b: f32| match op {
+        MorphologyOp::Erode  => a.min(b),
+        MorphologyOp::Dilate => a.max(b),
+    };
+
+    let pick_simd = |a: [F32x<N>; 4], b: [F32x<N>; 4]| -> [F32x<N>; 4] {
+        core::array::from_fn(|c| match op {
+            MorphologyOp::Erode  => a[c].min(b[c]),
+            MorphologyOp::Dilate => a[c].max(b[c]),
+        })
+    };
+
+    // horizontal, on de-interleaved channels.
+    let r = radius[0];
+    if r > 0 {
+        for y in 0..h {
+            for c in 0..4 {
+                line.clear();
+                for u in 0..w {
+                    line.extend_from_slice(&*src[(u, y)][c]);
+                }
+
+                for u in 0..w {
+                    result[(u, y)][c] = F32x::from_array(core::array::from_fn(|i| {
+                        let x = u*N + i;
+                        let window = &line[x.saturating_sub(r) .. (x + r + 1).min(w*N)];
+                        let v = window.iter().copied().reduce(pick).unwrap();
+                        if window.len() < 2*r + 1 { pick(v, 0.0) } else { v }
+                    }));
+                }
+            }
+        }
+    }
+
+    // vertical.
+    let r = radius[1];
+    if r > 0 {
+        for u in 0..w {
+            column.clear();
+            for y in 0..h {
+                column.push(result[(u, y)]);
+            }
+
+            for y in 0..h {
+                let window = &column[y.saturating_sub(r) .. (y + r + 1).min(h)];
+                let v = window.iter().copied().reduce(pick_simd).unwrap();
+                result[(u, y)] = if window.len() < 2*r + 1 { pick_simd(v, [F32x::ZERO(); 4]) } else { v };
+            }
+        }
+    }
+}
+
+fn offset_image<const N: usize>(src: &Image<[F32x<N>; 4]>, dx: i32, dy: i32, result: &mut Image<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let w = (src.width() as usize * N) as i32;
+    let h = src.height() as i32;
+
+    from_pixels(result, |x, y| {
+        let sx = x as i32 - dx;
+        let sy = y as i32 - dy;
+        if sx >= 0 && sx < w && sy >= 0 && sy < h {
+            pixel(src, sx as usize, sy as usize)
+        }
+        else { [0.0; 4] }
+    });
+}
+
+
+// the reference implementation from the svg spec.
+const BSIZE: usize = 0x100;
+const BM: i32 = 0xff;
+const PERLIN_N: f32 = 4096.0;
+
+const RAND_M: i32 = 2147483647;
+const RAND_A: i32 = 16807;
+const RAND_Q: i32 = 127773;
+const RAND_R: i32 = 2836;
+
+struct Turbulence {
+    lattice:  [usize; BSIZE + BSIZE + 2],
+    gradient: [[[f32; 2]; BSIZE + BSIZE + 2]; 4],
+}
+
+impl Turbulence {
+    fn new(seed: i32) -> Self {
+        let mut seed = seed;
+        if seed <= 0 { seed = -(seed % (RAND_M - 1)) + 1 }
+        if seed > RAND_M - 1 { seed = RAND_M - 1 }
+
+        let mut random = || {
+            seed = RAND_A*(seed % RAND_Q) - RAND_R*(seed / RAND_Q);
+            if seed <= 0 { seed += RAND_M }
+            seed
+        };
+
+        let mut lattice  = [0; BSIZE + BSIZE + 2];
+        let mut gradient = [[[0.0; 2]; BSIZE + BSIZE + 2]; 4];
+
+        for channel in gradient.iter_mut() {
+            for (i, g) in channel[..BSIZE].iter_mut().enumerate() {
+                lattice[i] = i;
+
+                for v in g.iter_mut() {
+                    *v = ((random() % (2*BSIZE as i32)) - BSIZE as i32) as f32 / BSIZE as f32;
+                }
+
+                let s = (g[0]*g[0] + g[1]*g[1]).sqrt();
+                if s > 0.0 {
+                    g[0] /= s;
+                    g[1] /= s;
+                }
+            }
+        }
+
+        for i in (1..BSIZE).rev() {
+            let j = random() as usize % BSIZE;
+            lattice.swap(i, j);
+        }
+
+        lattice.copy_within(0..BSIZE + 2, BSIZE);
+        for channel in gradient.iter_mut() {
+            channel.copy_within(0..BSIZE + 2, BSIZE);
+        }
+
+        Turbulence { lattice, gradient }
+    }
+
+    fn noise2(&self, channel: usize, p: [f32; 2]) -> f32 {
+        let setup = |v: f32| {
+            let t = v + PERLIN_N;
+            let b0 = (t as i32 & BM) as usize;
+            let b1 = ((b0 as i32 + 1) & BM) as usize;
+            let r0 = t - (t as i32) as f32;
+            (b0, b1, r0, r0 - 1.0)
+        };
+
+        let (bx0, bx1, rx0, rx1) = setup(p[0]);
+        let (by0, by1, ry0, ry1) = setup(p[1]);
+
+        let i = self.lattice[bx0];
+        let j = self.lattice[bx1];
+        let b00 = self.lattice[i + by0];
+        let b10 = self.lattice[j + by0];
+        let b01 = self.lattice[i + by1];
+        let b11 = self.lattice[j + by1];
+
+        let s_curve = |t: f32| t*t*(3.0 - 2.0*t);
+        let lerp = |t: f32, a: f32, b: f32| a + t*(b - a);
+
+        let sx = s_curve(rx0);
+        let sy = s_curve(ry0);
+
+        let g = &self.gradient[channel];
+        let dot = |q: [f32; 2], rx: f32, ry: f32| rx*q[0] + ry*q[1];
+
+        let a = lerp(sx, dot(g[b00], rx0, ry0), dot(g[b10], rx1, ry0));
+        let b = lerp(sx, dot(g[b01], rx0, ry1), dot(g[b11], rx1, ry1));
+        lerp(sy, a, b)
+    }
+
+    fn sum(&self, channel: usize, p: [f32; 2], num_octaves: u32, kind: TurbulenceKind) -> f32 {
+        let mut sum = 0.0;
+        let mut p = p;
+        let mut ratio = 1.0;
+        for _ in 0..num_octaves {
+            let noise = self.noise2(channel, p);
+            sum += match kind {
+                TurbulenceKind::FractalNoise => noise / ratio,
+                TurbulenceKind::Turbulence   => noise.abs() / ratio,
+            };
+            p = [p[0]*2.0, p[1]*2.0];
+            ratio *= 2.0;
+        }
+        sum
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn erode_borders() {
+        let primitives = [FilterPrimitive::Morphology {
+            input:  FilterInput::Source,
+            op:     MorphologyOp::Erode,
+            radius: F32x2::new(1.0, 1.0),
+        }];
+        let filter = Filter { primitives: &primitives };
+
+        // the second filter reuses the buffers.
+        let mut buffers = FilterBuffers::new();
+        for _ in 0..2 {
+            let mut layer = Image::with_clear([2, 3], [F32x4::ONE(); 4]);
+            apply_filter(&filter, &mut layer, U32x2::ZERO(), Transform::ID(), &mut buffers);
+
+            // only the pixels, that aren't on the border, remain.
+            for y in 0..3 {
+                for x in 0..8 {
+                    let a = if y == 1 && (1..7).contains(&x) { 1.0 } else { 0.0 };
+                    assert_eq!(pixel(&layer, x, y), [a; 4], "{x} {y}");
+                }
+            }
+        }
+    }
+}
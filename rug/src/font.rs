@@ -0,0 +1,1498 @@
+use sti::simd::*;
+
+use crate::geometry::Transform;
+use crate::path::PathBuilder;
+
+
+/*  truetype & opentype fonts.
+
+    outlines are read from the `glyf` table (as quadratic curves)
+    or from the `CFF ` table (as cubic curves).
+    glyph coordinates are in font units, with y pointing up;
+    callers map them to path coordinates with a transform.
+
+    not supported:
+        - font collections.
+        - hinting.
+        - `GPOS` kerning. only format 0 `kern` tables are used.
+        - `CFF2` and variable fonts.
+*/
+
+
+/// a parsed truetype or opentype font.
+/// - borrows the font file.
+#[derive(Clone, Copy, Debug)]
+pub struct Font<'a> {
+    units_per_em: u16,
+    num_glyphs:   u16,
+    ascender:     i16,
+    descender:    i16,
+    line_gap:     i16,
+
+    cmap: Option<Cmap<'a>>,
+
+    hmtx: &'a [u8],
+    num_h_metrics: u16,
+
+    outlines: Outlines<'a>,
+
+    /// the pairs of the first horizontal format 0 subtable.
+    kern: Option<&'a [u8]>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Outlines<'a> {
+    None,
+    TrueType { loca: &'a [u8], glyf: &'a [u8], long_offsets: bool },
+    Cff(Cff<'a>),
+}
+
+
+impl<'a> Font<'a> {
+    /// returns `None`, if the font is malformed or required tables are missing.
+    pub fn parse(data: &'a [u8]) -> Option<Font<'a>> {
+        let version = read_u32(data, 0)?;
+        if version != 0x00010000
+        && version != u32::from_be_bytes(*b"true")
+        && version != u32::from_be_bytes(*b"OTTO") {
+            return None;
+        }
+
+        let head = find_table(data, b"head")?;
+        let units_per_em = read_u16(head, 18)?;
+        let long_offsets = read_i16(head, 50)? != 0;
+        if units_per_em == 0 {
+            return None;
+        }
+
+        let maxp = find_table(data, b"maxp")?;
+        let num_glyphs = read_u16(maxp, 4)?;
+
+        let hhea = find_table(data, b"hhea")?;
+        let ascender      = read_i16(hhea, 4)?;
+        let descender     = read_i16(hhea, 6)?;
+        let line_gap      = read_i16(hhea, 8)?;
+        let num_h_metrics = read_u16(hhea, 34)?;
+
+        let hmtx = find_table(data, b"hmtx")?;
+
+        let cmap = find_table(data, b"cmap").and_then(Cmap::parse);
+
+        let outlines =
+            if let (Some(loca), Some(glyf)) = (find_table(data, b"loca"), find_table(data, b"glyf")) {
+                Outlines::TrueType { loca, glyf, long_offsets }
+            }
+            else if let Some(cff) = find_table(data, b"CFF ").and_then(Cff::parse) {
+                Outlines::Cff(cff)
+            }
+            else { Outlines::None };
+
+        let kern = find_table(data, b"kern").and_then(parse_kern);
+
+        Some(Font {
+            units_per_em, num_glyphs,
+            ascender, descender, line_gap,
+            cmap,
+            hmtx, num_h_metrics,
+            outlines,
+            kern,
+        })
+    }
+
+    #[inline(always)]
+    pub fn units_per_em(&self) -> u16 { self.units_per_em }
+
+    #[inline(always)]
+    pub fn num_glyphs(&self) -> u16 { self.num_glyphs }
+
+    /// in font units, above the baseline.
+    #[inline(always)]
+    pub fn ascender(&self) -> i16 { self.ascender }
+
+    /// in font units, usually negative.
+    #[inline(always)]
+    pub fn descender(&self) -> i16 { self.descender }
+
+    #[inline(always)]
+    pub fn line_gap(&self) -> i16 { self.line_gap }
+
+
+    /// returns `None`, if `c` isn't mapped.
+    pub fn glyph_index(&self, c: char) -> Option<u16> {
+        self.cmap?.glyph_index(c as u32)
+    }
+
+    /// the horizontal advance of `glyph` in font units.
+    pub fn advance(&self, glyph: u16) -> u16 {
+        let n = self.num_h_metrics as usize;
+        if n == 0 {
+            return 0;
+        }
+
+        // glyphs after the last metric share its advance.
+        let i = (glyph as usize).min(n - 1);
+        read_u16(self.hmtx, 4*i).unwrap_or(0)
+    }
+
+    /// the adjustment of the advance between `left` and `right` in font units.
+    pub fn kerning(&self, left: u16, right: u16) -> i16 {
+        let Some(pairs) = self.kern else { return 0 };
+
+        let key = (left as u32) << 16 | right as u32;
+
+        let mut lo = 0;
+        let mut hi = pairs.len() / 6;
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let pair = read_u32(pairs, 6*mid).unwrap_or(0);
+            if pair < key {
+                lo = mid + 1;
+            }
+            else if pair > key {
+                hi = mid;
+            }
+            else {
+                return read_i16(pairs, 6*mid + 4).unwrap_or(0);
+            }
+        }
+        0
+    }
+
+    /// appends the outline of `glyph` to `pb`.
+    /// - `tfx` maps font units to path coordinates.
+    /// - returns `false`, if the glyph is malformed or the font has no outlines.
+    ///   `pb` may contain a partial outline in that case.
+    pub fn glyph_outline(&self, glyph: u16, tfx: Transform, pb: &mut PathBuilder) -> bool {
+        if glyph >= self.num_glyphs {
+            return false;
+        }
+
+        match self.outlines {
+            Outlines::None => false,
+
+            Outlines::TrueType { loca, glyf, long_offsets } =>
+                glyf_outline(loca, glyf, long_offsets, glyph, tfx, pb, 0).is_some(),
+
+            Outlines::Cff(cff) =>
+                cff.outline(glyph, tfx, pb).is_some(),
+        }
+    }
+
+    /// appends the outlines of the glyphs of `text` to `pb`.
+    /// - the baseline starts at `origin`. y points down.
+    /// - `size` is the em size in path coordinates.
+    /// - unmapped characters use the `.notdef` glyph.
+    /// - returns the advance of the run.
+    pub fn text_outline(&self, text: &str, origin: F32x2, size: f32, pb: &mut PathBuilder) -> f32 {
+        let scale = size / self.units_per_em as f32;
+
+        let mut pen = 0.0;
+        let mut prev = None;
+        for c in text.chars() {
+            let glyph = self.glyph_index(c).unwrap_or(0);
+
+            if let Some(prev) = prev {
+                pen += self.kerning(prev, glyph) as f32 * scale;
+            }
+
+            let tfx =
+                Transform::translate(origin + F32x2::new(pen, 0.0))
+                * Transform::scale(F32x2::new(scale, -scale));
+            self.glyph_outline(glyph, tfx, pb);
+
+            pen += self.advance(glyph) as f32 * scale;
+            prev = Some(glyph);
+        }
+        pen
+    }
+}
+
+
+
+fn find_table<'a>(data: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    let num_tables = read_u16(data, 4)? as usize;
+    for i in 0..num_tables {
+        let record = 12 + 16*i;
+        if data.get(record .. record + 4)? == tag {
+            let offset = read_u32(data, record +  8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            return data.get(offset .. offset.checked_add(length)?);
+        }
+    }
+    None
+}
+
+
+#[derive(Clone, Copy, Debug)]
+struct Cmap<'a> {
+    format: u16,
+    table:  &'a [u8],
+}
+
+impl<'a> Cmap<'a> {
+    fn parse(cmap: &'a [u8]) -> Option<Cmap<'a>> {
+        let num_tables = read_u16(cmap, 2)? as usize;
+
+        let mut best: Option<(u32, Cmap)> = None;
+        for i in 0..num_tables {
+            let record = 4 + 8*i;
+            let platform = read_u16(cmap, record)?;
+            let encoding = read_u16(cmap, record + 2)?;
+            let offset   = read_u32(cmap, record + 4)? as usize;
+
+            let Some(table) = cmap.get(offset..) else { continue };
+            let Some(format) = read_u16(table, 0) else { continue };
+
+            // prefer subtables covering all of unicode.
+            let rank = match (platform, encoding, format) {
+                (3, 10, 12) | (0, _, 12) => 2,
+                (3,  1,  4) | (0, _,  4) => 1,
+                _ => continue,
+            };
+
+            let better = match best {
+                Some((best, _)) => rank > best,
+                None => true,
+            };
+            if better {
+                best = Some((rank, Cmap { format, table }));
+            }
+        }
+
+        best.map(|(_, cmap)| cmap)
+    }
+
+    fn glyph_index(&self, c: u32) -> Option<u16> {
+        let t = self.table;
+
+        let glyph = match self.format {
+            4 => {
+                let c = u16::try_from(c).ok()?;
+
+                let seg_count = read_u16(t, 6)? as usize / 2;
+                let end_codes        = 14;
+                let start_codes      = end_codes   + 2*seg_count + 2;
+                let id_deltas        = start_codes + 2*seg_count;
+                let id_range_offsets = id_deltas   + 2*seg_count;
+
+                // first segment with `end >= c`.
+                let mut lo = 0;
+                let mut hi = seg_count;
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    if read_u16(t, end_codes + 2*mid)? < c { lo = mid + 1 }
+                    else { hi = mid }
+                }
+                if lo == seg_count {
+                    return None;
+                }
+
+                let start = read_u16(t, start_codes + 2*lo)?;
+                if c < start {
+                    return None;
+                }
+
+                let delta = read_u16(t, id_deltas + 2*lo)?;
+                let range_offset_at = id_range_offsets + 2*lo;
+                let range_offset = read_u16(t, range_offset_at)? as usize;
+                if range_offset == 0 {
+                    c.wrapping_add(delta)
+                }
+                else {
+                    let glyph = read_u16(t, range_offset_at + range_offset + 2*(c - start) as usize)?;
+                    if glyph == 0 {
+                        return None;
+                    }
+                    glyph.wrapping_add(delta)
+                }
+            }
+
+            12 => {
+                let num_groups = read_u32(t, 12)? as usize;
+
+                let mut lo = 0;
+                let mut hi = num_groups;
+                let mut glyph = None;
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    let group = 16 + 12*mid;
+                    let start = read_u32(t, group)?;
+                    let end   = read_u32(t, group + 4)?;
+                    if c < start { hi = mid }
+                    else if c > end { lo = mid + 1 }
+                    else {
+                        let first = read_u32(t, group + 8)?;
+                        glyph = Some(u16::try_from(first.checked_add(c - start)?).ok()?);
+                        break;
+                    }
+                }
+                glyph?
+            }
+
+            _ => return None,
+        };
+
+        (glyph != 0).then_some(glyph)
+    }
+}
+
+
+fn parse_kern(kern: &[u8]) -> Option<&[u8]> {
+    // apple's version 1 tables aren't supported.
+    if read_u16(kern, 0)? != 0 {
+        return None;
+    }
+
+    let num_tables = read_u16(kern, 2)?;
+    let mut offset = 4;
+    for _ in 0..num_tables {
+        let length   = read_u16(kern, offset + 2)? as usize;
+        let coverage = read_u16(kern, offset + 4)?;
+
+        // horizontal, not minimum, not cross-stream.
+        let format = coverage >> 8;
+        if format == 0 && coverage & 0x7 == 0x1 {
+            let num_pairs = read_u16(kern, offset + 6)? as usize;
+            let pairs = offset + 14;
+            return kern.get(pairs .. pairs + 6*num_pairs);
+        }
+
+        offset += length;
+    }
+    None
+}
+
+
+
+const MAX_COMPONENT_DEPTH: u32 = 8;
+
+fn glyf_outline(loca: &[u8], glyf: &[u8], long_offsets: bool, glyph: u16, tfx: Transform, pb: &mut PathBuilder, depth: u32) -> Option<()> {
+    if depth > MAX_COMPONENT_DEPTH {
+        return None;
+    }
+
+    let g = glyph as usize;
+    let (begin, end) =
+        if long_offsets { (read_u32(loca, 4*g)? as usize, read_u32(loca, 4*g + 4)? as usize) }
+        else { (2*read_u16(loca, 2*g)? as usize, 2*read_u16(loca, 2*g + 2)? as usize) };
+
+    // glyphs without outlines, like spaces.
+    if begin >= end {
+        return Some(());
+    }
+
+    let data = glyf.get(begin..end)?;
+    let num_contours = read_i16(data, 0)?;
+    if num_contours >= 0 {
+        simple_glyph(data, num_contours as usize, tfx, pb)
+    }
+    else {
+        composite_glyph(loca, glyf, long_offsets, data, tfx, pb, depth)
+    }
+}
+
+fn simple_glyph(data: &[u8], num_contours: usize, tfx: Transform, pb: &mut PathBuilder) -> Option<()> {
+    const ON_CURVE:       u8 = 0x01;
+    const X_SHORT:        u8 = 0x02;
+    const Y_SHORT:        u8 = 0x04;
+    const REPEAT:         u8 = 0x08;
+    const X_SAME_OR_POS:  u8 = 0x10;
+    const Y_SAME_OR_POS:  u8 = 0x20;
+
+    let mut r = Reader::new(data, 10);
+
+    let mut end_points = Vec::with_capacity(num_contours);
+    for _ in 0..num_contours {
+        end_points.push(r.u16()? as usize);
+    }
+    let num_points = end_points.last().map_or(0, |end| end + 1);
+
+    let instructions_len = r.u16()? as usize;
+    r.skip(instructions_len)?;
+
+    let mut flags = Vec::with_capacity(num_points);
+    while flags.len() < num_points {
+        let flag = r.u8()?;
+        flags.push(flag);
+        if flag & REPEAT != 0 {
+            let count = r.u8()?;
+            for _ in 0..count {
+                flags.push(flag);
+            }
+        }
+    }
+    flags.truncate(num_points);
+
+    let coord = |r: &mut Reader, flag: u8, short: u8, same_or_pos: u8| -> Option<i32> {
+        if flag & short != 0 {
+            let d = r.u8()? as i32;
+            Some(if flag & same_or_pos != 0 { d } else { -d })
+        }
+        else if flag & same_or_pos != 0 { Some(0) }
+        else { Some(r.i16()? as i32) }
+    };
+
+    let mut xs = Vec::with_capacity(num_points);
+    let mut x = 0;
+    for &flag in &flags {
+        x += coord(&mut r, flag, X_SHORT, X_SAME_OR_POS)?;
+        xs.push(x);
+    }
+
+    let mut points = Vec::with_capacity(num_points);
+    let mut y = 0;
+    for (&flag, &x) in flags.iter().zip(&xs) {
+        y += coord(&mut r, flag, Y_SHORT, Y_SAME_OR_POS)?;
+        let p = tfx * F32x2::new(x as f32, y as f32);
+        points.push((p, flag & ON_CURVE != 0));
+    }
+
+    let mut begin = 0;
+    for &end in &end_points {
+        if end < begin || end >= num_points {
+            return None;
+        }
+        quad_contour(&points[begin..=end], pb);
+        begin = end + 1;
+    }
+
+    Some(())
+}
+
+/// - `points` are `(point, on_curve)`.
+///   two consecutive off-curve points have an implied on-curve point at their midpoint.
+fn quad_contour(points: &[(F32x2, bool)], pb: &mut PathBuilder) {
+    let n = points.len();
+    if n == 0 {
+        return;
+    }
+
+    let mid = |a: F32x2, b: F32x2| 0.5*(a + b);
+
+    // start at an on-curve point, if there is one.
+    let (start, first, count) = match points.iter().position(|p| p.1) {
+        Some(i) => (points[i].0, i + 1, n - 1),
+        None    => (mid(points[n-1].0, points[0].0), 0, n),
+    };
+
+    pb.move_to(start);
+
+    let mut control: Option<F32x2> = None;
+    for i in 0..count {
+        let (p, on_curve) = points[(first + i) % n];
+        if on_curve {
+            match control.take() {
+                Some(c) => pb.quad_to(c, p),
+                None    => pb.line_to(p),
+            }
+        }
+        else {
+            if let Some(c) = control {
+                pb.quad_to(c, mid(c, p));
+            }
+            control = Some(p);
+        }
+    }
+
+    if let Some(c) = control {
+        pb.quad_to(c, start);
+    }
+    pb.close_path();
+}
+
+fn composite_glyph(loca: &[u8], glyf: &[u8], long_offsets: bool, data: &[u8], tfx: Transform, pb: &mut PathBuilder, depth: u32) -> Option<()> {
+    const ARG_1_AND_2_ARE_WORDS:    u16 = 0x0001;
+    const ARGS_ARE_XY_VALUES:       u16 = 0x0002;
+    const WE_HAVE_A_SCALE:          u16 = 0x0008;
+    const MORE_COMPONENTS:          u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO:     u16 = 0x0080;
+
+    let mut r = Reader::new(data, 10);
+    loop {
+        let flags = r.u16()?;
+        let glyph = r.u16()?;
+
+        let (dx, dy) =
+            if flags & ARG_1_AND_2_ARE_WORDS != 0 { (r.i16()? as f32, r.i16()? as f32) }
+            else { (r.u8()? as i8 as f32, r.u8()? as i8 as f32) };
+
+        let (a, b, c, d) =
+            if flags & WE_HAVE_A_SCALE != 0 {
+                let s = r.f2dot14()?;
+                (s, 0.0, 0.0, s)
+            }
+            else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                (r.f2dot14()?, 0.0, 0.0, r.f2dot14()?)
+            }
+            else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                (r.f2dot14()?, r.f2dot14()?, r.f2dot14()?, r.f2dot14()?)
+            }
+            else { (1.0, 0.0, 0.0, 1.0) };
+
+        // matching points isn't supported.
+        let offset =
+            if flags & ARGS_ARE_XY_VALUES != 0 { F32x2::new(dx, dy) }
+            else { F32x2::ZERO() };
+
+        let component = Transform { columns: [F32x2::new(a, b), F32x2::new(c, d), offset] };
+        glyf_outline(loca, glyf, long_offsets, glyph, tfx * component, pb, depth + 1)?;
+
+        if flags & MORE_COMPONENTS == 0 {
+            return Some(());
+        }
+    }
+}
+
+
+
+#[derive(Clone, Copy, Debug)]
+struct Cff<'a> {
+    data: &'a [u8],
+    char_strings: Index<'a>,
+    global_subrs: Index<'a>,
+    local_subrs:  Index<'a>,
+
+    /// `(fd_array, fd_select)` of cid-keyed fonts.
+    cid: Option<(Index<'a>, usize)>,
+}
+
+impl<'a> Cff<'a> {
+    fn parse(data: &'a [u8]) -> Option<Cff<'a>> {
+        let header_size = read_u8(data, 2)? as usize;
+        let (_names,       at) = Index::parse(data, header_size)?;
+        let (top_dicts,    at) = Index::parse(data, at)?;
+        let (_strings,     at) = Index::parse(data, at)?;
+        let (global_subrs, _)  = Index::parse(data, at)?;
+
+        let mut char_strings = None;
+        let mut private = None;
+        let mut fd_array = None;
+        let mut fd_select = None;
+        let mut charstring_type = 2.0;
+        parse_dict(top_dicts.get(0)?, |op, args| match (op, args) {
+            (17,   [offset])       => char_strings = Some(*offset as usize),
+            (18,   [size, offset]) => private = Some((*size as usize, *offset as usize)),
+            (1206, [t])            => charstring_type = *t,
+            (1236, [offset])       => fd_array = Some(*offset as usize),
+            (1237, [offset])       => fd_select = Some(*offset as usize),
+            _ => (),
+        })?;
+
+        if charstring_type != 2.0 {
+            return None;
+        }
+
+        let (char_strings, _) = Index::parse(data, char_strings?)?;
+
+        let local_subrs = match private {
+            Some((size, offset)) => private_subrs(data, size, offset)?,
+            None => Index::default(),
+        };
+
+        let cid = match (fd_array, fd_select) {
+            (Some(fd_array), Some(fd_select)) => Some((Index::parse(data, fd_array)?.0, fd_select)),
+            _ => None,
+        };
+
+        Some(Cff { data, char_strings, global_subrs, local_subrs, cid })
+    }
+
+    fn outline(&self, glyph: u16, tfx: Transform, pb: &mut PathBuilder) -> Option<()> {
+        let code = self.char_strings.get(glyph as usize)?;
+
+        let local_subrs = match self.cid {
+            Some((fd_array, fd_select)) => {
+                let fd = fd_index(self.data, fd_select, glyph)?;
+
+                let mut private = None;
+                parse_dict(fd_array.get(fd)?, |op, args| {
+                    if let (18, [size, offset]) = (op, args) {
+                        private = Some((*size as usize, *offset as usize));
+                    }
+                })?;
+
+                match private {
+                    Some((size, offset)) => private_subrs(self.data, size, offset)?,
+                    None => Index::default(),
+                }
+            }
+
+            None => self.local_subrs,
+        };
+
+        let mut cs = CharString {
+            global_subrs: self.global_subrs,
+            local_subrs,
+            tfx, pb,
+            stack: [0.0; 48],
+            sp: 0,
+            x: 0.0,
+            y: 0.0,
+            num_stems: 0,
+            have_width: false,
+            open: false,
+            done: false,
+        };
+        cs.run(code, 0)?;
+
+        if cs.open {
+            cs.pb.close_path();
+        }
+        Some(())
+    }
+}
+
+fn private_subrs<'a>(data: &'a [u8], size: usize, offset: usize) -> Option<Index<'a>> {
+    let mut subrs = None;
+    parse_dict(data.get(offset .. offset + size)?, |op, args| {
+        if let (19, [subrs_offset]) = (op, args) {
+            subrs = Some(*subrs_offset as usize);
+        }
+    })?;
+
+    // the offset is relative to the private dict.
+    match subrs {
+        Some(subrs) => Some(Index::parse(data, offset + subrs)?.0),
+        None => Some(Index::default()),
+    }
+}
+
+fn fd_index(data: &[u8], fd_select: usize, glyph: u16) -> Option<usize> {
+    match read_u8(data, fd_select)? {
+        0 => read_u8(data, fd_select + 1 + glyph as usize).map(|fd| fd as usize),
+
+        3 => {
+            let num_ranges = read_u16(data, fd_select + 1)? as usize;
+            for i in 0..num_ranges {
+                let range = fd_select + 3 + 3*i;
+                let first = read_u16(data, range)?;
+                let fd    = read_u8(data, range + 2)?;
+                // the first glyph of the next range, or the sentinel.
+                let next  = read_u16(data, range + 3)?;
+                if glyph >= first && glyph < next {
+                    return Some(fd as usize);
+                }
+            }
+            None
+        }
+
+        _ => None,
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Index<'a> {
+    count:    usize,
+    off_size: usize,
+    offsets:  &'a [u8],
+    data:     &'a [u8],
+}
+
+impl<'a> Index<'a> {
+    /// returns the index at `at` and the offset after it.
+    fn parse(data: &'a [u8], at: usize) -> Option<(Index<'a>, usize)> {
+        let count = read_u16(data, at)? as usize;
+        if count == 0 {
+            return Some((Index::default(), at + 2));
+        }
+
+        let off_size = read_u8(data, at + 2)? as usize;
+        if !(1..=4).contains(&off_size) {
+            return None;
+        }
+
+        let offsets_at = at + 3;
+        let offsets_len = (count + 1)*off_size;
+        let offsets = data.get(offsets_at .. offsets_at + offsets_len)?;
+
+        let mut index = Index { count, off_size, offsets, data: &[] };
+
+        // offsets are relative to the byte before the object data.
+        let base = offsets_at + offsets_len - 1;
+        let end  = base + index.offset(count)?;
+        index.data = data.get(base..end)?;
+        Some((index, end))
+    }
+
+    fn offset(&self, i: usize) -> Option<usize> {
+        let bytes = self.offsets.get(i*self.off_size .. (i + 1)*self.off_size)?;
+        Some(bytes.iter().fold(0, |acc, b| acc << 8 | *b as usize))
+    }
+
+    fn get(&self, i: usize) -> Option<&'a [u8]> {
+        if i >= self.count {
+            return None;
+        }
+        self.data.get(self.offset(i)? .. self.offset(i + 1)?)
+    }
+}
+
+/// calls `f` with each operator and its operands.
+/// - two byte operators are `1200 + second byte`.
+fn parse_dict<F: FnMut(u16, &[f64])>(dict: &[u8], mut f: F) -> Option<()> {
+    let mut operands = [0.0; 48];
+    let mut n = 0;
+
+    let mut r = Reader::new(dict, 0);
+    while !r.done() {
+        let b0 = r.u8()?;
+        let value = match b0 {
+            0..=21 => {
+                let op = if b0 == 12 { 1200 + r.u8()? as u16 } else { b0 as u16 };
+                f(op, &operands[..n]);
+                n = 0;
+                continue;
+            }
+
+            28 => r.i16()? as f64,
+            29 => r.u32()? as i32 as f64,
+            30 => parse_real(&mut r)?,
+            32..=246  => b0 as f64 - 139.0,
+            247..=250 =>  (b0 as f64 - 247.0)*256.0 + r.u8()? as f64 + 108.0,
+            251..=254 => -(b0 as f64 - 251.0)*256.0 - r.u8()? as f64 - 108.0,
+            _ => return None,
+        };
+
+        if n == operands.len() {
+            return None;
+        }
+        operands[n] = value;
+        n += 1;
+    }
+    Some(())
+}
+
+fn parse_real(r: &mut Reader) -> Option<f64> {
+    let mut s = String::new();
+    'nibbles: loop {
+        let b = r.u8()?;
+        for nibble in [b >> 4, b & 0xf] {
+            match nibble {
+                0..=9 => s.push((b'0' + nibble) as char),
+                0xa   => s.push('.'),
+                0xb   => s.push('E'),
+                0xc   => s.push_str("E-"),
+                0xe   => s.push('-'),
+                0xf   => break 'nibbles,
+                _     => return None,
+            }
+        }
+    }
+    s.parse().ok()
+}
+
+
+const MAX_SUBR_DEPTH: u32 = 10;
+
+/// type 2 charstring interpreter.
+struct CharString<'p, 'a> {
+    global_subrs: Index<'a>,
+    local_subrs:  Index<'a>,
+
+    tfx: Transform,
+    pb:  &'p mut PathBuilder,
+
+    stack: [f32; 48],
+    sp:    usize,
+
+    x: f32,
+    y: f32,
+
+    num_stems:  usize,
+    have_width: bool,
+    open:       bool,
+    done:       bool,
+}
+
+impl<'p, 'a> CharString<'p, 'a> {
+    fn run(&mut self, code: &'a [u8], depth: u32) -> Option<()> {
+        if depth > MAX_SUBR_DEPTH {
+            return None;
+        }
+
+        let mut r = Reader::new(code, 0);
+        while !r.done() && !self.done {
+            let b0 = r.u8()?;
+
+            // operands.
+            let value = match b0 {
+                28 => Some(r.i16()? as f32),
+                32..=246  => Some(b0 as f32 - 139.0),
+                247..=250 => Some( (b0 as f32 - 247.0)*256.0 + r.u8()? as f32 + 108.0),
+                251..=254 => Some(-(b0 as f32 - 251.0)*256.0 - r.u8()? as f32 - 108.0),
+                255 => Some(r.u32()? as i32 as f32 / 65536.0),
+                _ => None,
+            };
+            if let Some(value) = value {
+                if self.sp == self.stack.len() {
+                    return None;
+                }
+                self.stack[self.sp] = value;
+                self.sp += 1;
+                continue;
+            }
+
+            // operators.
+            match b0 {
+                // hstem, vstem, hstemhm, vstemhm.
+                1 | 3 | 18 | 23 => {
+                    self.stems();
+                }
+
+                // hintmask, cntrmask.
+                19 | 20 => {
+                    self.stems();
+                    r.skip((self.num_stems + 7) / 8)?;
+                }
+
+                // rmoveto.
+                21 => {
+                    self.width(self.sp > 2);
+                    let [dx, dy] = self.args::<2>()?;
+                    self.move_by(dx, dy);
+                }
+
+                // hmoveto.
+                22 => {
+                    self.width(self.sp > 1);
+                    let [dx] = self.args::<1>()?;
+                    self.move_by(dx, 0.0);
+                }
+
+                // vmoveto.
+                4 => {
+                    self.width(self.sp > 1);
+                    let [dy] = self.args::<1>()?;
+                    self.move_by(0.0, dy);
+                }
+
+                // rlineto.
+                5 => {
+                    let stack = self.stack;
+                    for d in stack[..self.sp].chunks_exact(2) {
+                        self.line_by(d[0], d[1])?;
+                    }
+                }
+
+                // hlineto, vlineto.
+                6 | 7 => {
+                    let stack = self.stack;
+                    let mut horizontal = b0 == 6;
+                    for &d in &stack[..self.sp] {
+                        if horizontal { self.line_by(d, 0.0)? }
+                        else          { self.line_by(0.0, d)? }
+                        horizontal = !horizontal;
+                    }
+                }
+
+                // rrcurveto.
+                8 => {
+                    let stack = self.stack;
+                    for d in stack[..self.sp].chunks_exact(6) {
+                        self.curve_by(d[0], d[1], d[2], d[3], d[4], d[5])?;
+                    }
+                }
+
+                // rcurveline.
+                24 => {
+                    let stack = self.stack;
+                    let args = &stack[..self.sp];
+                    if args.len() < 2 {
+                        return None;
+                    }
+                    let (curves, line) = args.split_at(args.len() - 2);
+                    for d in curves.chunks_exact(6) {
+                        self.curve_by(d[0], d[1], d[2], d[3], d[4], d[5])?;
+                    }
+                    self.line_by(line[0], line[1])?;
+                }
+
+                // rlinecurve.
+                25 => {
+                    let stack = self.stack;
+                    let args = &stack[..self.sp];
+                    if args.len() < 6 {
+                        return None;
+                    }
+                    let (lines, d) = args.split_at(args.len() - 6);
+                    for l in lines.chunks_exact(2) {
+                        self.line_by(l[0], l[1])?;
+                    }
+                    self.curve_by(d[0], d[1], d[2], d[3], d[4], d[5])?;
+                }
+
+                // vvcurveto.
+                26 => {
+                    let stack = self.stack;
+                    let args = &stack[..self.sp];
+                    let (mut dx1, args) =
+                        if args.len() % 2 == 1 { (args[0], &args[1..]) }
+                        else { (0.0, args) };
+                    for d in args.chunks_exact(4) {
+                        self.curve_by(dx1, d[0], d[1], d[2], 0.0, d[3])?;
+                        dx1 = 0.0;
+                    }
+                }
+
+                // hhcurveto.
+                27 => {
+                    let stack = self.stack;
+                    let args = &stack[..self.sp];
+                    let (mut dy1, args) =
+                        if args.len() % 2 == 1 { (args[0], &args[1..]) }
+                        else { (0.0, args) };
+                    for d in args.chunks_exact(4) {
+                        self.curve_by(d[0], dy1, d[1], d[2], d[3], 0.0)?;
+                        dy1 = 0.0;
+                    }
+                }
+
+                // vhcurveto, hvcurveto.
+                30 | 31 => {
+                    let stack = self.stack;
+                    let args = &stack[..self.sp];
+                    let n = args.len();
+
+                    let mut horizontal = b0 == 31;
+                    let mut i = 0;
+                    while i + 4 <= n {
+                        // the last curve may have an extra final delta.
+                        let extra = if n - (i + 4) == 1 { args[n - 1] } else { 0.0 };
+                        let d = &args[i .. i + 4];
+                        if horizontal { self.curve_by(d[0], 0.0, d[1], d[2], extra, d[3])? }
+                        else          { self.curve_by(0.0, d[0], d[1], d[2], d[3], extra)? }
+                        horizontal = !horizontal;
+                        i += 4;
+                    }
+                }
+
+                // callsubr, callgsubr.
+                10 | 29 => {
+                    if self.sp == 0 {
+                        return None;
+                    }
+                    self.sp -= 1;
+
+                    let subrs = if b0 == 10 { self.local_subrs } else { self.global_subrs };
+                    let i = self.stack[self.sp] as i32 + subr_bias(subrs.count);
+                    let subr = subrs.get(usize::try_from(i).ok()?)?;
+                    self.run(subr, depth + 1)?;
+                    continue;
+                }
+
+                // return.
+                11 => return Some(()),
+
+                // endchar.
+                14 => {
+                    // 4 extra args are the deprecated accented character form.
+                    self.width(self.sp == 1 || self.sp == 5);
+                    if self.open {
+                        self.pb.close_path();
+                        self.open = false;
+                    }
+                    self.done = true;
+                }
+
+                12 => {
+                    let stack = self.stack;
+                    let args = &stack[..self.sp];
+                    match r.u8()? {
+                        // hflex.
+                        34 => {
+                            let &[dx1, dx2, dy2, dx3, dx4, dx5, dx6] = args else { return None };
+                            self.curve_by(dx1, 0.0, dx2,  dy2, dx3, 0.0)?;
+                            self.curve_by(dx4, 0.0, dx5, -dy2, dx6, 0.0)?;
+                        }
+
+                        // flex.
+                        35 => {
+                            let &[dx1, dy1, dx2, dy2, dx3, dy3, dx4, dy4, dx5, dy5, dx6, dy6, _] = args else { return None };
+                            self.curve_by(dx1, dy1, dx2, dy2, dx3, dy3)?;
+                            self.curve_by(dx4, dy4, dx5, dy5, dx6, dy6)?;
+                        }
+
+                        // hflex1.
+                        36 => {
+                            let &[dx1, dy1, dx2, dy2, dx3, dx4, dx5, dy5, dx6] = args else { return None };
+                            self.curve_by(dx1, dy1, dx2, dy2, dx3, 0.0)?;
+                            self.curve_by(dx4, 0.0, dx5, dy5, dx6, -(dy1 + dy2 + dy5))?;
+                        }
+
+                        // flex1.
+                        37 => {
+                            let &[dx1, dy1, dx2, dy2, dx3, dy3, dx4, dy4, dx5, dy5, d6] = args else { return None };
+                            let dx = dx1 + dx2 + dx3 + dx4 + dx5;
+                            let dy = dy1 + dy2 + dy3 + dy4 + dy5;
+                            let (dx6, dy6) =
+                                if dx.abs() > dy.abs() { (d6, -dy) }
+                                else { (-dx, d6) };
+                            self.curve_by(dx1, dy1, dx2, dy2, dx3, dy3)?;
+                            self.curve_by(dx4, dy4, dx5, dy5, dx6, dy6)?;
+                        }
+
+                        // arithmetic & storage operators aren't supported.
+                        _ => (),
+                    }
+                }
+
+                _ => (),
+            }
+
+            self.sp = 0;
+        }
+
+        Some(())
+    }
+
+    /// the first stack clearing operator may have the glyph's width as an extra first argument.
+    fn width(&mut self, present: bool) {
+        if self.have_width {
+            return;
+        }
+        self.have_width = true;
+
+        if present && self.sp > 0 {
+            self.stack.copy_within(1..self.sp, 0);
+            self.sp -= 1;
+        }
+    }
+
+    fn stems(&mut self) {
+        self.width(self.sp % 2 == 1);
+        self.num_stems += self.sp / 2;
+    }
+
+    fn args<const N: usize>(&self) -> Option<[f32; N]> {
+        if self.sp < N {
+            return None;
+        }
+        Some(core::array::from_fn(|i| self.stack[i]))
+    }
+
+    #[inline(always)]
+    fn point(&self) -> F32x2 {
+        self.tfx * F32x2::new(self.x, self.y)
+    }
+
+    fn move_by(&mut self, dx: f32, dy: f32) {
+        if self.open {
+            self.pb.close_path();
+        }
+        self.x += dx;
+        self.y += dy;
+        self.pb.move_to(self.point());
+        self.open = true;
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) -> Option<()> {
+        if !self.open {
+            return None;
+        }
+        self.x += dx;
+        self.y += dy;
+        self.pb.line_to(self.point());
+        Some(())
+    }
+
+    fn curve_by(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) -> Option<()> {
+        if !self.open {
+            return None;
+        }
+        self.x += dx1;
+        self.y += dy1;
+        let p1 = self.point();
+        self.x += dx2;
+        self.y += dy2;
+        let p2 = self.point();
+        self.x += dx3;
+        self.y += dy3;
+        let p3 = self.point();
+        self.pb.cubic_to(p1, p2, p3);
+        Some(())
+    }
+}
+
+fn subr_bias(count: usize) -> i32 {
+    if count < 1240 { 107 }
+    else if count < 33900 { 1131 }
+    else { 32768 }
+}
+
+
+
+#[inline(always)]
+fn read_u8(data: &[u8], at: usize) -> Option<u8> {
+    data.get(at).copied()
+}
+
+#[inline(always)]
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    let b = data.get(at .. at + 2)?;
+    Some(u16::from_be_bytes([b[0], b[1]]))
+}
+
+#[inline(always)]
+fn read_i16(data: &[u8], at: usize) -> Option<i16> {
+    read_u16(data, at).map(|v| v as i16)
+}
+
+#[inline(always)]
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    let b = data.get(at .. at + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+
+/// big endian reader.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos:  usize,
+}
+
+impl<'a> Reader<'a> {
+    #[inline(always)]
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Reader { data, pos }
+    }
+
+    #[inline(always)]
+    fn done(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    #[inline(always)]
+    fn skip(&mut self, n: usize) -> Option<()> {
+        if self.pos + n > self.data.len() {
+            return None;
+        }
+        self.pos += n;
+        Some(())
+    }
+
+    #[inline(always)]
+    fn u8(&mut self) -> Option<u8> {
+        let v = read_u8(self.data, self.pos)?;
+        self.pos += 1;
+        Some(v)
+    }
+
+    #[inline(always)]
+    fn u16(&mut self) -> Option<u16> {
+        let v = read_u16(self.data, self.pos)?;
+        self.pos += 2;
+        Some(v)
+    }
+
+    #[inline(always)]
+    fn i16(&mut self) -> Option<i16> {
+        self.u16().map(|v| v as i16)
+    }
+
+    #[inline(always)]
+    fn u32(&mut self) -> Option<u32> {
+        let v = read_u32(self.data, self.pos)?;
+        self.pos += 4;
+        Some(v)
+    }
+
+    #[inline(always)]
+    fn f2dot14(&mut self) -> Option<f32> {
+        Some(self.i16()? as f32 / 16384.0)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::Verb;
+
+    fn be16(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// a font with `outline_tables` and the required tables.
+    /// - the required tables come last, so any truncation cuts one of them.
+    fn font_data(version: &[u8; 4], num_glyphs: u16, outline_tables: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+
+        let mut maxp = vec![0; 6];
+        maxp[4..6].copy_from_slice(&num_glyphs.to_be_bytes());
+
+        let mut hhea = vec![0; 36];
+        hhea[4..6].copy_from_slice(&800i16.to_be_bytes());
+        hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes());
+        hhea[34..36].copy_from_slice(&1u16.to_be_bytes());
+
+        let hmtx = be16(&[500, 0]);
+
+        let mut tables = outline_tables.to_vec();
+        tables.extend([
+            (b"head", &head[..]),
+            (b"maxp", &maxp[..]),
+            (b"hhea", &hhea[..]),
+            (b"hmtx", &hmtx[..]),
+        ]);
+
+        let mut data = Vec::new();
+        data.extend(version);
+        data.extend((tables.len() as u16).to_be_bytes());
+        data.extend([0; 6]);
+
+        let mut offset = 12 + 16*tables.len();
+        for (tag, table) in &tables {
+            data.extend(*tag);
+            data.extend([0; 4]);
+            data.extend((offset as u32).to_be_bytes());
+            data.extend((table.len() as u32).to_be_bytes());
+            offset += (table.len() + 3) & !3;
+        }
+        for (_, table) in &tables {
+            data.extend(*table);
+            data.resize((data.len() + 3) & !3, 0);
+        }
+        data
+    }
+
+    /// - cmap: 'A'..='C' -> 1..=3 by delta, 'a' -> 5 and 'b' -> 0 by the glyph id array.
+    /// - glyph 1 is a triangle.
+    /// - glyph 2 is glyph 1, plus glyph 1 scaled by 0.5 and moved by (100, 0).
+    /// - glyph 3 refers to itself.
+    fn truetype_font() -> Vec<u8> {
+        let mut format4 = be16(&[
+            4, 0, 0, 2*3, 4, 1, 2,
+            0x43, 0x62, 0xffff,
+            0,
+            0x41, 0x61, 0xffff,
+            1u16.wrapping_sub(0x41), 0, 1,
+            0, 4, 0,
+            5, 0,
+        ]);
+        let len = format4.len() as u16;
+        format4[2..4].copy_from_slice(&len.to_be_bytes());
+
+        let mut cmap = be16(&[0, 1, 3, 1]);
+        cmap.extend(12u32.to_be_bytes());
+        cmap.extend(format4);
+
+        let mut glyf = Vec::new();
+
+        glyf.extend(be16(&[1, 0, 0, 100, 100, 2, 0]));
+        glyf.extend([1, 1, 1]);
+        glyf.extend(be16(&[0, 100, -100i16 as u16, 0, 0, 100]));
+        glyf.push(0);
+
+        glyf.extend(be16(&[-1i16 as u16, 0, 0, 150, 100, 0x0022, 1]));
+        glyf.extend([0, 0]);
+        glyf.extend(be16(&[0x000b, 1, 100, 0, 0x2000]));
+
+        glyf.extend(be16(&[-1i16 as u16, 0, 0, 0, 0, 0x0002, 3]));
+        glyf.extend([0, 0]);
+
+        let loca = be16(&[0, 0, 15, 28, 36]);
+        assert_eq!(glyf.len(), 2*36);
+
+        font_data(&0x00010000u32.to_be_bytes(), 4, &[
+            (b"cmap", &cmap[..]),
+            (b"loca", &loca[..]),
+            (b"glyf", &glyf[..]),
+        ])
+    }
+
+    fn cff_index(objects: &[&[u8]]) -> Vec<u8> {
+        let mut index = be16(&[objects.len() as u16]);
+        index.push(1);
+        let mut offset = 1;
+        index.push(offset);
+        for object in objects {
+            offset += object.len() as u8;
+            index.push(offset);
+        }
+        for object in objects {
+            index.extend(*object);
+        }
+        index
+    }
+
+    fn cff_int(v: usize) -> Vec<u8> {
+        let mut bytes = vec![29];
+        bytes.extend((v as u32).to_be_bytes());
+        bytes
+    }
+
+    /// - glyph 1 moves to (100, 100), calls a local and a global subroutine,
+    ///   each of which draws a line.
+    /// - glyph 2 calls a local subroutine that doesn't exist.
+    fn cff_font() -> Vec<u8> {
+        let names   = cff_index(&[b"a"]);
+        let strings = cff_index(&[]);
+
+        // -200 100 rlineto return.
+        let global_subrs = cff_index(&[&[251, 92, 239, 5, 11]]);
+
+        // 200 0 rlineto return.
+        let local_subrs = cff_index(&[&[247, 92, 139, 5, 11]]);
+
+        let char_strings = cff_index(&[
+            // endchar.
+            &[14],
+            // 500 (width) 100 100 rmoveto, -107 callsubr, -107 callgsubr, endchar.
+            &[248, 136, 239, 239, 21, 32, 10, 32, 29, 14],
+            // 0 callsubr.
+            &[139, 10],
+        ]);
+
+        let mut private = cff_int(6);
+        private.push(19);
+
+        let top_dict = |char_strings_at: usize, private_at: usize| {
+            let mut dict = cff_int(char_strings_at);
+            dict.push(17);
+            dict.extend(cff_int(private.len()));
+            dict.extend(cff_int(private_at));
+            dict.push(18);
+            dict
+        };
+
+        let char_strings_at = 4 + names.len() + cff_index(&[&top_dict(0, 0)]).len() + strings.len() + global_subrs.len();
+        let private_at = char_strings_at + char_strings.len();
+
+        let mut cff = vec![1, 0, 4, 1];
+        cff.extend(names);
+        cff.extend(cff_index(&[&top_dict(char_strings_at, private_at)]));
+        cff.extend(strings);
+        cff.extend(global_subrs);
+        assert_eq!(cff.len(), char_strings_at);
+        cff.extend(char_strings);
+        cff.extend(&private);
+        cff.extend(local_subrs);
+
+        font_data(b"OTTO", 3, &[(b"CFF ", &cff[..])])
+    }
+
+    fn outline(font: &Font, glyph: u16) -> Option<(Vec<Verb>, Vec<F32x2>)> {
+        let mut pb = PathBuilder::new();
+        if !font.glyph_outline(glyph, Transform::ID(), &mut pb) {
+            return None;
+        }
+        let path = pb.build();
+        let path = path.path();
+        Some((path.verbs().to_vec(), path.points().to_vec()))
+    }
+
+    fn points(ps: &[[f32; 2]]) -> Vec<F32x2> {
+        ps.iter().map(|p| (*p).into()).collect()
+    }
+
+    const TRIANGLE: [Verb; 5] = [Verb::BeginClosed, Verb::Line, Verb::Line, Verb::Line, Verb::EndClosed];
+
+
+    #[test]
+    fn cmap_format_4() {
+        let data = truetype_font();
+        let font = Font::parse(&data).unwrap();
+
+        assert_eq!(font.glyph_index('A'), Some(1));
+        assert_eq!(font.glyph_index('C'), Some(3));
+        assert_eq!(font.glyph_index('a'), Some(5));
+
+        // between segments, before the first, unmapped in the glyph id array.
+        assert_eq!(font.glyph_index('D'), None);
+        assert_eq!(font.glyph_index('@'), None);
+        assert_eq!(font.glyph_index('b'), None);
+
+        // the sentinel segment and beyond the bmp.
+        assert_eq!(font.glyph_index('\u{ffff}'), None);
+        assert_eq!(font.glyph_index('\u{1f600}'), None);
+    }
+
+    #[test]
+    fn glyf_outlines() {
+        let data = truetype_font();
+        let font = Font::parse(&data).unwrap();
+
+        assert_eq!(font.units_per_em(), 1000);
+        assert_eq!(font.num_glyphs(), 4);
+        assert_eq!((font.ascender(), font.descender()), (800, -200));
+        assert_eq!(font.advance(2), 500);
+
+        assert_eq!(outline(&font, 0), Some((vec![], vec![])));
+
+        assert_eq!(outline(&font, 1), Some((
+            TRIANGLE.to_vec(),
+            points(&[[0.0, 0.0], [100.0, 0.0], [0.0, 100.0], [0.0, 0.0]]))));
+
+        assert_eq!(outline(&font, 2), Some((
+            [TRIANGLE, TRIANGLE].concat(),
+            points(&[
+                [  0.0, 0.0], [100.0, 0.0], [  0.0, 100.0], [  0.0, 0.0],
+                [100.0, 0.0], [150.0, 0.0], [100.0,  50.0], [100.0, 0.0],
+            ]))));
+
+        // recursive components and glyphs past `num_glyphs`.
+        assert_eq!(outline(&font, 3), None);
+        assert_eq!(outline(&font, 4), None);
+    }
+
+    #[test]
+    fn cff_subroutines() {
+        let data = cff_font();
+        let font = Font::parse(&data).unwrap();
+        assert!(matches!(font.outlines, Outlines::Cff(_)));
+
+        assert_eq!(outline(&font, 0), Some((vec![], vec![])));
+
+        assert_eq!(outline(&font, 1), Some((
+            TRIANGLE.to_vec(),
+            points(&[[100.0, 100.0], [300.0, 100.0], [100.0, 200.0], [100.0, 100.0]]))));
+
+        assert_eq!(outline(&font, 2), None);
+    }
+
+    #[test]
+    fn malformed() {
+        fn exercise(font: &Font) {
+            for c in ['A', 'a', 'b', 'z', '\u{1f600}'] {
+                font.glyph_index(c);
+            }
+            for glyph in 0..font.num_glyphs().min(8) {
+                font.advance(glyph);
+                font.kerning(glyph, 1);
+                outline(font, glyph);
+            }
+        }
+
+        assert!(Font::parse(&[]).is_none());
+
+        for data in [truetype_font(), cff_font()] {
+            for n in 0..data.len() {
+                assert!(Font::parse(&data[..n]).is_none());
+            }
+
+            for i in 0..data.len() {
+                let mut data = data.clone();
+                data[i] ^= 0xff;
+                if let Some(font) = Font::parse(&data) {
+                    exercise(&font);
+                }
+            }
+        }
+
+        let mut seed = 0x2545f491u32;
+        for _ in 0..64 {
+            let mut data = b"OTTO".to_vec();
+            data.extend((0..1024).map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed >> 24) as u8
+            }));
+            if let Some(font) = Font::parse(&data) {
+                exercise(&font);
+            }
+        }
+    }
+}
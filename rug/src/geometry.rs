@@ -1,7 +1,7 @@
 use sti::simd::*;
 
 
-trait F32x2Ext {
+pub trait F32x2Ext {
     fn normalized(self) -> Self;
     fn rotated_acw(self) -> Self;
     fn rotated_cw(self) -> F32x2;
@@ -329,6 +329,16 @@ pub fn cubic(p0: F32x2, p1: F32x2, p2: F32x2, p3: F32x2) -> Cubic {
 }
 
 impl Cubic {
+    #[inline(always)]
+    pub fn eval(self, t: f32) -> F32x2 {
+        let l10 = self.p0.lerp(self.p1, t);
+        let l11 = self.p1.lerp(self.p2, t);
+        let l12 = self.p2.lerp(self.p3, t);
+        let l20 = l10.lerp(l11, t);
+        let l21 = l11.lerp(l12, t);
+        l20.lerp(l21, t)
+    }
+
     pub fn split(self, t: f32) -> (Cubic, Cubic) {
         let l10 = self.p0.lerp(self.p1, t);
         let l11 = self.p1.lerp(self.p2, t);
@@ -90,6 +90,17 @@ impl<'a, T: Copy> ImgMut<'a, T> {
         unsafe { core::slice::from_raw_parts_mut(self.data, self.len) }
     }
 
+    #[inline(always)]
+    pub fn reborrow(&mut self) -> ImgMut<T> {
+        ImgMut {
+            data:    self.data,
+            len:     self.len,
+            size:    self.size,
+            stride:  self.stride,
+            phantom: PhantomData,
+        }
+    }
+
     #[track_caller]
     #[inline(always)]
     pub fn write_n<const N: usize>(&mut self, x: usize, y: usize, vs: [T; N]) {
@@ -100,6 +111,27 @@ impl<'a, T: Copy> ImgMut<'a, T> {
         }
     }
 
+    /// the `size` pixels at `pos`, clamped to the image.
+    /// - safety: views, that are used at the same time, must not overlap.
+    #[inline(always)]
+    pub unsafe fn sub_img_mut_unck(&self, pos: [u32; 2], size: [u32; 2]) -> ImgMut<T> {
+        let [x, y] = pos.map(|p| p as usize);
+        let x = x.min(self.width()  as usize);
+        let y = y.min(self.height() as usize);
+        let w = (size[0] as usize).min(self.width()  as usize - x);
+        let h = (size[1] as usize).min(self.height() as usize - y);
+
+        let begin = y*self.stride + x;
+        let len = if w == 0 || h == 0 { 0 } else { (h - 1)*self.stride + w };
+        ImgMut {
+            data:    unsafe { self.data.add(begin) },
+            len,
+            size:    U32x2::new(w as u32, h as u32),
+            stride:  self.stride,
+            phantom: PhantomData,
+        }
+    }
+
     pub fn copy_expand<U: Copy, const N: usize, F: Fn(U) -> [T; N]>
         (&mut self, src: &Img<U>, to: I32x2, f: F)
     where T: Send, U: Sync, F: Sync
@@ -118,7 +150,8 @@ impl<'a, T: Copy> ImgMut<'a, T> {
         let stride = self.stride;
         let data = self.data_mut();
         let data_begin = begin_y*stride + begin_x;
-        let data_end = data_begin + h*stride;
+        // the last row of sub images ends at their width.
+        let data_end = (data_begin + h*stride).min(data.len());
 
         use forkyou::{Spliterator, SpliterChunksMut};
         forkyou::for_each(data[data_begin..data_end].spliter_chunks_mut(stride).enumerate(), |(dy, data)| {
@@ -169,6 +202,77 @@ impl<'a, T: Copy> Clone for Img<'a, T> {
 }
 
 
+/// how colors of a target store alpha.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    /// colors are independent of alpha, like the command buffer's colors.
+    Straight,
+    /// colors are multiplied by alpha.
+    Premultiplied,
+}
+
+/// an image of one of the supported target formats.
+/// - the names give the channels in memory order (on little endian).
+pub enum DynImgMut<'a> {
+    /// `0xAABBGGRR`.
+    Rgba8  (ImgMut<'a, u32>, AlphaMode),
+    /// `0xAARRGGBB`, like the command buffer's colors.
+    Bgra8  (ImgMut<'a, u32>, AlphaMode),
+    /// `0bRRRRRGGGGGGBBBBB`.
+    /// - `Premultiplied` is the result composited onto black.
+    Rgb565 (ImgMut<'a, u16>, AlphaMode),
+    A8     (ImgMut<'a, u8>),
+    Rgba16 (ImgMut<'a, [u16; 4]>, AlphaMode),
+    RgbaF32(ImgMut<'a, [f32; 4]>, AlphaMode),
+}
+
+impl<'a> DynImgMut<'a> {
+    pub fn size(&self) -> U32x2 {
+        match self {
+            DynImgMut::Rgba8  (img, _) => img.size(),
+            DynImgMut::Bgra8  (img, _) => img.size(),
+            DynImgMut::Rgb565 (img, _) => img.size(),
+            DynImgMut::A8     (img)    => img.size(),
+            DynImgMut::Rgba16 (img, _) => img.size(),
+            DynImgMut::RgbaF32(img, _) => img.size(),
+        }
+    }
+
+    pub fn alpha_mode(&self) -> AlphaMode {
+        match self {
+            DynImgMut::Rgba8  (_, alpha) |
+            DynImgMut::Bgra8  (_, alpha) |
+            DynImgMut::Rgb565 (_, alpha) |
+            DynImgMut::Rgba16 (_, alpha) |
+            DynImgMut::RgbaF32(_, alpha) => *alpha,
+            // the alpha channel is the same either way.
+            DynImgMut::A8(_) => AlphaMode::Premultiplied,
+        }
+    }
+
+    /// the `size` pixels at `pos`, clamped to the image.
+    /// - safety: views, that are used at the same time, must not overlap.
+    pub unsafe fn sub_img_mut_unck(&self, pos: [u32; 2], size: [u32; 2]) -> DynImgMut {
+        unsafe { match self {
+            DynImgMut::Rgba8  (img, alpha) => DynImgMut::Rgba8  (img.sub_img_mut_unck(pos, size), *alpha),
+            DynImgMut::Bgra8  (img, alpha) => DynImgMut::Bgra8  (img.sub_img_mut_unck(pos, size), *alpha),
+            DynImgMut::Rgb565 (img, alpha) => DynImgMut::Rgb565 (img.sub_img_mut_unck(pos, size), *alpha),
+            DynImgMut::A8     (img)        => DynImgMut::A8     (img.sub_img_mut_unck(pos, size)),
+            DynImgMut::Rgba16 (img, alpha) => DynImgMut::Rgba16 (img.sub_img_mut_unck(pos, size), *alpha),
+            DynImgMut::RgbaF32(img, alpha) => DynImgMut::RgbaF32(img.sub_img_mut_unck(pos, size), *alpha),
+        }}
+    }
+}
+
+/// `u32` images are straight alpha `Rgba8`.
+impl<'a, 'b> From<&'a mut ImgMut<'b, u32>> for DynImgMut<'a> {
+    #[inline(always)]
+    fn from(img: &'a mut ImgMut<'b, u32>) -> Self {
+        DynImgMut::Rgba8(img.reborrow(), AlphaMode::Straight)
+    }
+}
+
+
 pub struct Image<T: Copy, A: Alloc = GlobalAlloc> {
     data:   Vec<T, A>,
     size:   U32x2,
@@ -234,6 +338,25 @@ impl<T: Copy, A: Alloc> Image<T, A> {
         }
     }
 
+    /// the `size` pixels at `pos`.
+    #[track_caller]
+    #[inline(always)]
+    pub fn sub_img(&self, pos: [u32; 2], size: [u32; 2]) -> Img<T> {
+        let [x, y] = pos.map(|p| p as usize);
+        let [w, h] = size.map(|s| s as usize);
+        assert!(x + w <= self.width() as usize && y + h <= self.height() as usize);
+
+        let begin = y*self.stride + x;
+        let len = if w == 0 || h == 0 { 0 } else { (h - 1)*self.stride + w };
+        Img {
+            data:    unsafe { self.data.as_ptr().add(begin) } as *mut T, // is only used as `*const T`.
+            len,
+            size:    size.into(),
+            stride:  self.stride,
+            phantom: PhantomData,
+        }
+    }
+
 
     #[inline(always)]
     pub fn size(&self) -> U32x2 { self.size }
@@ -247,6 +370,10 @@ impl<T: Copy, A: Alloc> Image<T, A> {
     #[inline(always)]
     pub fn stride(&self) -> usize { self.stride }
 
+    /// the number of bytes allocated.
+    #[inline(always)]
+    pub fn memory(&self) -> usize { self.data.cap() * core::mem::size_of::<T>() }
+
 
     #[track_caller]
     #[inline(always)]
@@ -4,6 +4,14 @@ pub mod image;
 pub mod path;
 pub mod stroke;
 pub mod rasterizer;
+pub mod blend;
+pub mod blur;
+pub mod filter;
+pub mod font;
+pub mod mask_cache;
 pub mod cmd;
+pub mod serialize;
+pub mod svg;
+pub mod pdf;
 pub mod renderer;
 
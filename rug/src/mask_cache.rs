@@ -0,0 +1,500 @@
+use sti::simd::*;
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::geometry::*;
+use crate::image::*;
+use crate::path::{Path, Verb};
+use crate::rasterizer::{Rasterizer, FillRule};
+use crate::stroke::{StrokeStyle, StrokeBuffers, stroke_with};
+
+
+/*  coverage mask cache.
+
+    masks are keyed by the contents of the path, the fill rule or
+    stroke style, the linear part of the transform, and the fractional
+    part of its translation, rounded to `SUBPIXEL_STEPS` steps per pixel.
+    so drawing the same path at another integer offset reuses its mask.
+
+    the key only holds a hash of the path, so each entry keeps a copy
+    of its path, which is compared on a hit.
+    on a hash collision, the other path keeps the entry,
+    and the colliding path isn't cached.
+    strokes are keyed by the unstroked path and only stroked on a miss.
+
+    masks are packed into shelves of an atlas image.
+    when the atlas is full, all masks are evicted.
+*/
+
+
+pub const SUBPIXEL_STEPS: u32 = 4;
+
+/// zero columns left and right of each mask in the atlas.
+/// - raster rects are aligned to 4 pixels, so they can extend
+///   up to 3 pixels beyond the mask.
+const PADDING: u32 = 3;
+
+
+/// what's rasterized of a path.
+#[derive(Clone, Copy)]
+pub enum MaskShape<'a> {
+    Fill(FillRule),
+    Stroke(StrokeStyle<'a>),
+}
+
+/// a mask in the atlas of a `MaskCache`.
+#[derive(Clone, Copy)]
+pub struct CachedMask {
+    /// integer rect in target pixels, that the mask covers.
+    pub rect: Rect,
+
+    /// position of `rect.min` in the atlas.
+    pos: [u32; 2],
+}
+
+
+pub struct MaskCache {
+    atlas: Image<f32>,
+    max_mask_size: u32,
+
+    entries: HashMap<MaskKey, MaskEntry>,
+
+    // the paths of the entries.
+    verbs:  Vec<Verb>,
+    points: Vec<F32x2>,
+    dashes: Vec<f32>,
+
+    shelves: Vec<Shelf>,
+    shelves_end: u32,
+
+    raster_image: Image<f32>,
+    stroke: StrokeBuffers,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct MaskKey {
+    path:     u64,
+    shape:    ShapeKey,
+    linear:   [u32; 4],
+    subpixel: [u32; 2],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum ShapeKey {
+    Fill   { even_odd: bool },
+    Stroke { width: u32, cap: u8, join: u8, miter_limit: u32, dash_offset: u32 },
+}
+
+#[derive(Clone, Copy)]
+struct MaskEntry {
+    /// position in the atlas, right of the left padding.
+    pos:  [u32; 2],
+    size: [u32; 2],
+
+    /// of the mask's top left corner, relative to the
+    /// integer part of the translation.
+    offset: [i32; 2],
+
+    /// ranges in `verbs`, `points`, and `dashes`.
+    verbs:  [u32; 2],
+    points: [u32; 2],
+    dashes: [u32; 2],
+}
+
+struct Shelf {
+    y:      u32,
+    height: u32,
+    end_x:  u32,
+}
+
+
+impl MaskCache {
+    /// - `atlas_size` is the width and height of the atlas in pixels.
+    ///   masks larger than a quarter of that aren't cached.
+    pub fn new(atlas_size: u32) -> Self {
+        MaskCache {
+            atlas: Image::new([atlas_size, atlas_size]),
+            max_mask_size: atlas_size / 4,
+            entries: HashMap::new(),
+            verbs:  Vec::new(),
+            points: Vec::new(),
+            dashes: Vec::new(),
+            shelves: Vec::new(),
+            shelves_end: 0,
+            raster_image: Image::new([0, 0]),
+            stroke: StrokeBuffers::new(),
+        }
+    }
+
+    /// evicts all masks.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.verbs.clear();
+        self.points.clear();
+        self.dashes.clear();
+        self.shelves.clear();
+        self.shelves_end = 0;
+    }
+
+    #[inline(always)]
+    pub fn num_masks(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// the mask of `shape` of `path`, rasterizing and caching it, if necessary.
+    /// - returns `None`, if the mask can't be cached.
+    pub fn lookup(&mut self, path: Path, shape: MaskShape, tfx: Transform) -> Option<CachedMask> {
+        let steps = SUBPIXEL_STEPS as f32;
+
+        let (key, t_integer, subpixel) = MaskKey::new(path, shape, tfx);
+
+        let entry = match self.entries.get(&key) {
+            Some(entry) => {
+                if !self.same_path(entry, path, shape) {
+                    return None;
+                }
+                *entry
+            }
+
+            None => {
+                let mut local_tfx = tfx;
+                local_tfx.columns[2] = F32x2::new(subpixel[0]/steps, subpixel[1]/steps);
+
+                let entry = self.insert(path, shape, local_tfx)?;
+                self.entries.insert(key, entry);
+                entry
+            }
+        };
+
+        let min = F32x2::new(
+            t_integer[0] + entry.offset[0] as f32,
+            t_integer[1] + entry.offset[1] as f32);
+        let size = F32x2::new(entry.size[0] as f32, entry.size[1] as f32);
+
+        Some(CachedMask {
+            rect: Rect { min, max: min + size },
+            pos:  entry.pos,
+        })
+    }
+
+    /// the pixels of `mask` at `origin` with `size`, in target pixels.
+    /// - the pixels must be within `mask.rect` vertically, and may extend
+    ///   up to `PADDING` pixels beyond it horizontally.
+    #[track_caller]
+    pub fn mask_img(&self, mask: CachedMask, origin: F32x2, size: U32x2) -> Img<f32> {
+        let x = mask.pos[0] as i32 + (origin.x() - mask.rect.min.x()) as i32;
+        let y = mask.pos[1] as i32 + (origin.y() - mask.rect.min.y()) as i32;
+        assert!(x >= mask.pos[0] as i32 - PADDING as i32 && y >= mask.pos[1] as i32);
+        assert!(x + size.x() as i32 <= mask.pos[0] as i32 + mask.rect.width() as i32 + PADDING as i32);
+
+        self.atlas.sub_img([x as u32, y as u32], *size)
+    }
+
+    fn same_path(&self, entry: &MaskEntry, path: Path, shape: MaskShape) -> bool {
+        let range = |r: [u32; 2]| r[0] as usize .. r[1] as usize;
+        let verbs  = &self.verbs [range(entry.verbs)];
+        let points = &self.points[range(entry.points)];
+        let dashes = &self.dashes[range(entry.dashes)];
+
+        let same_f32 = |a: &f32, b: &f32| a.to_bits() == b.to_bits();
+
+        verbs == path.verbs()
+        && points.len() == path.points().len()
+        && points.iter().zip(path.points()).all(|(a, b)|
+            same_f32(&a.x(), &b.x()) && same_f32(&a.y(), &b.y()))
+        && dashes.len() == shape_dashes(shape).len()
+        && dashes.iter().zip(shape_dashes(shape)).all(|(a, b)| same_f32(a, b))
+    }
+
+    fn insert(&mut self, path: Path, shape: MaskShape, tfx: Transform) -> Option<MaskEntry> {
+        let (raster_path, fill_rule) = match shape {
+            MaskShape::Fill(fill_rule) => (path, fill_rule),
+            MaskShape::Stroke(style) => (stroke_with(path, &style, &mut self.stroke), FillRule::NonZero),
+        };
+
+        let aabb = tfx.aabb_transform(raster_path.aabb());
+        if !aabb.valid() {
+            return None;
+        }
+
+        let min = [aabb.min.x().floor(), aabb.min.y().floor()];
+        let max = [aabb.max.x().ceil(),  aabb.max.y().ceil()];
+        let size = [0, 1].map(|i| (max[i] - min[i]) as u32);
+        if size[0] == 0 || size[1] == 0
+        || size[0] > self.max_mask_size || size[1] > self.max_mask_size {
+            return None;
+        }
+
+        let mut raster_tfx = tfx;
+        raster_tfx.columns[2] -= F32x2::new(min[0], min[1]);
+
+        let mut r = Rasterizer::new(&mut self.raster_image, size);
+        r.fill_rule = fill_rule;
+        r.fill_path(raster_path, &raster_tfx);
+        r.accumulate();
+
+        let padded = [size[0] + 2*PADDING, size[1]];
+        let pos = match self.allocate(padded) {
+            Some(pos) => pos,
+            None => {
+                self.clear();
+                self.allocate(padded)?
+            }
+        };
+
+        for y in 0..size[1] as usize {
+            for x in 0..padded[0] as usize {
+                let mask_x = x as i32 - PADDING as i32;
+                let coverage =
+                    if mask_x >= 0 && mask_x < size[0] as i32 {
+                        self.raster_image[(mask_x as usize, y)]
+                    }
+                    else { 0.0 };
+                self.atlas[(pos[0] as usize + x, pos[1] as usize + y)] = coverage;
+            }
+        }
+
+        let range_at = |begin: usize, len: usize| [begin as u32, (begin + len) as u32];
+        let verbs  = range_at(self.verbs.len(),  path.verbs().len());
+        let points = range_at(self.points.len(), path.points().len());
+        let dashes = range_at(self.dashes.len(), shape_dashes(shape).len());
+        self.verbs .extend_from_slice(path.verbs());
+        self.points.extend_from_slice(path.points());
+        self.dashes.extend_from_slice(shape_dashes(shape));
+
+        Some(MaskEntry {
+            pos: [pos[0] + PADDING, pos[1]],
+            size,
+            offset: min.map(|m| m as i32),
+            verbs, points, dashes,
+        })
+    }
+
+    fn allocate(&mut self, size: [u32; 2]) -> Option<[u32; 2]> {
+        let [w, h] = size;
+        let atlas_w = self.atlas.width();
+        let atlas_h = self.atlas.height();
+
+        // first shelf that fits, without wasting more than half of its height.
+        for shelf in &mut self.shelves {
+            if h <= shelf.height && 2*h >= shelf.height && shelf.end_x + w <= atlas_w {
+                let pos = [shelf.end_x, shelf.y];
+                shelf.end_x += w;
+                return Some(pos);
+            }
+        }
+
+        if w > atlas_w || self.shelves_end + h > atlas_h {
+            return None;
+        }
+
+        let y = self.shelves_end;
+        self.shelves.push(Shelf { y, height: h, end_x: w });
+        self.shelves_end += h;
+        Some([0, y])
+    }
+}
+
+
+impl MaskKey {
+    /// - returns the key, the integer part of the translation,
+    ///   and its fractional part in subpixel steps.
+    fn new(path: Path, shape: MaskShape, tfx: Transform) -> (MaskKey, [f32; 2], [f32; 2]) {
+        let steps = SUBPIXEL_STEPS as f32;
+
+        let t = tfx.columns[2];
+        let t_steps   = [t.x(), t.y()].map(|t| (t*steps).round());
+        let t_integer = t_steps.map(|t| (t/steps).floor());
+        let subpixel  = [0, 1].map(|i| t_steps[i] - t_integer[i]*steps);
+
+        let [a, b] = *tfx.columns[0];
+        let [c, d] = *tfx.columns[1];
+
+        let key = MaskKey {
+            path:     path_hash(path, shape),
+            shape:    ShapeKey::new(shape),
+            linear:   [a, b, c, d].map(f32::to_bits),
+            subpixel: subpixel.map(|s| s as u32),
+        };
+        (key, t_integer, subpixel)
+    }
+}
+
+impl ShapeKey {
+    fn new(shape: MaskShape) -> Self {
+        match shape {
+            MaskShape::Fill(fill_rule) =>
+                ShapeKey::Fill { even_odd: fill_rule == FillRule::EvenOdd },
+
+            MaskShape::Stroke(style) =>
+                ShapeKey::Stroke {
+                    width: style.width.to_bits(),
+                    cap:   style.cap  as u8,
+                    join:  style.join as u8,
+                    miter_limit: style.miter_limit.to_bits(),
+                    dash_offset: style.dash_offset.to_bits(),
+                },
+        }
+    }
+}
+
+#[inline]
+fn shape_dashes<'a>(shape: MaskShape<'a>) -> &'a [f32] {
+    match shape {
+        MaskShape::Fill(_)       => &[],
+        MaskShape::Stroke(style) => style.dashes,
+    }
+}
+
+fn path_hash(path: Path, shape: MaskShape) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for verb in path.verbs() {
+        (*verb as u8).hash(&mut hasher);
+    }
+    for p in path.points() {
+        p.x().to_bits().hash(&mut hasher);
+        p.y().to_bits().hash(&mut hasher);
+    }
+    for dash in shape_dashes(shape) {
+        dash.to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::PathBuilder;
+    use crate::cmd::*;
+    use crate::renderer::{RenderParams, render, render_cached};
+
+    fn square(x: f32, y: f32, size: f32) -> crate::path::PathBuf {
+        let mut pb = PathBuilder::new();
+        pb.move_to([x, y]);
+        pb.line_to([x + size, y]);
+        pb.line_to([x + size, y + size]);
+        pb.line_to([x, y + size]);
+        pb.close_path();
+        pb.build()
+    }
+
+    fn translate(x: f32, y: f32) -> Transform {
+        Transform::translate(F32x2::new(x, y))
+    }
+
+    const FILL: MaskShape = MaskShape::Fill(FillRule::NonZero);
+
+    #[test]
+    fn integer_offset_hit() {
+        let path = square(1.0, 2.0, 10.0);
+        let mut cache = MaskCache::new(256);
+
+        let a = cache.lookup(path.path(), FILL, translate(0.25, 0.0)).unwrap();
+        let b = cache.lookup(path.path(), FILL, translate(10.25, 5.0)).unwrap();
+        assert_eq!(cache.num_masks(), 1);
+
+        assert_eq!(a.pos, b.pos);
+        assert_eq!(b.rect.min.x(), a.rect.min.x() + 10.0);
+        assert_eq!(b.rect.min.y(), a.rect.min.y() + 5.0);
+        assert_eq!(b.rect.max.x(), a.rect.max.x() + 10.0);
+        assert_eq!(b.rect.max.y(), a.rect.max.y() + 5.0);
+    }
+
+    #[test]
+    fn subpixel_miss() {
+        let path = square(1.0, 2.0, 10.0);
+        let mut cache = MaskCache::new(256);
+
+        let a = cache.lookup(path.path(), FILL, translate(0.25, 0.0)).unwrap();
+        let b = cache.lookup(path.path(), FILL, translate(0.5, 0.0)).unwrap();
+        assert_eq!(cache.num_masks(), 2);
+        assert_ne!(a.pos, b.pos);
+
+        // rounds to the same bucket.
+        cache.lookup(path.path(), FILL, translate(0.45, 0.0)).unwrap();
+        assert_eq!(cache.num_masks(), 2);
+    }
+
+    #[test]
+    fn collision_rejected() {
+        let path_a = square(1.0, 2.0, 10.0);
+        let path_b = square(3.0, 4.0, 10.0);
+        let tfx = translate(0.0, 0.0);
+        let mut cache = MaskCache::new(256);
+
+        cache.lookup(path_a.path(), FILL, tfx).unwrap();
+
+        // fake a hash collision: `path_b`'s key maps to `path_a`'s entry.
+        let (key_a, _, _) = MaskKey::new(path_a.path(), FILL, tfx);
+        let (key_b, _, _) = MaskKey::new(path_b.path(), FILL, tfx);
+        let entry_a = cache.entries[&key_a];
+        cache.entries.insert(key_b, entry_a);
+        let num_points = cache.points.len();
+
+        assert!(cache.lookup(path_b.path(), FILL, tfx).is_none());
+        assert_eq!(cache.num_masks(), 2);
+        assert_eq!(cache.points.len(), num_points);
+
+        // the original entry is still hit.
+        let a = cache.lookup(path_a.path(), FILL, tfx).unwrap();
+        assert_eq!(a.pos, entry_a.pos);
+    }
+
+    #[test]
+    fn eviction() {
+        // masks up to 16x16 pixels are cached.
+        let mut cache = MaskCache::new(64);
+
+        let mut evicted = false;
+        for i in 0..100 {
+            let path = square(i as f32, 0.0, 10.0);
+            assert!(cache.lookup(path.path(), FILL, translate(0.0, 0.0)).is_some());
+
+            if cache.num_masks() < i + 1 {
+                assert_eq!(cache.num_masks(), 1);
+                evicted = true;
+                break;
+            }
+        }
+        assert!(evicted);
+
+        // too large.
+        let path = square(0.0, 0.0, 20.0);
+        assert!(cache.lookup(path.path(), FILL, translate(0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn cached_matches_uncached() {
+        let cmd_buf = CmdBuf::new(|cb| {
+            let path = cb.build_path(|pb| {
+                pb.move_to([5.3, 4.1]);
+                pb.cubic_to([40.0, -10.0], [60.0, 50.0], [30.7, 45.2]);
+                pb.quad_to([10.0, 40.0], [5.3, 4.1]);
+                pb.close_path();
+            });
+            cb.push(Cmd::FillPath { path, fill_rule: FillRule::NonZero, paint: Paint::Solid(0xff2060c0), opacity: 1.0, blend: crate::blend::BlendMode::SrcOver });
+        });
+
+        let params = RenderParams { clear: 0xffffffff, tfx: Transform::ID(), load: false };
+        let size = [67, 53];
+
+        let mut expected = Image::<u32>::new(size);
+        render(&cmd_buf, &params, &mut expected.img_mut());
+
+        let mut cache = MaskCache::new(256);
+        for _ in 0..2 {
+            let mut cached = Image::<u32>::new(size);
+            render_cached(&cmd_buf, &params, &mut cache, &mut cached.img_mut());
+            assert_eq!(cache.num_masks(), 1);
+
+            for (a, b) in cached.data().iter().zip(expected.data()) {
+                for (a, b) in a.to_le_bytes().iter().zip(b.to_le_bytes()) {
+                    assert!((*a as i32 - b as i32).abs() <= 1);
+                }
+            }
+        }
+    }
+}
@@ -168,6 +168,12 @@ impl PathBuilder {
     pub fn build(&mut self) -> PathBuf<GlobalAlloc> {
         self.build_in(GlobalAlloc)
     }
+
+    /// the number of bytes allocated.
+    pub fn memory(&self) -> usize {
+        self.verbs.cap()  * core::mem::size_of::<Verb>()
+        + self.points.cap() * core::mem::size_of::<F32x2>()
+    }
 }
 
 
@@ -195,6 +201,62 @@ impl<A: Alloc> RawPathBuilder<A> {
         self.verbs.clear();
         self.points.clear();
     }
+
+    /// the number of bytes allocated.
+    pub fn memory(&self) -> usize {
+        self.verbs.cap()  * core::mem::size_of::<Verb>()
+        + self.points.cap() * core::mem::size_of::<F32x2>()
+    }
+}
+
+
+
+/// reusable memory for a single path.
+/// - unlike `PathBuilder::build`, `store` doesn't allocate,
+///   once the storage is large enough.
+pub struct PathStorage {
+    blocks: Vec<PathBlock>,
+}
+
+#[derive(Clone, Copy)]
+#[repr(align(16))]
+struct PathBlock([u8; 16]);
+
+impl PathStorage {
+    pub fn new() -> Self {
+        PathStorage { blocks: Vec::new() }
+    }
+
+    /// stores the path of `pb`, replacing the previous path.
+    pub fn store(&mut self, pb: &mut PathBuilder) -> Path<'_> {
+        if pb.in_path {
+            pb._end_path(Verb::EndOpen);
+        }
+
+        // ensure aabb is valid.
+        let aabb =
+            if pb.verbs.len() > 0 { pb.aabb }
+            else { Rect::ZERO() };
+
+        let layout = PathData::layout(pb.verbs.len(), pb.points.len()).unwrap(); // @temp
+        debug_assert!(layout.align() <= core::mem::align_of::<PathBlock>());
+
+        let num_blocks = (layout.size() + core::mem::size_of::<PathBlock>() - 1) / core::mem::size_of::<PathBlock>();
+        self.blocks.clear();
+        self.blocks.reserve_exactly(num_blocks);
+
+        let data: NonNull<PathData> = NonNull::new(self.blocks.as_mut_ptr()).unwrap().cast();
+
+        // verbs/points are valid by construction.
+        unsafe { PathData::write(data, &pb.verbs, &pb.points, aabb, layout) };
+
+        Path { data, phantom: PhantomData }
+    }
+
+    /// the number of bytes allocated.
+    pub fn memory(&self) -> usize {
+        self.blocks.cap() * core::mem::size_of::<PathBlock>()
+    }
 }
 
 
@@ -211,28 +273,10 @@ pub struct PathBuf<A: Alloc = GlobalAlloc> {
 impl<A: Alloc> PathBuf<A> {
     #[inline(always)]
     pub unsafe fn new_in(verbs: &[Verb], points: &[F32x2], aabb: Rect, alloc: A) -> Self {
-        let num_verbs  = verbs.len().try_into().unwrap();  // @temp
-        let num_points = points.len().try_into().unwrap(); // @temp
-
         let layout = PathData::layout(verbs.len(), points.len()).unwrap(); // @temp
         let data: NonNull<PathData> = alloc.alloc(layout).unwrap().cast(); // @temp
 
-        unsafe {
-            data.as_ptr().write(PathData {
-                refs: AtomicU32::new(1),
-                aabb,
-                num_verbs, num_points,
-            });
-
-            let vp: *mut Verb  = cat_next_mut(data.as_ptr(), 1);
-            let pp: *mut F32x2 = cat_next_mut(vp, verbs.len());
-
-            let end: *mut u8 = cat_next_mut(pp, points.len());
-            debug_assert_eq!(end as usize - data.as_ptr() as usize, layout.size());
-
-            core::ptr::copy(verbs.as_ptr(),  vp, verbs.len());
-            core::ptr::copy(points.as_ptr(), pp, points.len());
-        }
+        unsafe { PathData::write(data, verbs, points, aabb, layout) };
 
         Self { data, alloc }
     }
@@ -303,6 +347,31 @@ impl PathData {
                 Layout::array::<Verb>(verb_count).ok()?)?,
             Layout::array::<F32x2>(point_count).ok()?)
     }
+
+    /// writes the header, verbs, and points to `data`.
+    /// - `data` must be valid for `layout`, which must be the path's layout.
+    #[inline(always)]
+    unsafe fn write(data: NonNull<PathData>, verbs: &[Verb], points: &[F32x2], aabb: Rect, layout: Layout) {
+        let num_verbs:  u32 = verbs.len().try_into().unwrap();  // @temp
+        let num_points: u32 = points.len().try_into().unwrap(); // @temp
+
+        unsafe {
+            data.as_ptr().write(PathData {
+                refs: AtomicU32::new(1),
+                aabb,
+                num_verbs, num_points,
+            });
+
+            let vp: *mut Verb  = cat_next_mut(data.as_ptr(), 1);
+            let pp: *mut F32x2 = cat_next_mut(vp, verbs.len());
+
+            let end: *mut u8 = cat_next_mut(pp, points.len());
+            debug_assert_eq!(end as usize - data.as_ptr() as usize, layout.size());
+
+            core::ptr::copy(verbs.as_ptr(),  vp, verbs.len());
+            core::ptr::copy(points.as_ptr(), pp, points.len());
+        }
+    }
 }
 
 
@@ -312,6 +381,10 @@ pub struct Path<'a> {
     phantom: PhantomData<&'a ()>,
 }
 
+// paths are immutable.
+unsafe impl<'a> Send for Path<'a> {}
+unsafe impl<'a> Sync for Path<'a> {}
+
 impl<'a> Path<'a> {
     #[inline(always)]
     fn data(&self) -> &PathData { unsafe { self.data.as_ref() } }
@@ -0,0 +1,659 @@
+use sti::simd::*;
+
+use core::fmt::{self, Write};
+
+use crate::geometry::{Transform, Rect};
+use crate::path::{Path, Verb};
+use crate::stroke::{StrokeStyle, LineCap, LineJoin};
+use crate::rasterizer::FillRule;
+use crate::blend::BlendMode;
+use crate::cmd::*;
+use crate::renderer::{RenderParams, gradient_tfx};
+
+
+/*  pdf export.
+
+    writes a single page pdf with uncompressed content streams.
+    one unit of the command buffer is one point on the page.
+
+    transforms & clips are `q`/`Q` pairs, groups are transparency
+    group form xobjects. gradients are shading patterns, whose
+    repeat & reflect spread methods are expanded into stitching
+    functions over the area of the painted path.
+
+    not representable in pdf (without rasterizing):
+        - filters, `BlurLayer`. ignored.
+        - the blur of `DropShadow`. drawn as hard shadows.
+        - conic gradients. filled with the color of their first stop.
+        - the alpha of gradient stops. ignored.
+        - porter-duff operators other than `SrcOver`.
+          drawn with `SrcOver`.
+        - the `Pad` & `Reflect` extend modes of image paints.
+          images are always repeated.
+*/
+
+
+/// converts `cmd_buf` into a pdf document with one page of `size` points.
+pub fn to_pdf(cmd_buf: &CmdBuf, params: &RenderParams, size: [u32; 2]) -> Vec<u8> {
+    let mut w = PdfWriter {
+        cmd_buf,
+        size,
+        objects: Vec::new(),
+        ext_gstates: Vec::new(),
+        patterns: Vec::new(),
+        xobjects: Vec::new(),
+        images: Vec::new(),
+        streams: Vec::new(),
+    };
+
+    let catalog   = w.reserve();
+    let pages     = w.reserve();
+    let page      = w.reserve();
+    let resources = w.reserve();
+
+    // pdf's y axis points up.
+    let flip = Transform { columns: [
+        F32x2::new(1.0, 0.0),
+        F32x2::new(0.0, -1.0),
+        F32x2::new(0.0, size[1] as f32),
+    ]};
+    let base = flip * params.tfx;
+
+    let mut content = String::new();
+    let clear = params.clear;
+    if !params.load && clear >> 24 != 0 {
+        let gs = w.ext_gstate(alpha(clear), BlendMode::SrcOver);
+        let _ = writeln!(content, "/GS{} gs {} rg 0 0 {} {} re f", gs, rgb(clear), size[0], size[1]);
+    }
+    let _ = writeln!(content, "{} cm", matrix(base));
+    w.streams.push(Stream { content, tfx: base, abs_base: Transform::ID(), saves: Vec::new(), group: None });
+
+    for i in 0..cmd_buf.num_cmds() {
+        w.cmd(cmd_buf.cmd(i), resources);
+    }
+
+    // close unbalanced groups.
+    while w.streams.len() > 1 {
+        w.end_group(resources);
+    }
+
+    let stream = w.streams.pop().unwrap();
+    let contents = w.add_stream("", stream.content.as_bytes());
+
+    w.set(catalog, format!("<< /Type /Catalog /Pages {} 0 R >>", pages).into_bytes());
+    w.set(pages, format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>", page).into_bytes());
+    w.set(page, format!(
+        "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources {} 0 R /Contents {} 0 R \
+        /Group << /S /Transparency /CS /DeviceRGB >> >>",
+        pages, size[0], size[1], resources, contents).into_bytes());
+
+    let mut dict = String::from("<< /ProcSet [/PDF /ImageC]");
+    let ext_gstates: Vec<u32> = w.ext_gstates.iter().map(|g| g.id).collect();
+    for (name, prefix, ids) in [("ExtGState", "GS", &ext_gstates), ("Pattern", "P", &w.patterns), ("XObject", "X", &w.xobjects)] {
+        if ids.is_empty() { continue }
+        let _ = write!(dict, " /{} <<", name);
+        for (i, id) in ids.iter().enumerate() {
+            let _ = write!(dict, " /{}{} {} 0 R", prefix, i, id);
+        }
+        dict.push_str(" >>");
+    }
+    dict.push_str(" >>");
+    w.set(resources, dict.into_bytes());
+
+    w.finish(catalog)
+}
+
+
+struct PdfWriter<'c> {
+    cmd_buf: &'c CmdBuf,
+    size:    [u32; 2],
+
+    /// object `i` has id `i + 1`.
+    objects: Vec<Vec<u8>>,
+
+    /// resources, named by their prefix and index.
+    ext_gstates: Vec<ExtGState>,
+    patterns:    Vec<u32>,
+    xobjects:    Vec<u32>,
+
+    /// image xobjects: image id, interpolate, object id.
+    images: Vec<(usize, bool, u32)>,
+
+    /// the page and the open groups.
+    streams: Vec<Stream>,
+}
+
+struct ExtGState {
+    alpha: u32,
+    blend: &'static str,
+    id:    u32,
+}
+
+struct Stream {
+    content: String,
+
+    /// maps command coordinates to the stream's default space,
+    /// which pattern matrices are relative to.
+    tfx: Transform,
+
+    /// maps the stream's default space to the page's.
+    abs_base: Transform,
+
+    /// the `tfx` to restore on `Q`.
+    saves: Vec<Transform>,
+
+    /// opacity & blend mode.
+    group: Option<(f32, BlendMode)>,
+}
+
+impl<'c> PdfWriter<'c> {
+    fn reserve(&mut self) -> u32 {
+        self.objects.push(Vec::new());
+        self.objects.len() as u32
+    }
+
+    fn set(&mut self, id: u32, object: Vec<u8>) {
+        self.objects[id as usize - 1] = object;
+    }
+
+    fn add(&mut self, object: String) -> u32 {
+        self.objects.push(object.into_bytes());
+        self.objects.len() as u32
+    }
+
+    fn add_stream(&mut self, dict: &str, data: &[u8]) -> u32 {
+        let mut object = format!("<< {} /Length {} >>\nstream\n", dict, data.len()).into_bytes();
+        object.extend_from_slice(data);
+        object.extend_from_slice(b"\nendstream");
+        self.objects.push(object);
+        self.objects.len() as u32
+    }
+
+    fn finish(self, root: u32) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+
+        let mut offsets = Vec::with_capacity(self.objects.len());
+        for (i, object) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            out.extend_from_slice(object);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref = out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.objects.len() + 1);
+        for offset in offsets {
+            let _ = write!(table, "{:010} 00000 n \n", offset);
+        }
+        let _ = write!(table, "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.objects.len() + 1, root, xref);
+        out.extend_from_slice(table.as_bytes());
+        out
+    }
+
+    #[inline]
+    fn stream(&mut self) -> &mut Stream {
+        self.streams.last_mut().unwrap()
+    }
+
+    fn cmd(&mut self, cmd: &Cmd, resources: u32) {
+        match *cmd {
+            Cmd::FillPath { path, fill_rule, paint, opacity, blend } => {
+                let aabb = path.aabb();
+                let Some((paint, alpha)) = self.paint(paint, aabb, aabb, false) else { return };
+                let gs = self.ext_gstate(alpha * opacity, blend);
+
+                let op = match fill_rule {
+                    FillRule::NonZero => "f",
+                    FillRule::EvenOdd => "f*",
+                };
+                let s = &mut self.stream().content;
+                let _ = writeln!(s, "q /GS{} gs {}", gs, paint);
+                path_ops(s, path);
+                let _ = writeln!(s, "{} Q", op);
+            }
+
+            Cmd::StrokePath { path, style, paint, opacity, blend } => {
+                // the pattern maps through the unstroked aabb, matching `render`.
+                let aabb = path.aabb();
+                let reach = style.width * style.miter_limit.max(1.0);
+                let coverage = aabb.grow(F32x2::splat(reach));
+                let Some((paint, alpha)) = self.paint(paint, aabb, coverage, true) else { return };
+                let gs = self.ext_gstate(alpha * opacity, blend);
+
+                let s = &mut self.stream().content;
+                let _ = writeln!(s, "q /GS{} gs {}", gs, paint);
+                stroke_ops(s, &style);
+                path_ops(s, path);
+                s.push_str("S Q\n");
+            }
+
+            Cmd::DropShadow { path, offset, blur_radius: _, color } => {
+                let gs = self.ext_gstate(alpha(color), BlendMode::SrcOver);
+                let s = &mut self.stream().content;
+                let _ = writeln!(s, "q /GS{} gs {} rg 1 0 0 1 {} {} cm", gs, rgb(color), N(offset.x()), N(offset.y()));
+                path_ops(s, path);
+                s.push_str("f Q\n");
+            }
+
+            Cmd::BlurLayer { .. } => (),
+
+            Cmd::PushTransform { tfx } => {
+                let stream = self.stream();
+                stream.saves.push(stream.tfx);
+                stream.tfx = stream.tfx * tfx;
+                let _ = writeln!(stream.content, "q {} cm", matrix(tfx));
+            }
+
+            Cmd::PushClipPath { path, fill_rule } => {
+                let stream = self.stream();
+                stream.saves.push(stream.tfx);
+                stream.content.push_str("q\n");
+                path_ops(&mut stream.content, path);
+                stream.content.push_str(match fill_rule {
+                    FillRule::NonZero => "W n\n",
+                    FillRule::EvenOdd => "W* n\n",
+                });
+            }
+
+            Cmd::PopTransform |
+            Cmd::PopClip => {
+                // unbalanced pops are ignored.
+                let stream = self.stream();
+                if let Some(tfx) = stream.saves.pop() {
+                    stream.tfx = tfx;
+                    stream.content.push_str("Q\n");
+                }
+            }
+
+            Cmd::BeginGroup { opacity, blend, filter: _ } => {
+                let parent = self.stream();
+                let abs_base = parent.abs_base * parent.tfx;
+                self.streams.push(Stream {
+                    content: String::new(),
+                    tfx: Transform::ID(),
+                    abs_base,
+                    saves: Vec::new(),
+                    group: Some((opacity, blend)),
+                });
+            }
+
+            Cmd::EndGroup => {
+                // unbalanced ends are ignored.
+                if self.streams.len() > 1 {
+                    self.end_group(resources);
+                }
+            }
+        }
+    }
+
+    fn end_group(&mut self, resources: u32) {
+        let mut stream = self.streams.pop().unwrap();
+        for _ in 0..stream.saves.len() {
+            stream.content.push_str("Q\n");
+        }
+
+        // the group covers the page.
+        let page = Rect { min: F32x2::ZERO(), max: F32x2::new(self.size[0] as f32, self.size[1] as f32) };
+        let bbox = match stream.abs_base.invert(0.00001) {
+            Some(inv) => inv.aabb_transform(page),
+            None => page,
+        };
+
+        let form = self.add_stream(&format!(
+            "/Type /XObject /Subtype /Form /BBox [{} {} {} {}] \
+            /Group << /S /Transparency /CS /DeviceRGB >> /Resources {} 0 R",
+            N(bbox.min.x()), N(bbox.min.y()), N(bbox.max.x()), N(bbox.max.y()), resources),
+            stream.content.as_bytes());
+        self.xobjects.push(form);
+        let xobject = self.xobjects.len() - 1;
+
+        let (opacity, blend) = stream.group.unwrap();
+        let gs = self.ext_gstate(opacity, blend);
+        let _ = writeln!(self.stream().content, "q /GS{} gs /X{} Do Q", gs, xobject);
+    }
+
+    fn ext_gstate(&mut self, alpha: f32, blend: BlendMode) -> usize {
+        let alpha = alpha.clamp(0.0, 1.0);
+        let blend = blend_name(blend);
+
+        let existing = self.ext_gstates.iter().position(|g| g.alpha == alpha.to_bits() && g.blend == blend);
+        if let Some(i) = existing {
+            return i;
+        }
+
+        let id = self.add(format!("<< /Type /ExtGState /ca {} /CA {} /BM /{} >>", N(alpha), N(alpha), blend));
+        self.ext_gstates.push(ExtGState { alpha: alpha.to_bits(), blend, id });
+        self.ext_gstates.len() - 1
+    }
+
+    /// returns the operators that set the paint, and its alpha.
+    /// - `bounds` are the path bounds, that `GradientUnits::Relative` refer to.
+    /// - `coverage` bounds the painted area, in path coordinates.
+    /// - returns `None`, if nothing should be painted.
+    fn paint(&mut self, paint: Paint, bounds: Rect, coverage: Rect, stroke: bool) -> Option<(String, f32)> {
+        let cmd_buf = self.cmd_buf;
+        let (color_op, pattern_cs, pattern_op) =
+            if stroke { ("RG", "CS", "SCN") }
+            else      { ("rg", "cs", "scn") };
+
+        let pattern = match paint {
+            Paint::Solid(color) => {
+                return Some((format!("{} {}", rgb(color), color_op), alpha(color)));
+            }
+
+            Paint::ConicGradient(id) => {
+                let g = cmd_buf.conic_gradient(id);
+                let color = g.stops.first().map_or(0, |stop| stop.color);
+                return Some((format!("{} {}", rgb(color), color_op), alpha(color)));
+            }
+
+            Paint::LinearGradient(id) => {
+                let g = cmd_buf.linear_gradient(id);
+                let g_tfx = gradient_tfx(g.units, g.tfx, bounds)?;
+
+                let d = g.p1 - g.p0;
+                let dd = d.x()*d.x() + d.y()*d.y();
+                let (t0, t1) = spread_range(g.spread, g_tfx, coverage, |p| {
+                    let v = p - g.p0;
+                    let t = (v.x()*d.x() + v.y()*d.y()) / dd;
+                    (t, t)
+                });
+
+                let function = self.spread_function(g.stops, g.spread, t0, t1);
+                let p0 = g.p0 + F32x2::splat(t0 as f32)*d;
+                let p1 = g.p0 + F32x2::splat(t1 as f32)*d;
+                let shading = self.add(format!(
+                    "<< /ShadingType 2 /ColorSpace /DeviceRGB /Coords [{} {} {} {}] \
+                    /Domain [{} {}] /Function {} /Extend [true true] >>",
+                    N(p0.x()), N(p0.y()), N(p1.x()), N(p1.y()), t0, t1, function));
+
+                let tfx = self.stream().tfx * g_tfx;
+                self.add(format!("<< /Type /Pattern /PatternType 2 /Shading {} 0 R /Matrix [{}] >>",
+                    shading, matrix(tfx)))
+            }
+
+            Paint::RadialGradient(id) => {
+                let g = cmd_buf.radial_gradient(id);
+                let g_tfx = gradient_tfx(g.units, g.tfx, bounds)?;
+
+                // a point is inside the circle at `t`, if it is within `fr + t*grow` of the focal point.
+                let grow = g.cr - g.fr - (g.cp - g.fp).length();
+                let (_, t1) = spread_range(g.spread, g_tfx, coverage, |p| {
+                    let t = ((p - g.fp).length() - g.fr) / grow;
+                    (0.0, if grow > 0.0 { t } else { f32::INFINITY })
+                });
+
+                let function = self.spread_function(g.stops, g.spread, 0, t1);
+                let c = g.fp + F32x2::splat(t1 as f32)*(g.cp - g.fp);
+                let r = g.fr + t1 as f32*(g.cr - g.fr);
+                let shading = self.add(format!(
+                    "<< /ShadingType 3 /ColorSpace /DeviceRGB /Coords [{} {} {} {} {} {}] \
+                    /Domain [0 {}] /Function {} /Extend [true true] >>",
+                    N(g.fp.x()), N(g.fp.y()), N(g.fr), N(c.x()), N(c.y()), N(r), t1, function));
+
+                let tfx = self.stream().tfx * g_tfx;
+                self.add(format!("<< /Type /Pattern /PatternType 2 /Shading {} 0 R /Matrix [{}] >>",
+                    shading, matrix(tfx)))
+            }
+
+            Paint::Image { image, tfx, sampling, extend: _ } => {
+                let interpolate = sampling != ImageSampling::Nearest;
+                let xobject = self.image_xobject(image, interpolate);
+
+                let [w, h] = cmd_buf.image(image).size;
+                let tfx = self.stream().tfx * tfx;
+                // image xobjects fill the unit square, with the first row at the top.
+                self.add_stream(&format!(
+                    "/Type /Pattern /PatternType 1 /PaintType 1 /TilingType 1 \
+                    /BBox [0 0 {} {}] /XStep {} /YStep {} /Matrix [{}] \
+                    /Resources << /XObject << /I0 {} 0 R >> >>",
+                    w, h, w, h, matrix(tfx), xobject),
+                    format!("{} 0 0 -{} 0 {} cm /I0 Do", w, h, h).as_bytes())
+            }
+        };
+
+        self.patterns.push(pattern);
+        Some((format!("/Pattern {} /P{} {}", pattern_cs, self.patterns.len() - 1, pattern_op), 1.0))
+    }
+
+    /// returns a function reference over `[t0, t1]`,
+    /// that maps each unit interval to `stops`.
+    fn spread_function(&mut self, stops: &[GradientStop], spread: SpreadMethod, t0: i32, t1: i32) -> String {
+        let function = self.stops_function(stops);
+        if matches!(spread, SpreadMethod::Pad) {
+            return format!("{} 0 R", function);
+        }
+
+        let mut functions = String::new();
+        let mut bounds = String::new();
+        let mut encode = String::new();
+        for k in t0..t1 {
+            let _ = write!(functions, "{} 0 R ", function);
+            if k > t0 {
+                let _ = write!(bounds, "{} ", k);
+            }
+            let reversed = matches!(spread, SpreadMethod::Reflect) && k.rem_euclid(2) == 1;
+            encode.push_str(if reversed { "1 0 " } else { "0 1 " });
+        }
+        let id = self.add(format!(
+            "<< /FunctionType 3 /Domain [{} {}] /Functions [{}] /Bounds [{}] /Encode [{}] >>",
+            t0, t1, functions.trim_end(), bounds.trim_end(), encode.trim_end()));
+        format!("{} 0 R", id)
+    }
+
+    /// returns a function object over `[0, 1]`, that interpolates `stops`.
+    fn stops_function(&mut self, stops: &[GradientStop]) -> u32 {
+        let color = |c: u32| {
+            let [r, g, b] = [16, 8, 0].map(|s| ((c >> s) & 0xff) as f32 / 255.0);
+            format!("[{} {} {}]", N(r), N(g), N(b))
+        };
+
+        if stops.len() < 2 {
+            let c = color(stops.first().map_or(0, |stop| stop.color));
+            return self.add(format!("<< /FunctionType 2 /Domain [0 1] /C0 {} /C1 {} /N 1 >>", c, c));
+        }
+
+        // extend the first & last stop to 0 & 1.
+        let mut points = Vec::with_capacity(stops.len() + 2);
+        let mut last = 0.0;
+        points.push((0.0, stops[0].color));
+        for stop in stops {
+            last = stop.offset.clamp(last, 1.0);
+            points.push((last, stop.color));
+        }
+        points.push((1.0, stops[stops.len() - 1].color));
+
+        let mut functions = String::new();
+        let mut bounds = String::new();
+        let mut encode = String::new();
+        for (i, segment) in points.windows(2).enumerate() {
+            let _ = write!(functions, "<< /FunctionType 2 /Domain [0 1] /C0 {} /C1 {} /N 1 >> ",
+                color(segment[0].1), color(segment[1].1));
+            if i > 0 {
+                let _ = write!(bounds, "{} ", N(segment[0].0));
+            }
+            encode.push_str("0 1 ");
+        }
+        self.add(format!(
+            "<< /FunctionType 3 /Domain [0 1] /Functions [{}] /Bounds [{}] /Encode [{}] >>",
+            functions.trim_end(), bounds.trim_end(), encode.trim_end()))
+    }
+
+    fn image_xobject(&mut self, image: ImageId, interpolate: bool) -> u32 {
+        let index = image.usize();
+        if let Some(entry) = self.images.iter().find(|e| e.0 == index && e.1 == interpolate) {
+            return entry.2;
+        }
+
+        let source = self.cmd_buf.image(image);
+        let [w, h] = source.size;
+        let n = (w*h) as usize;
+
+        let mut rgb = Vec::with_capacity(3*n);
+        let mut alpha = Vec::with_capacity(n);
+        for p in &source.pixels[..n] {
+            rgb.extend_from_slice(&[(p >> 16) as u8, (p >> 8) as u8, *p as u8]);
+            alpha.push((p >> 24) as u8);
+        }
+
+        let smask = self.add_stream(&format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray \
+            /BitsPerComponent 8 /Interpolate {}", w, h, interpolate), &alpha);
+        let id = self.add_stream(&format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+            /BitsPerComponent 8 /Interpolate {} /SMask {} 0 R", w, h, interpolate, smask), &rgb);
+
+        self.images.push((index, interpolate, id));
+        id
+    }
+}
+
+
+/// returns the integer range of gradient parameters covering `coverage`.
+/// - `t_at` returns the min & max parameter at a point in gradient coordinates.
+fn spread_range<F: Fn(F32x2) -> (f32, f32)>(spread: SpreadMethod, g_tfx: Transform, coverage: Rect, t_at: F) -> (i32, i32) {
+    const MAX_REPEATS: f32 = 64.0;
+
+    if matches!(spread, SpreadMethod::Pad) {
+        return (0, 1);
+    }
+
+    let rect = match g_tfx.invert(0.00001) {
+        Some(inv) => inv.aabb_transform(coverage),
+        None => return (0, 1),
+    };
+
+    let mut t0 = 0.0f32;
+    let mut t1 = 1.0f32;
+    for p in [rect.min, F32x2::new(rect.max.x(), rect.min.y()), F32x2::new(rect.min.x(), rect.max.y()), rect.max] {
+        let (lo, hi) = t_at(p);
+        if lo.is_finite() { t0 = t0.min(lo) }
+        t1 = t1.max(if hi.is_nan() { MAX_REPEATS } else { hi });
+    }
+
+    let t0 = t0.floor().max(-MAX_REPEATS) as i32;
+    let t1 = t1.ceil().min(MAX_REPEATS) as i32;
+    (t0, t1.max(t0 + 1))
+}
+
+
+/// pdf numbers can't use exponents.
+struct N(f32);
+
+impl fmt::Display for N {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let v = if self.0.is_finite() { self.0 } else { 0.0 };
+        let s = format!("{:.4}", v);
+        let s = s.trim_end_matches('0').trim_end_matches('.');
+        f.write_str(if s == "-0" || s.is_empty() { "0" } else { s })
+    }
+}
+
+fn path_ops(out: &mut String, path: Path) {
+    let points = path.points();
+    let mut i = 0;
+    let mut next = || {
+        i += 1;
+        points[i - 1]
+    };
+
+    let mut last = F32x2::ZERO();
+    for verb in path.verbs() {
+        match verb {
+            Verb::BeginOpen | Verb::BeginClosed => {
+                last = next();
+                let _ = writeln!(out, "{} {} m", N(last.x()), N(last.y()));
+            }
+
+            Verb::Line => {
+                last = next();
+                let _ = writeln!(out, "{} {} l", N(last.x()), N(last.y()));
+            }
+
+            Verb::Quad => {
+                // raise to a cubic.
+                let (c, p) = (next(), next());
+                let c1 = last + F32x2::splat(2.0/3.0)*(c - last);
+                let c2 = p    + F32x2::splat(2.0/3.0)*(c - p);
+                let _ = writeln!(out, "{} {} {} {} {} {} c",
+                    N(c1.x()), N(c1.y()), N(c2.x()), N(c2.y()), N(p.x()), N(p.y()));
+                last = p;
+            }
+
+            Verb::Cubic => {
+                let (c1, c2, p) = (next(), next(), next());
+                let _ = writeln!(out, "{} {} {} {} {} {} c",
+                    N(c1.x()), N(c1.y()), N(c2.x()), N(c2.y()), N(p.x()), N(p.y()));
+                last = p;
+            }
+
+            Verb::EndClosed => out.push_str("h\n"),
+            Verb::EndOpen => (),
+        }
+    }
+}
+
+fn stroke_ops(out: &mut String, style: &StrokeStyle) {
+    let cap = match style.cap {
+        LineCap::Butt   => 0,
+        LineCap::Round  => 1,
+        LineCap::Square => 2,
+    };
+    let join = match style.join {
+        LineJoin::Miter => 0,
+        LineJoin::Round => 1,
+        LineJoin::Bevel => 2,
+    };
+
+    let _ = write!(out, "{} w {} J {} j {} M ", N(style.width), cap, join, N(style.miter_limit.max(1.0)));
+    if !style.dashes.is_empty() {
+        out.push('[');
+        for dash in style.dashes {
+            let _ = write!(out, "{} ", N(*dash));
+        }
+        let _ = write!(out, "] {} d", N(style.dash_offset));
+    }
+    out.push('\n');
+}
+
+fn matrix(tfx: Transform) -> String {
+    let [a, b] = *tfx.columns[0];
+    let [c, d] = *tfx.columns[1];
+    let [e, f] = *tfx.columns[2];
+    format!("{} {} {} {} {} {}", N(a), N(b), N(c), N(d), N(e), N(f))
+}
+
+#[inline]
+fn rgb(argb: u32) -> String {
+    let [r, g, b] = [16, 8, 0].map(|s| ((argb >> s) & 0xff) as f32 / 255.0);
+    format!("{} {} {}", N(r), N(g), N(b))
+}
+
+#[inline]
+fn alpha(argb: u32) -> f32 {
+    (argb >> 24) as f32 / 255.0
+}
+
+fn blend_name(blend: BlendMode) -> &'static str {
+    use BlendMode::*;
+    match blend {
+        Multiply   => "Multiply",
+        Screen     => "Screen",
+        Overlay    => "Overlay",
+        Darken     => "Darken",
+        Lighten    => "Lighten",
+        ColorDodge => "ColorDodge",
+        ColorBurn  => "ColorBurn",
+        HardLight  => "HardLight",
+        SoftLight  => "SoftLight",
+        Difference => "Difference",
+        Exclusion  => "Exclusion",
+        Hue        => "Hue",
+        Saturation => "Saturation",
+        Color      => "Color",
+        Luminosity => "Luminosity",
+        _ => "Normal",
+    }
+}
+
@@ -9,12 +9,40 @@ use crate::image::*;
 use crate::path::*;
 
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FillRule {
     NonZero,
     EvenOdd,
 }
 
+impl FillRule {
+    /// maps the accumulated (signed, fractional) winding number to coverage.
+    #[inline(always)]
+    pub fn coverage(self, winding: f32) -> f32 {
+        match self {
+            FillRule::NonZero => winding.abs().min(1.0),
+            FillRule::EvenOdd => {
+                // triangle wave: 0 -> 0, 1 -> 1, 2 -> 0, ...
+                let w = winding.abs() % 2.0;
+                1.0 - (w - 1.0).abs()
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn coverage_x<const N: usize>(self, winding: F32x<N>) -> F32x<N>  where (): SimdLanes<N> {
+        match self {
+            FillRule::NonZero => winding.abs().at_most(F32x::splat(1.0)),
+            FillRule::EvenOdd => {
+                let w = winding.abs();
+                let k = (w * F32x::splat(0.5)).to_i32_unck().to_f32();
+                let w = w - F32x::splat(2.0)*k;
+                F32x::splat(1.0) - (w - F32x::splat(1.0)).abs()
+            }
+        }
+    }
+}
+
 
 // these are absolute and in pixel space.
 pub const ZERO_TOLERANCE:       f32 = 0.001;
@@ -28,10 +56,15 @@ const BUFFER_SIZE: usize = 32;
 pub struct Rasterizer<'a> {
     pub flatten_tolerance_sq: f32,
     pub flatten_recursion: u32,
+    pub fill_rule: FillRule,
     deltas: ImgMut<'a, f32>,
     size: F32x2,
     safe_size: F32x2,
     deltas_len: i32,
+    // the stored rows and columns, see `with_window`.
+    rows: [f32; 2],
+    row_offset: i32,
+    end_x: u32,
     // @todo: static vec.
     buffer: [[F32x2; 2]; BUFFER_SIZE],
     buffered: usize,
@@ -55,23 +88,39 @@ pub struct Rasterizer<'a> {
 
 impl<'a> Rasterizer<'a> {
     pub fn new<A: Alloc>(image: &'a mut Image<f32, A>, size: [u32; 2]) -> Self {
+        Self::with_window(image, size, [0, size[1]], size[0])
+    }
+
+    /// like `new`, but only computes the coverage of the rows `rows`,
+    /// left of `end_x`.
+    /// - the geometry is still clipped to `size`, so the coverage
+    ///   matches `new`'s exactly.
+    /// - the columns left of the window can't be skipped,
+    ///   they feed the accumulation.
+    pub fn with_window<A: Alloc>(image: &'a mut Image<f32, A>, size: [u32; 2], rows: [u32; 2], end_x: u32) -> Self {
         //spall::trace_scope!("rug::raster::new");
 
-        let size = U32x2::from_array(size);
-        let mask_size = size + U32x2::new(2, 1);
+        assert!(rows[0] <= rows[1] && rows[1] <= size[1] && end_x <= size[0]);
+
+        let mask_size = U32x2::new(size[0] + 2, rows[1] - rows[0] + 1);
         image.resize_and_clear(*mask_size, 0.0);
 
         let deltas = image.img_mut();
         let deltas_len = deltas.data().len().try_into().unwrap();
+        let row_offset = (rows[0] as usize * deltas.stride()).try_into().unwrap();
 
-        let size = size.as_i32().to_f32();
+        let size = U32x2::from_array(size).as_i32().to_f32();
         Rasterizer {
             flatten_tolerance_sq: FLATTEN_TOLERANCE_SQ,
             flatten_recursion: FLATTEN_RECURSION,
+            fill_rule: FillRule::NonZero,
             deltas,
             size,
             safe_size: size + F32x2::splat(0.9),
             deltas_len,
+            rows: rows.map(|r| r as f32),
+            row_offset,
+            end_x,
             buffer: [[F32x2::ZERO(); 2]; BUFFER_SIZE],
             buffered: 0,
         }
@@ -83,7 +132,9 @@ impl<'a> Rasterizer<'a> {
 
     #[inline(always)]
     pub fn is_invisible(&self, aabb: Rect) -> bool {
-        aabb.min.ge(self.size).any() || aabb.max.y() <= 0.0
+        aabb.min.x() >= self.size.x()
+        || aabb.min.y() >= self.rows[1]
+        || aabb.max.y() <= self.rows[0]
     }
 
     #[inline(always)]
@@ -205,16 +256,17 @@ impl<'a> Rasterizer<'a> {
             self.flush();
         }
 
-        let w = self.width() as usize;
+        let w = self.end_x as usize;
         let h = self.height() as usize;
 
+        let fill_rule = self.fill_rule;
         let mut deltas = self.deltas;
 
         for y in 0..h {
             let mut c = 0.0;
             for x in 0..w {
                 c += deltas[(x, y)];
-                deltas[(x, y)] = c.abs().min(1.0);
+                deltas[(x, y)] = fill_rule.coverage(c);
             }
         }
 
@@ -230,9 +282,10 @@ impl<'a> Rasterizer<'a> {
             self.flush();
         }
 
-        let w = self.width() as usize;
+        let w = self.end_x as usize;
         let h = self.height() as usize;
 
+        let fill_rule = self.fill_rule;
         let mut deltas = self.deltas;
 
         for y in 0..h {
@@ -250,7 +303,7 @@ impl<'a> Rasterizer<'a> {
                 d = d + shift::<8>(d);
                 c = c + d;
 
-                deltas.write_n(x, y, c.abs().at_most(F32x4::splat(1.0)).as_array());
+                deltas.write_n(x, y, fill_rule.coverage_x(c).as_array());
 
                 c = F32x4::splat(c[3]);
 
@@ -271,7 +324,7 @@ impl<'a> Rasterizer<'a> {
             let mut c = c[3];
             for x in aligned_w..w {
                 c += deltas[(x, y)];
-                deltas[(x, y)] = c.abs().min(1.0);
+                deltas[(x, y)] = fill_rule.coverage(c);
             }
         }
 
@@ -429,13 +482,13 @@ impl<'a> Rasterizer<'a> {
             debug_assert!(x_i.le(F32v::splat(r.size.x())).all()); // le because padding
 
             let x = x_i.to_i32_unck();
-            let o = row_base + x;
-
-            assert!(o.ge(I32v::splat(0)).all());
-            assert!(o.lt(I32v::splat(r.deltas_len - 1)).all());
+            let o = row_base + x - I32v::splat(r.row_offset);
 
             let deltas = r.deltas.data_mut().as_mut_ptr();
             for i in 0..WIDTH {
+                // rows outside of the window are dropped.
+                if o[i] < 0 || o[i] >= r.deltas_len - 1 { continue }
+
                 unsafe {
                     *deltas.add(o[i] as usize + 0) += delta_left[i];
                     *deltas.add(o[i] as usize + 1) += delta_right[i];
@@ -465,7 +518,7 @@ impl<'a> Rasterizer<'a> {
         let mut y_next = y_i0 + y_step + y_nudge;
 
         let     row_delta = stride.copysign(dy) as i32;
-        let mut row_base  = (stride * y_i0) as i32;
+        let mut row_base  = (stride * y_i0) as i32 - self.row_offset;
 
         for _ in 0..steps {
             let prev_base = row_base;
@@ -474,21 +527,24 @@ impl<'a> Rasterizer<'a> {
             row_base += row_delta;
 
             //println!("Segment(({}, {}), ({}, {})),", x_prev, y_prev, x, y);
-            add_delta(self, prev_base as isize as usize, y_prev, y);
+            add_delta(self, prev_base, y_prev, y);
 
             y_prev = y;
         }
 
-        debug_assert!(row_base == (stride * y_i1) as i32);
+        debug_assert!(row_base == (stride * y_i1) as i32 - self.row_offset);
 
         //println!("Segment(({}, {}), ({}, {})),", x_prev, y_prev, x1, y1);
-        add_delta(self, row_base as isize as usize, y_prev, y1);
+        add_delta(self, row_base, y_prev, y1);
 
 
         #[inline(always)]
-        unsafe fn add_delta(r: &mut Rasterizer, row_base: usize, y0: f32, y1: f32) {
+        unsafe fn add_delta(r: &mut Rasterizer, row_base: i32, y0: f32, y1: f32) {
+            // rows outside of the window are dropped.
+            if row_base < 0 || row_base >= r.deltas_len { return }
+
             let delta = y1 - y0;
-            r.deltas.data_mut()[row_base + 0] += delta;
+            r.deltas.data_mut()[row_base as usize] += delta;
         }
     }
 
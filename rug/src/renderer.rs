@@ -8,7 +8,12 @@ use crate::color::*;
 use crate::image::*;
 use crate::path::Path;
 use crate::cmd::*;
-use crate::rasterizer::Rasterizer;
+use crate::rasterizer::{Rasterizer, FillRule};
+use crate::stroke::{StrokeStyle, StrokeBuffers, stroke_with};
+use crate::blend::{BlendMode, StaticBlend, with_blend_mode, composite, unpremultiply};
+use crate::blur::{gaussian_box_radii, blur_extent, blur_mask, blur_layer};
+use crate::filter::{FilterBuffers, apply_filter};
+use crate::mask_cache::{MaskCache, MaskShape};
 
 
 #[derive(Clone, Copy)]
@@ -17,13 +22,9 @@ pub struct RenderParams {
 
     pub tfx: Transform,
 
-    // target format.
-    //  eventually maybe take DynImgMut, which is an enum,
-    //  cause there's a statically known set of supported 
-    //  image formats -- but that also means,
-    //  the `target` param already specifies the format).
-
-    // composite mode (whether base color is clear or old target values).
+    /// draw on top of the target's contents, instead of clearing to `clear`.
+    /// - the contents are read according to the target's `AlphaMode`.
+    pub load: bool,
 }
 
 /* implementation notes:
@@ -40,10 +41,482 @@ pub struct RenderParams {
         tbd.
 */
 
-// right, so this is the function, we'd put in a trait.
-// which means a renderer is a struct, which would enable
-// allocation caching, for example.
-pub fn render(cmd_buf: &CmdBuf, params: &RenderParams, target: &mut ImgMut<u32>) {
+/// renders `cmd_buf` into `target`.
+/// - `target` is a `DynImgMut` or a `&mut ImgMut<u32>` (rgba8).
+/// - allocates new buffers on each call, see `Renderer`.
+pub fn render(cmd_buf: &CmdBuf, params: &RenderParams, target: impl Into<DynImgMut<'_>>) {
+    Renderer::new().render(cmd_buf, params, target);
+}
+
+/// like `render`, but reuses the coverage masks of fills from `mask_cache`.
+/// - the cache can be shared across frames and command buffers.
+pub fn render_cached(cmd_buf: &CmdBuf, params: &RenderParams, mask_cache: &mut MaskCache, target: impl Into<DynImgMut<'_>>) {
+    Renderer::new().render_cached(cmd_buf, params, mask_cache, target);
+}
+
+
+/// renders command buffers, reusing its buffers across calls.
+/// - keep one around (eg: per window), to avoid allocating each frame.
+/// - the buffers only grow. use `free_memory` to release them.
+pub struct Renderer {
+    buffers: RenderBuffers,
+
+    // for `render_tiled`.
+    tiles: Vec<Tile>,
+    shadows: Vec<SharedShadow>,
+    worker_buffers: Vec<RenderBuffers>,
+
+    peak_memory: usize,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Renderer {
+            buffers: RenderBuffers::new(),
+            tiles: Vec::new(),
+            shadows: Vec::new(),
+            worker_buffers: Vec::new(),
+            peak_memory: 0,
+        }
+    }
+
+    pub fn render(&mut self, cmd_buf: &CmdBuf, params: &RenderParams, target: impl Into<DynImgMut<'_>>) {
+        let mut target = target.into();
+        let size = target.size();
+        render_impl(cmd_buf, params, None, &target, full_tile(size), None, None, &mut self.buffers);
+        write_back(&self.buffers.image, I32x2::ZERO(), &mut target);
+        self.update_peak_memory();
+    }
+
+    /// like `render`, but reuses the coverage masks of fills from `mask_cache`.
+    pub fn render_cached(&mut self, cmd_buf: &CmdBuf, params: &RenderParams, mask_cache: &mut MaskCache, target: impl Into<DynImgMut<'_>>) {
+        let mut target = target.into();
+        let size = target.size();
+        render_impl(cmd_buf, params, Some(mask_cache), &target, full_tile(size), None, None, &mut self.buffers);
+        write_back(&self.buffers.image, I32x2::ZERO(), &mut target);
+        self.update_peak_memory();
+    }
+
+    /// like `render`, but renders tiles of the target in parallel.
+    /// - the output is bit-identical to `render`.
+    /// - command buffers with effects, that read neighboring pixels
+    ///   (`BlurLayer` & filters), are rendered serially.
+    /// - drop shadows are blurred once, by the first tile, that draws them.
+    pub fn render_tiled(&mut self, cmd_buf: &CmdBuf, params: &RenderParams, target: impl Into<DynImgMut<'_>>) {
+        let target = target.into();
+        let size = target.size();
+        let width = (size.x() + 3) / 4 * 4;
+
+        let tiles_x = (width    + TILE_SIZE - 1) / TILE_SIZE;
+        let tiles_y = (size.y() + TILE_SIZE - 1) / TILE_SIZE;
+        let num_tiles = (tiles_x * tiles_y) as usize;
+        if num_tiles == 0 { return }
+
+        // the tiles keep their command lists across calls.
+        let tiles = &mut self.tiles;
+        tiles.truncate(num_tiles);
+        while tiles.len() < num_tiles {
+            tiles.push(Tile {
+                rect: Rect::ZERO(),
+                cmds: Vec::new(),
+            });
+        }
+        for ty in 0..tiles_y {
+            for tx in 0..tiles_x {
+                let min = U32x2::new(tx*TILE_SIZE, ty*TILE_SIZE);
+                let max = (min + U32x2::splat(TILE_SIZE)).min(U32x2::new(width, size.y()));
+
+                let tile = &mut tiles[(ty*tiles_x + tx) as usize];
+                tile.rect = Rect { min: min.as_i32().to_f32(), max: max.as_i32().to_f32() };
+                tile.cmds.clear();
+            }
+        }
+
+        if !bin_commands(cmd_buf, params.tfx, tiles, tiles_x) {
+            return self.render(cmd_buf, params, target);
+        }
+
+        // the slots keep their masks across calls.
+        let mut num_shadows = 0;
+        for i in 0..cmd_buf.num_cmds() {
+            if let Cmd::DropShadow { .. } = cmd_buf.cmd(i) {
+                if num_shadows == self.shadows.len() {
+                    self.shadows.push(SharedShadow {
+                        cmd:  0,
+                        mask: std::sync::Mutex::new(ShadowMask { ready: false, image: Image::with_clear([0, 0], 0.0) }),
+                    });
+                }
+                let shadow = &mut self.shadows[num_shadows];
+                shadow.cmd = i as u32;
+                shadow.mask.get_mut().unwrap().ready = false;
+                num_shadows += 1;
+            }
+        }
+
+        // tiles are handed out one at a time, so the busy parts
+        // of the target are spread across the workers.
+        // - the buffers are reused through a pool, which only grows
+        //   to the number of tiles rendered at the same time.
+        let pool = std::sync::Mutex::new(core::mem::take(&mut self.worker_buffers));
+        {
+            let target = &target;
+            let pool = &pool;
+            let shadows = &self.shadows[..num_shadows];
+
+            use forkyou::SpliterChunksMut;
+            forkyou::for_each(self.tiles[..].spliter_chunks_mut(1), |tile| {
+                let tile = &tile[0];
+
+                let mut buffers = pool.lock().unwrap().pop().unwrap_or_else(RenderBuffers::new);
+                render_impl(cmd_buf, params, None, target, tile.rect, Some(&tile.cmds), Some(shadows), &mut buffers);
+
+                // the tiles don't overlap.
+                let mut dst = unsafe {
+                    target.sub_img_mut_unck(*tile.rect.min.to_i32_unck().as_u32(), *tile.rect.size().to_i32_unck().as_u32())
+                };
+                write_back(&buffers.image, I32x2::ZERO(), &mut dst);
+
+                pool.lock().unwrap().push(buffers);
+            });
+        }
+        self.worker_buffers = pool.into_inner().unwrap();
+
+        self.update_peak_memory();
+    }
+
+
+    /// the number of bytes held by the buffers.
+    pub fn memory(&self) -> usize {
+        self.buffers.memory()
+        + self.tiles.capacity() * core::mem::size_of::<Tile>()
+        + self.tiles.iter().map(|t| t.memory()).sum::<usize>()
+        + self.shadows.capacity() * core::mem::size_of::<SharedShadow>()
+        + self.shadows.iter().map(|s| s.mask.lock().unwrap().image.memory()).sum::<usize>()
+        + self.worker_buffers.capacity() * core::mem::size_of::<RenderBuffers>()
+        + self.worker_buffers.iter().map(|b| b.memory()).sum::<usize>()
+    }
+
+    /// the max number of bytes held by the buffers,
+    /// since the renderer was created.
+    pub fn peak_memory(&self) -> usize {
+        self.peak_memory
+    }
+
+    /// releases the buffers.
+    pub fn free_memory(&mut self) {
+        self.buffers = RenderBuffers::new();
+        self.tiles = Vec::new();
+        self.shadows = Vec::new();
+        self.worker_buffers = Vec::new();
+    }
+
+    #[inline]
+    fn update_peak_memory(&mut self) {
+        // the buffers don't shrink during a render,
+        // so the peak is reached at the end.
+        self.peak_memory = self.peak_memory.max(self.memory());
+    }
+}
+
+
+/// width & height of the tiles of `render_tiled` in pixels.
+pub const TILE_SIZE: u32 = 128;
+
+/// like `render`, but renders tiles of the target in parallel.
+/// - allocates new buffers on each call, see `Renderer::render_tiled`.
+pub fn render_tiled(cmd_buf: &CmdBuf, params: &RenderParams, target: impl Into<DynImgMut<'_>>) {
+    Renderer::new().render_tiled(cmd_buf, params, target);
+}
+
+struct Tile {
+    /// integer rect in target pixels.
+    /// `rect.min.x()` and the width are aligned to 4.
+    rect: Rect,
+    /// indices of the commands, that affect the tile.
+    cmds: Vec<u32>,
+}
+
+impl Tile {
+    fn memory(&self) -> usize {
+        self.cmds.capacity() * core::mem::size_of::<u32>()
+    }
+}
+
+/// the blurred mask of a `DropShadow`, shared by the tiles.
+/// - the first tile, that draws the shadow, computes the mask.
+struct SharedShadow {
+    /// the index of the command.
+    cmd:  u32,
+    mask: std::sync::Mutex<ShadowMask>,
+}
+
+struct ShadowMask {
+    ready: bool,
+    image: Image<f32>,
+}
+
+/// assigns each command to the tiles, it may draw to.
+/// state commands (transforms, clips, groups) go to every tile.
+/// - returns `false`, if the command buffer can't be tiled.
+fn bin_commands(cmd_buf: &CmdBuf, base_tfx: Transform, tiles: &mut [Tile], tiles_x: u32) -> bool {
+    let tiles_y = tiles.len() as u32 / tiles_x.max(1);
+
+    let mut tfx_stack = Vec::new();
+    let mut tfx = base_tfx;
+
+    for i in 0..cmd_buf.num_cmds() {
+        // conservative bounds in target pixels, like in `group_bounds`.
+        let aabb = match *cmd_buf.cmd(i) {
+            Cmd::FillPath { path, .. } => {
+                Some(tfx.aabb_transform(path.aabb()))
+            }
+
+            Cmd::StrokePath { path, style, .. } => {
+                Some(tfx.aabb_transform(path.aabb().grow(F32x2::splat(style.extent()))))
+            }
+
+            Cmd::DropShadow { path, offset, blur_radius, .. } => {
+                let radii = gaussian_box_radii(blur_radius/2.0 * tfx_scale(tfx));
+                let aabb = tfx.aabb_transform(path.aabb()).grow(F32x2::splat(blur_extent(radii) as f32));
+                let offset = tfx.mul_normal(offset);
+                Some(Rect { min: aabb.min + offset, max: aabb.max + offset })
+            }
+
+            // these read pixels outside of the tile.
+            Cmd::BlurLayer { .. } |
+            Cmd::BeginGroup { filter: Some(_), .. } => return false,
+
+            Cmd::PushTransform { tfx: cmd_tfx } => {
+                tfx_stack.push(tfx);
+                tfx = tfx * cmd_tfx;
+                None
+            }
+
+            Cmd::PopTransform => {
+                // see `render_impl`.
+                tfx = tfx_stack.pop().unwrap_or(base_tfx);
+                None
+            }
+
+            Cmd::PushClipPath { .. } |
+            Cmd::PopClip |
+            Cmd::BeginGroup { filter: None, .. } |
+            Cmd::EndGroup => None,
+        };
+
+        let Some(aabb) = aabb else {
+            for tile in tiles.iter_mut() {
+                tile.cmds.push(i as u32);
+            }
+            continue;
+        };
+
+        // antialiasing may touch the pixels around the aabb.
+        let aabb = aabb.grow(F32x2::splat(1.0));
+        if !aabb.valid() { continue }
+
+        let tile_range = |min: f32, max: f32, n: u32| {
+            let t0 = (min / TILE_SIZE as f32).floor().max(0.0) as u32;
+            let t1 = (max / TILE_SIZE as f32).ceil().min(n as f32) as u32;
+            t0..t1
+        };
+        for ty in tile_range(aabb.min.y(), aabb.max.y(), tiles_y) {
+            for tx in tile_range(aabb.min.x(), aabb.max.x(), tiles_x) {
+                tiles[(ty*tiles_x + tx) as usize].cmds.push(i as u32);
+            }
+        }
+    }
+
+    true
+}
+
+#[inline]
+fn full_tile(size: U32x2) -> Rect {
+    let width = (size.x() + 3) / 4 * 4;
+    Rect { min: F32x2::ZERO(), max: U32x2::new(width, size.y()).as_i32().to_f32() }
+}
+
+#[inline]
+fn overlaps(a: Rect, b: Rect) -> bool {
+    let c = a.clamp_to(b);
+    c.min.lt(c.max).all()
+}
+
+/// the rows, and the end of the columns, of the raster rect
+/// at `origin` with `size`, that are in `tile`.
+/// - for `Rasterizer::with_window`.
+fn raster_window(origin: F32x2, size: U32x2, tile: Rect) -> ([u32; 2], u32) {
+    let min  = (tile.min - origin).to_i32_unck();
+    let max  = (tile.max - origin).to_i32_unck();
+    let size = size.as_i32();
+
+    let y0 = min.y().clamp(0, size.y());
+    let y1 = max.y().clamp(y0, size.y());
+    let end_x = max.x().clamp(0, size.x());
+    ([y0 as u32, y1 as u32], end_x as u32)
+}
+
+/// reads the pixels of `target` at `from` into `image`.
+/// - pixels outside of `target` are cleared.
+fn load_base(target: &DynImgMut, from: U32x2, image: &mut Image<[F32x4; 4]>) {
+    let premultiply = target.alpha_mode() == AlphaMode::Straight;
+    match target {
+        DynImgMut::Rgba8  (target, _) => load_expand(&target.img(), from, premultiply, image, |c| abgr_u8x_unpack(c)),
+        DynImgMut::Bgra8  (target, _) => load_expand(&target.img(), from, premultiply, image, |c| argb_u8x_unpack(c)),
+        DynImgMut::Rgb565 (target, _) => load_expand(&target.img(), from, premultiply, image, |c| rgb565_u16x_unpack(c)),
+        DynImgMut::A8     (target)    => load_expand(&target.img(), from, premultiply, image, |c| a8_u8x_unpack(c)),
+        DynImgMut::Rgba16 (target, _) => load_expand(&target.img(), from, premultiply, image, |c| rgba_u16x_unpack(c)),
+        DynImgMut::RgbaF32(target, _) => load_expand(&target.img(), from, premultiply, image, |c| rgba_f32x_unpack(c)),
+    }
+}
+
+fn load_expand<T, F>(src: &Img<T>, from: U32x2, premultiply: bool, image: &mut Image<[F32x4; 4]>, f: F)
+where T: Copy + Default + Sync, F: Fn([T; 4]) -> [F32x4; 4] + Sync {
+    let x0 = from.x() as usize;
+    let y0 = from.y() as usize;
+    let w  = src.width()  as usize;
+    let h  = src.height() as usize;
+
+    let width  = image.width() as usize;
+    let stride = image.stride();
+
+    use forkyou::{Spliterator, SpliterChunksMut};
+    forkyou::for_each(image.data_mut().spliter_chunks_mut(stride).enumerate(), |(dy, row)| {
+        let y = y0 + dy;
+        for u in 0..width {
+            let x = x0 + 4*u;
+            let c =
+                if y >= h { [T::default(); 4] }
+                else if x + 4 <= w { src.read_n(x, y) }
+                else { core::array::from_fn(|i| if x + i < w { src[(x + i, y)] } else { T::default() }) };
+
+            let [r, g, b, a] = f(c);
+            row[u] =
+                if premultiply { [r*a, g*a, b*a, a] }
+                else { [r, g, b, a] };
+        }
+    });
+}
+
+fn write_back(image: &Image<[F32x4; 4]>, to: I32x2, target: &mut DynImgMut) {
+    let image = image.img();
+    let alpha = target.alpha_mode();
+    match target {
+        DynImgMut::Rgba8  (target, _) => target.copy_expand(&image, to, |c| *abgr_u8x_pack(with_alpha_mode(c, alpha))),
+        DynImgMut::Bgra8  (target, _) => target.copy_expand(&image, to, |c| *argb_u8x_pack(with_alpha_mode(c, alpha))),
+        DynImgMut::Rgb565 (target, _) => target.copy_expand(&image, to, |c| rgb565_u16x_pack(with_alpha_mode(c, alpha))),
+        DynImgMut::A8     (target)    => target.copy_expand(&image, to, |c| a8_u8x_pack(c)),
+        DynImgMut::Rgba16 (target, _) => target.copy_expand(&image, to, |c| rgba_u16x_pack(with_alpha_mode(c, alpha))),
+        DynImgMut::RgbaF32(target, _) => target.copy_expand(&image, to, |c| rgba_f32x_pack(with_alpha_mode(c, alpha))),
+    }
+}
+
+/// converts premultiplied colors to `alpha`.
+/// - fully transparent colors become zero.
+#[inline(always)]
+fn with_alpha_mode<const N: usize>(c: [F32x<N>; 4], alpha: AlphaMode) -> [F32x<N>; 4]  where (): SimdLanes<N> {
+    match alpha {
+        AlphaMode::Premultiplied => c,
+        AlphaMode::Straight => {
+            let [r, g, b, a] = c;
+            [unpremultiply(r, a), unpremultiply(g, a), unpremultiply(b, a), a]
+        }
+    }
+}
+
+
+/// the allocations of `render_impl`.
+struct RenderBuffers {
+    /// the rendered image.
+    image: Image<[F32x4; 4]>,
+
+    raster_image: Image<f32>,
+    gradient_stops: Vec<GradientStopF32>,
+    stroke: StrokeBuffers,
+
+    tfx_stack:  Vec<Transform>,
+    clip_stack: Vec<ClipMask>,
+    layers:     Vec<Layer>,
+
+    // images of popped clips & layers.
+    clip_images:  Vec<Image<f32>>,
+    layer_images: Vec<Image<[F32x4; 4]>>,
+
+    effect_mask:    Image<f32>,
+    effect_image:   Image<[F32x4; 4]>,
+    blur_line:      Vec<f32>,
+    blur_simd_line: Vec<[F32x4; 4]>,
+    blur_channels:  [Vec<f32>; 4],
+
+    filter: FilterBuffers<4>,
+}
+
+impl RenderBuffers {
+    fn new() -> Self {
+        RenderBuffers {
+            image: Image::with_clear([0, 0], [F32x4::ZERO(); 4]),
+            raster_image: Image::new([0, 0]),
+            gradient_stops: Vec::new(),
+            stroke: StrokeBuffers::new(),
+            tfx_stack:  Vec::new(),
+            clip_stack: Vec::new(),
+            layers:     Vec::new(),
+            clip_images:  Vec::new(),
+            layer_images: Vec::new(),
+            effect_mask:    Image::new([0, 0]),
+            effect_image:   Image::with_clear([0, 0], [F32x4::ZERO(); 4]),
+            blur_line:      Vec::new(),
+            blur_simd_line: Vec::new(),
+            blur_channels:  Default::default(),
+            filter: FilterBuffers::new(),
+        }
+    }
+
+    fn memory(&self) -> usize {
+        fn vec<T>(vec: &Vec<T>) -> usize {
+            vec.capacity() * core::mem::size_of::<T>()
+        }
+
+        // the stacks are empty between renders.
+        self.image.memory()
+        + self.raster_image.memory()
+        + vec(&self.gradient_stops)
+        + self.stroke.memory()
+        + vec(&self.tfx_stack)
+        + vec(&self.clip_stack)
+        + vec(&self.layers)
+        + vec(&self.clip_images)  + self.clip_images .iter().map(|i| i.memory()).sum::<usize>()
+        + vec(&self.layer_images) + self.layer_images.iter().map(|i| i.memory()).sum::<usize>()
+        + self.effect_mask.memory()
+        + self.effect_image.memory()
+        + vec(&self.blur_line)
+        + vec(&self.blur_simd_line)
+        + self.blur_channels.iter().map(vec).sum::<usize>()
+        + self.filter.memory()
+    }
+}
+
+/// renders the commands into `buffers.image`, which covers `tile`.
+/// - `target` is only read, if `params.load`.
+/// - `tile` is an integer rect in target pixels, aligned like `Tile::rect`.
+/// - `cmds` are the indices of the commands to render, `None` for all.
+/// - `shadows` are the masks of drop shadows, that are shared with other tiles.
+fn render_impl(cmd_buf: &CmdBuf, params: &RenderParams, mut mask_cache: Option<&mut MaskCache>,
+    target: &DynImgMut, tile: Rect, cmds: Option<&[u32]>, shadows: Option<&[SharedShadow]>,
+    buffers: &mut RenderBuffers)
+{
+    let RenderBuffers {
+        image: render_image,
+        raster_image,
+        gradient_stops: gradient_stop_buffer,
+        stroke: stroke_buffers,
+        tfx_stack, clip_stack, layers,
+        clip_images, layer_images,
+        effect_mask, effect_image,
+        blur_line, blur_simd_line, blur_channels,
+        filter: filter_buffers,
+    } = buffers;
+
     let clear = argb_unpack_premultiply(params.clear);
     let clear = [
         F32x4::splat(clear[0]),
@@ -52,176 +525,690 @@ pub fn render(cmd_buf: &CmdBuf, params: &RenderParams, target: &mut ImgMut<u32>)
         F32x4::splat(clear[3]),
     ];
 
-    let mut render_image = {
-        let w = (target.width() + 3) / 4;
-        let h = target.height();
-        <Image<[F32x4; 4], _>>::with_clear([w, h], clear)
-    };
+    let tile_origin = tile.min.to_i32_unck();
+    let tile_size = tile.size().to_i32_unck().as_u32();
+    render_image.resize_and_clear([tile_size.x()/4, tile_size.y()], clear);
+    if params.load {
+        load_base(target, tile_origin.as_u32(), render_image);
+    }
 
-    let mut raster_image = Image::new([0, 0]);
     let clip = Rect { min: F32x2::ZERO(), max: target.size().as_i32().to_f32() };
 
-    let mut gradient_stop_buffer = Vec::new();
+    tfx_stack.clear();
+    let mut tfx = params.tfx;
 
-    let tfx = &params.tfx;
+    debug_assert!(clip_stack.is_empty());
+    let mut clip_bounds = clip;
 
-    for i in 0..cmd_buf.num_cmds() {
-        match *cmd_buf.cmd(i) {
-            Cmd::FillPathSolid { path, color } => {
-                let aabb = tfx.aabb_transform(path.aabb());
+    debug_assert!(layers.is_empty());
+    let mut layer_bounds = clip;
 
-                let (raster_size, raster_origin, blit_offset) =
-                    raster_rect_for(aabb, clip, 4);
+    let num_cmds = cmds.map_or(cmd_buf.num_cmds(), |cmds| cmds.len());
+    for k in 0..num_cmds {
+        let i = cmds.map_or(k, |cmds| cmds[k] as usize);
 
-                if raster_size.eq(U32x2::ZERO()).any() { continue }
+        let (path, shape, bounds, paint, opacity, blend) = match *cmd_buf.cmd(i) {
+            Cmd::PushTransform { tfx: cmd_tfx } => {
+                tfx_stack.push(tfx);
+                tfx = tfx * cmd_tfx;
+                continue;
+            }
+
+            Cmd::PopTransform => {
+                // unbalanced pops reset to the render transform.
+                tfx = tfx_stack.pop().unwrap_or(params.tfx);
+                continue;
+            }
 
-                let mut tfx = *tfx;
-                tfx.columns[2] -= raster_origin;
+            Cmd::PushClipPath { path, fill_rule } => {
+                let mask = clip_images.pop().unwrap_or_else(|| Image::new([0, 0]));
+                let clip_mask = ClipMask::new(path, fill_rule, tfx, clip_bounds, tile, clip_stack.last(), mask);
+                clip_bounds = clip_mask.rect;
+                clip_stack.push(clip_mask);
+                continue;
+            }
 
-                let mut r = Rasterizer::new(&mut raster_image, *raster_size);
-                r.fill_path(path, &tfx);
-                let mask = r.accumulate();
+            Cmd::PopClip => {
+                if let Some(clip_mask) = clip_stack.pop() {
+                    clip_images.push(clip_mask.mask);
+                }
+                clip_bounds = clip_stack.last().map_or(clip, |c| c.rect);
+                continue;
+            }
 
-                let color = argb_unpack_premultiply(color);
+            Cmd::BeginGroup { opacity, blend, filter } => {
+                let bounds = match filter {
+                    // filters can spread the contents arbitrarily (offset, flood).
+                    Some(_) => clip_bounds,
+                    None    => group_bounds(cmd_buf, i, tfx),
+                };
+                let bounds = bounds.clamp_to(clip_bounds).clamp_to(layer_bounds);
+
+                let (size, origin, _) = raster_rect_for(bounds, clip, 4);
+
+                let rect = Rect { min: origin, max: origin + size.as_i32().to_f32() };
+
+                // only the part in the tile is stored.
+                let image_rect = rect.clamp_to(tile);
+                let image_size = image_rect.size().to_i32_unck().as_u32();
+                let mut image = layer_images.pop().unwrap_or_else(|| Image::with_clear([0, 0], [F32x4::ZERO(); 4]));
+                image.resize_and_clear([image_size.x()/4, image_size.y()], [F32x4::ZERO(); 4]);
+                let image_origin = image_rect.min.to_i32_unck().as_u32();
+                layers.push(Layer { rect, image_origin, image, opacity, blend, filter, tfx });
+                layer_bounds = rect;
+                continue;
+            }
 
-                fill_mask_solid(&mask.img(), blit_offset, color, &mut render_image.img_mut());
+            Cmd::EndGroup => {
+                // unbalanced ends are ignored.
+                if layers.len() > 0 {
+                    end_group(cmd_buf, render_image, tile_origin, layers, layer_images, filter_buffers);
+                    layer_bounds = layers.last().map_or(clip, |l| l.rect);
+                }
+                continue;
             }
 
-            Cmd::StrokePathSolid { path, color, width } => {
-                let stroke = crate::stroke::stroke(path, width);
-                let path = stroke.path();
+            Cmd::DropShadow { path, offset, blur_radius, color } => {
+                let radii = gaussian_box_radii(blur_radius/2.0 * tfx_scale(tfx));
+                let extent = F32x2::splat(blur_extent(radii) as f32);
+
+                let mut shadow_tfx = tfx;
+                shadow_tfx.columns[2] += tfx.mul_normal(offset);
 
-                let aabb = tfx.aabb_transform(path.aabb());
+                let bounds = clip_bounds.clamp_to(layer_bounds);
+
+                // the blur pulls in geometry from up to `extent` outside of `bounds`.
+                let mask_rect = shadow_tfx.aabb_transform(path.aabb())
+                    .grow(extent)
+                    .clamp_to(bounds.grow(extent))
+                    .round_inclusive();
+                let mask_size = mask_rect.size().to_i32_unck().as_u32();
+                if mask_size.eq(U32x2::ZERO()).any() { continue }
 
                 let (raster_size, raster_origin, blit_offset) =
-                    raster_rect_for(aabb, clip, 4);
+                    raster_rect_for(mask_rect.clamp_to(bounds), clip, 4);
 
                 if raster_size.eq(U32x2::ZERO()).any() { continue }
 
-                let mut tfx = *tfx;
-                tfx.columns[2] -= raster_origin;
+                let raster_rect = Rect { min: raster_origin, max: raster_origin + raster_size.as_i32().to_f32() };
+                if !overlaps(raster_rect, tile) { continue }
+
+                let mut raster_tfx = shadow_tfx;
+                raster_tfx.columns[2] -= mask_rect.min;
+
+                let mut blurred_shadow = |image: &mut Image<f32>| {
+                    let mut r = Rasterizer::new(image, *mask_size);
+                    r.fill_path(path, &raster_tfx);
+                    blur_mask(&mut r.accumulate(), radii, blur_line);
+                };
+
+                // the mask doesn't depend on the tile,
+                // so tiles share it, instead of each blurring all of it.
+                let shared = shadows.and_then(|shadows| {
+                    let k = shadows.binary_search_by_key(&(i as u32), |s| s.cmd).ok()?;
+                    Some(shadows[k].mask.lock().unwrap())
+                });
+                let shadow_guard;
+                let shadow = match shared {
+                    Some(mut shared) => {
+                        if !shared.ready {
+                            blurred_shadow(&mut shared.image);
+                            shared.ready = true;
+                        }
+                        shadow_guard = shared;
+                        shadow_guard.image.img()
+                    }
+
+                    None => {
+                        blurred_shadow(raster_image);
+                        raster_image.img()
+                    }
+                };
 
-                let mut r = Rasterizer::new(&mut raster_image, *raster_size);
-                r.fill_path(path, &tfx);
-                let mask = r.accumulate();
+                // crop to the visible part.
+                effect_mask.resize_and_clear(*raster_size, 0.0);
+                let delta = (raster_origin - mask_rect.min).to_i32_unck();
+                for y in 0..raster_size.y() as i32 {
+                    let sy = y + delta.y();
+                    if sy < 0 || sy >= mask_size.y() as i32 { continue }
 
-                let color = argb_unpack_premultiply(color);
+                    for x in 0..raster_size.x() as i32 {
+                        let sx = x + delta.x();
+                        if sx < 0 || sx >= mask_size.x() as i32 { continue }
+
+                        effect_mask[(x as usize, y as usize)] = shadow[(sx as usize, sy as usize)];
+                    }
+                }
+
+                let mut mask = effect_mask.img_mut();
+
+                if let Some(clip_mask) = clip_stack.last() {
+                    clip_mask.apply(&mut mask, blit_offset);
+                }
 
-                fill_mask_solid(&mask.img(), blit_offset, color, &mut render_image.img_mut());
+                let (mut layer_img, offset) = layer_target(render_image, tile_origin, layers, blit_offset);
+
+                let color = argb_unpack_premultiply(color);
+                fill_mask_solid(&mask.img(), offset, color, BlendMode::SrcOver, &mut layer_img);
+                continue;
             }
 
-            Cmd::FillPathLinearGradient { path, gradient, opacity } => {
-                let aabb = tfx.aabb_transform(path.aabb());
+            Cmd::BlurLayer { blur_radius } => {
+                let radii = gaussian_box_radii(blur_radius/2.0 * tfx_scale(tfx));
+                let extent = F32x2::splat(blur_extent(radii) as f32);
 
-                let (raster_size, raster_origin, blit_offset) =
-                    raster_rect_for(aabb, clip, 4);
+                let bounds = clip_bounds.clamp_to(layer_bounds);
 
-                if raster_size.eq(U32x2::ZERO()).any() { continue }
+                // the region, that contributes to `bounds`.
+                let (src_size, src_origin, src_offset) =
+                    raster_rect_for(bounds.grow(extent).clamp_to(layer_bounds), clip, 4);
+
+                let (dst_size, dst_origin, dst_offset) =
+                    raster_rect_for(bounds, clip, 4);
 
-                let mut tfx = *tfx;
-                tfx.columns[2] -= raster_origin;
+                if dst_size.eq(U32x2::ZERO()).any() { continue }
 
-                let mut r = Rasterizer::new(&mut raster_image, *raster_size);
-                r.fill_path(path, &tfx);
-                let mask = r.accumulate();
+                let (layer_img, src_offset) = layer_target(render_image, tile_origin, layers, src_offset);
+                let u0 = (src_offset.x() / 4) as usize;
+                let v0 = src_offset.y() as usize;
 
-                let gradient = cmd_buf.linear_gradient(gradient);
-                let stops = gradient.stops;
+                effect_image.resize_and_clear([src_size.x()/4, src_size.y()], [F32x4::ZERO(); 4]);
+                for y in 0..src_size.y() as usize {
+                    for u in 0..(src_size.x()/4) as usize {
+                        effect_image[(u, y)] = layer_img[(u0 + u, v0 + y)];
+                    }
+                }
 
-                let p0 = (tfx * gradient.tfx) * gradient.p0;
-                let p1 = (tfx * gradient.tfx) * gradient.p1;
+                blur_layer(&mut effect_image.img_mut(), radii, blur_line, blur_simd_line, blur_channels);
 
-                if stops.len() == 2 {
-                    let s0 = stops[0];
-                    let s1 = stops[1];
-                    let c0 = argb_unpack(s0.color);
-                    let c1 = argb_unpack(s1.color);
-                    fill_mask_linear_gradient_2(
-                        p0.lerp(p1, s0.offset), p0.lerp(p1, s1.offset),
-                        c0, c1, opacity,
-                        &mask.img(), blit_offset, &mut render_image.img_mut());
+                // coverage of the clip.
+                effect_mask.resize_and_clear(*dst_size, 1.0);
+                let mut mask = effect_mask.img_mut();
+                if let Some(clip_mask) = clip_stack.last() {
+                    clip_mask.apply(&mut mask, dst_offset);
                 }
-                else if stops.len() > 0 {
-                    gradient_stop_buffer.clear();
-                    for stop in stops {
-                        gradient_stop_buffer.push(GradientStopF32 {
-                            offset: stop.offset,
-                            color:  argb_unpack(stop.color),
-                        });
-                    }
 
-                    fill_mask_linear_gradient_n(
-                        p0, p1,
-                        &gradient_stop_buffer, opacity,
-                        &mask.img(), blit_offset, &mut render_image.img_mut());
+                let (mut layer_img, dst_offset) = layer_target(render_image, tile_origin, layers, dst_offset);
+                let du0 = (dst_offset.x() / 4) as usize;
+                let dv0 = dst_offset.y() as usize;
+                let delta = (dst_origin - src_origin).to_i32_unck().as_u32();
+                let su0 = (delta.x() / 4) as usize;
+                let sv0 = delta.y() as usize;
+
+                for y in 0..dst_size.y() as usize {
+                    for u in 0..(dst_size.x()/4) as usize {
+                        let coverage = F32x4::from_array(mask.read_n(4*u, y));
+                        let blurred = effect_image[(su0 + u, sv0 + y)];
+                        let old = layer_img[(du0 + u, dv0 + y)];
+                        layer_img[(du0 + u, dv0 + y)] =
+                            core::array::from_fn(|c| old[c] + (blurred[c] - old[c])*coverage);
+                    }
                 }
+                continue;
             }
 
-            Cmd::FillPathRadialGradient { path, gradient, opacity } => {
-                let Some(inv_tfx) = tfx.invert(0.00001) else { continue };
+            Cmd::FillPath { path, fill_rule, paint, opacity, blend } => {
+                (path, MaskShape::Fill(fill_rule), path.aabb(), paint, opacity, blend)
+            }
 
-                let aabb = tfx.aabb_transform(path.aabb());
+            Cmd::StrokePath { path, style, paint, opacity, blend } => {
+                // relative gradients use the bounds of the original path (svg's geometry bbox).
+                (path, MaskShape::Stroke(style), path.aabb(), paint, opacity, blend)
+            }
+        };
+
+        // cached masks are looked up by the unstroked path,
+        // so hits don't run the stroker.
+        let cached = match mask_cache.as_deref_mut() {
+            Some(cache) => cache.lookup(path, shape, tfx),
+            None => None,
+        };
+
+        let mut raster_path = None;
+        let path_rect = match cached {
+            Some(cached) => cached.rect,
+
+            None => {
+                let (path, fill_rule) = match shape {
+                    MaskShape::Fill(fill_rule) => (path, fill_rule),
+                    MaskShape::Stroke(style) => (stroke_with(path, &style, stroke_buffers), FillRule::NonZero),
+                };
+                raster_path = Some((path, fill_rule));
+                tfx.aabb_transform(path.aabb())
+            }
+        };
 
-                let (raster_size, raster_origin, blit_offset) =
-                    raster_rect_for(aabb, clip, 4);
+        let aabb = path_rect.clamp_to(clip_bounds).clamp_to(layer_bounds);
 
-                if raster_size.eq(U32x2::ZERO()).any() { continue }
+        let (raster_size, raster_origin, blit_offset) =
+            raster_rect_for(aabb, clip, 4);
 
-                let mut tfx = *tfx;
-                tfx.columns[2] -= raster_origin;
-
-                let mut r = Rasterizer::new(&mut raster_image, *raster_size);
-                r.fill_path(path, &tfx);
-                let mask = r.accumulate();
-
-                let gradient = cmd_buf.radial_gradient(gradient);
-                let stops = gradient.stops;
-
-                if let Some(inv_grad_tfx) = gradient.tfx.invert(0.00001) {
-                    if stops.len() == 2 {
-                        let s0 = stops[0];
-                        let s1 = stops[1];
-                        let c0 = argb_unpack(s0.color);
-                        let c1 = argb_unpack(s1.color);
-                        fill_mask_radial_gradient_2(
-                            raster_origin, inv_tfx, inv_grad_tfx,
-                            gradient,
-                            GradientStopF32 { offset: s0.offset, color: c0 },
-                            GradientStopF32 { offset: s1.offset, color: c1 },
-                            opacity,
-                            &mask.img(),
-                            blit_offset,
-                            &mut render_image.img_mut());
-                    }
-                    else if stops.len() > 0 {
-                        gradient_stop_buffer.clear();
-                        for stop in stops {
-                            gradient_stop_buffer.push(GradientStopF32 {
-                                offset: stop.offset,
-                                color:  argb_unpack(stop.color),
-                            });
-                        }
+        if raster_size.eq(U32x2::ZERO()).any() { continue }
+
+        // only the part of the raster rect in the tile is rasterized.
+        // the raster rect itself doesn't depend on the tile,
+        // so the coverage matches untiled renders exactly.
+        let (rows, end_x) = raster_window(raster_origin, raster_size, tile);
+        let mask_size = U32x2::new(end_x, rows[1] - rows[0]);
+        if mask_size.eq(U32x2::ZERO()).any() { continue }
+
+        let mask_origin = raster_origin + F32x2::new(0.0, rows[0] as f32);
+        let mask_offset = blit_offset + U32x2::new(0, rows[0]);
+
+        let (mut layer_img, offset) = layer_target(render_image, tile_origin, layers, mask_offset);
+
+        let mut fill = |mask: &Img<f32>| {
+            fill_mask_paint(
+                cmd_buf, paint, opacity, blend,
+                tfx, bounds, raster_origin, rows[0],
+                mask, offset,
+                gradient_stop_buffer, &mut layer_img);
+        };
+
+        if let Some(cached) = cached {
+            let cache = mask_cache.as_deref().unwrap();
+            let mask = cache.mask_img(cached, mask_origin, mask_size);
+
+            match clip_stack.last() {
+                None => fill(&mask),
 
-                        fill_mask_radial_gradient_n(
-                            raster_origin, inv_tfx, inv_grad_tfx,
-                            gradient, &gradient_stop_buffer,
-                            opacity,
-                            &mask.img(), blit_offset, &mut render_image.img_mut());
+                // the atlas is shared, so clipping works on a copy.
+                Some(clip_mask) => {
+                    raster_image.resize_and_clear(*mask_size, 0.0);
+                    let mut clipped = raster_image.img_mut();
+                    for y in 0..mask_size.y() as usize {
+                        for x in 0..mask_size.x() as usize {
+                            clipped[(x, y)] = mask[(x, y)];
+                        }
                     }
+                    clip_mask.apply(&mut clipped, mask_offset);
+                    fill(&clipped.img());
                 }
-                else {
-                    //println!("skipping radial gradient with degenerate transform");
-                }
             }
         }
+        else {
+            let Some((path, fill_rule)) = raster_path else { unreachable!() };
+
+            let mut raster_tfx = tfx;
+            raster_tfx.columns[2] -= raster_origin;
+
+            let mut r = Rasterizer::with_window(raster_image, *raster_size, rows, end_x);
+            r.fill_rule = fill_rule;
+            r.fill_path(path, &raster_tfx);
+            let mut mask = r.accumulate();
+
+            if let Some(clip_mask) = clip_stack.last() {
+                clip_mask.apply(&mut mask, mask_offset);
+            }
+
+            fill(&mask.img());
+        }
+    }
+
+    // close unbalanced groups.
+    while layers.len() > 0 {
+        end_group(cmd_buf, render_image, tile_origin, layers, layer_images, filter_buffers);
+    }
+
+    // and unbalanced clips.
+    for clip_mask in clip_stack.drain(..) {
+        clip_images.push(clip_mask.mask);
+    }
+}
+
+
+/// fills `mask` with `paint`.
+/// - gradient and image ids in `paint` refer to `resources`.
+/// - `tfx` maps path coordinates to target pixels.
+/// - `bounds` are the path bounds, that `GradientUnits::Relative` refer to.
+/// - `raster_origin` is the position of the raster rect in target pixels.
+///   `mask` covers its rows from `first_row` on.
+/// - `offset` is the position of `mask` in `target` and may be negative.
+///   pixels outside of `target` are skipped, but the paint is still
+///   evaluated relative to `raster_origin`, so cropped fills match
+///   uncropped ones exactly.
+pub fn fill_mask_paint<const N: usize>(
+    resources: &CmdBuf,
+    paint: Paint,
+    opacity: f32,
+    blend: BlendMode,
+    tfx: Transform,
+    bounds: Rect,
+    raster_origin: F32x2,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    gradient_stop_buffer: &mut Vec<GradientStopF32>,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let mut raster_tfx = tfx;
+    raster_tfx.columns[2] -= raster_origin;
+
+    let load_stops = |buffer: &mut Vec<GradientStopF32>, stops: &[GradientStop]| {
+        buffer.clear();
+        for stop in stops {
+            buffer.push(GradientStopF32 {
+                offset: stop.offset,
+                color:  argb_unpack(stop.color),
+            });
+        }
+    };
+
+    let stop_f32 = |stop: GradientStop| {
+        GradientStopF32 { offset: stop.offset, color: argb_unpack(stop.color) }
+    };
+
+    match paint {
+        Paint::Solid(color) => {
+            let color = argb_unpack_premultiply(color) * F32x4::splat(opacity);
+            fill_mask_solid(mask, offset, color, blend, target);
+        }
+
+        Paint::LinearGradient(gradient) => {
+            let gradient = resources.linear_gradient(gradient);
+            let stops = gradient.stops;
+
+            let Some(grad_tfx) = gradient_tfx(gradient.units, gradient.tfx, bounds) else { return };
+
+            let p0 = (raster_tfx * grad_tfx) * gradient.p0;
+            let p1 = (raster_tfx * grad_tfx) * gradient.p1;
+
+            if stops.len() == 2 {
+                fill_mask_linear_gradient_2(
+                    p0, p1,
+                    stop_f32(stops[0]), stop_f32(stops[1]),
+                    gradient.spread,
+                    opacity, blend,
+                    first_row, mask, offset, target);
+            }
+            else if stops.len() > 0 {
+                load_stops(gradient_stop_buffer, stops);
+                fill_mask_linear_gradient_n(
+                    p0, p1,
+                    gradient_stop_buffer, gradient.spread,
+                    opacity, blend,
+                    first_row, mask, offset, target);
+            }
+        }
+
+        Paint::RadialGradient(gradient) => {
+            let gradient = resources.radial_gradient(gradient);
+            let stops = gradient.stops;
+
+            let Some(inv_tfx) = tfx.invert(0.00001) else { return };
+            let Some(grad_tfx) = gradient_tfx(gradient.units, gradient.tfx, bounds) else { return };
+            let Some(inv_grad_tfx) = grad_tfx.invert(0.00001) else { return };
+
+            if stops.len() == 2 {
+                fill_mask_radial_gradient_2(
+                    raster_origin, inv_tfx, inv_grad_tfx,
+                    gradient,
+                    stop_f32(stops[0]), stop_f32(stops[1]),
+                    opacity, blend,
+                    first_row, mask, offset, target);
+            }
+            else if stops.len() > 0 {
+                load_stops(gradient_stop_buffer, stops);
+                fill_mask_radial_gradient_n(
+                    raster_origin, inv_tfx, inv_grad_tfx,
+                    gradient, gradient_stop_buffer,
+                    opacity, blend,
+                    first_row, mask, offset, target);
+            }
+        }
+
+        Paint::ConicGradient(gradient) => {
+            let gradient = resources.conic_gradient(gradient);
+            let stops = gradient.stops;
+
+            let Some(inv_tfx) = tfx.invert(0.00001) else { return };
+            let Some(grad_tfx) = gradient_tfx(gradient.units, gradient.tfx, bounds) else { return };
+            let Some(inv_grad_tfx) = grad_tfx.invert(0.00001) else { return };
+
+            if stops.len() == 2 {
+                fill_mask_conic_gradient_2(
+                    raster_origin, inv_tfx, inv_grad_tfx,
+                    gradient,
+                    stop_f32(stops[0]), stop_f32(stops[1]),
+                    opacity, blend,
+                    first_row, mask, offset, target);
+            }
+            else if stops.len() > 0 {
+                load_stops(gradient_stop_buffer, stops);
+                fill_mask_conic_gradient_n(
+                    raster_origin, inv_tfx, inv_grad_tfx,
+                    gradient, gradient_stop_buffer,
+                    opacity, blend,
+                    first_row, mask, offset, target);
+            }
+        }
+
+        Paint::Image { image, tfx: image_tfx, sampling, extend } => {
+            let Some(inv_tfx) = (tfx * image_tfx).invert(0.00001) else { return };
+
+            fill_mask_image(
+                raster_origin, inv_tfx,
+                resources.image(image), sampling, extend,
+                opacity, blend,
+                first_row, mask, offset, target);
+        }
+    }
+}
+
+
+/// coverage of the intersection of the active clip paths.
+struct ClipMask {
+    /// integer rect in target pixels, that bounds the clip.
+    rect: Rect,
+    /// the part of `rect` in the tile, covered by `mask`.
+    mask_rect: Rect,
+    mask: Image<f32>,
+}
+
+impl ClipMask {
+    /// - `mask` is reused for the coverage.
+    fn new(path: Path, fill_rule: FillRule, tfx: Transform, bounds: Rect, tile: Rect, parent: Option<&ClipMask>, mut mask: Image<f32>) -> ClipMask {
+        let rect = tfx.aabb_transform(path.aabb()).clamp_to(bounds).round_inclusive();
+        let size = rect.size().to_i32_unck().as_u32();
+
+        let empty = Rect { min: rect.min, max: rect.min };
+
+        if size.eq(U32x2::ZERO()).any() {
+            // everything is clipped.
+            return ClipMask { rect: empty, mask_rect: empty, mask };
+        }
+
+        // like fills, only the part in the tile is rasterized.
+        let (rows, end_x) = raster_window(rect.min, size, tile);
+        if rows[0] == rows[1] || end_x == 0 {
+            return ClipMask { rect, mask_rect: empty, mask };
+        }
+
+        let mask_rect = Rect {
+            min: rect.min + F32x2::new(0.0, rows[0] as f32),
+            max: rect.min + F32x2::new(end_x as f32, rows[1] as f32),
+        };
+
+        let mut raster_tfx = tfx;
+        raster_tfx.columns[2] -= rect.min;
+
+        let mut r = Rasterizer::with_window(&mut mask, *size, rows, end_x);
+        r.fill_rule = fill_rule;
+        r.fill_path(path, &raster_tfx);
+        let mut coverage = r.accumulate();
+
+        if let Some(parent) = parent {
+            parent.apply(&mut coverage, mask_rect.min.to_i32_unck().as_u32());
+        }
+
+        ClipMask { rect, mask_rect, mask }
+    }
+
+    /// multiplies `mask` (placed at `offset` in target pixels) by the clip coverage.
+    /// - the coverage outside of the tile is zero.
+    fn apply(&self, mask: &mut ImgMut<f32>, offset: U32x2) {
+        let x0 = self.mask_rect.min.x() as i32 - offset.x() as i32;
+        let y0 = self.mask_rect.min.y() as i32 - offset.y() as i32;
+        let w  = self.mask_rect.width()  as i32;
+        let h  = self.mask_rect.height() as i32;
+
+        for y in 0..mask.height() as i32 {
+            let cy = y - y0;
+            for x in 0..mask.width() as i32 {
+                let cx = x - x0;
+
+                let c =
+                    if cx >= 0 && cy >= 0 && cx < w && cy < h {
+                        self.mask[(cx as usize, cy as usize)]
+                    }
+                    else { 0.0 };
+
+                mask[(x as usize, y as usize)] *= c;
+            }
+        }
+    }
+}
+
+
+/// offscreen buffer of a group.
+struct Layer {
+    /// integer rect in target pixels, that bounds the group.
+    /// `rect.min.x()` is aligned to 4.
+    rect:    Rect,
+    /// the position of `image`, which covers `rect` clamped to the tile.
+    image_origin: U32x2,
+    image:   Image<[F32x4; 4]>,
+    opacity: f32,
+    blend:   BlendMode,
+    filter:  Option<FilterId>,
+
+    /// the transform at `BeginGroup`.
+    tfx:     Transform,
+}
+
+impl Layer {
+    #[inline(always)]
+    fn origin(&self) -> U32x2 {
+        self.image_origin
+    }
+}
+
+/// returns the image of the innermost layer
+/// and `offset` (in target pixels) relative to that image.
+/// - `base_origin` is the position of `base` in target pixels.
+#[inline(always)]
+fn layer_target<'i>(base: &'i mut Image<[F32x4; 4]>, base_origin: I32x2, layers: &'i mut [Layer], offset: U32x2)
+    -> (ImgMut<'i, [F32x4; 4]>, I32x2)
+{
+    match layers.last_mut() {
+        Some(layer) => {
+            let origin = layer.origin();
+            (layer.image.img_mut(), offset.as_i32() - origin.as_i32())
+        }
+        None => (base.img_mut(), offset.as_i32() - base_origin),
     }
+}
 
-    // writeback.
-    {
-        // @todo: un-premultiply for non-opaque clear.
-        target.copy_expand(&render_image.img(), I32x2::ZERO(),
-            |c| *abgr_u8x_pack(c));
+/// pops the innermost layer, applies its filter,
+/// and composites it onto its parent.
+/// - the layer's image is returned to `layer_images`.
+fn end_group(cmd_buf: &CmdBuf, base: &mut Image<[F32x4; 4]>, base_origin: I32x2, layers: &mut Vec<Layer>, layer_images: &mut Vec<Image<[F32x4; 4]>>, filter_buffers: &mut FilterBuffers<4>) {
+    let mut layer = layers.pop().unwrap();
+    if let Some(filter) = layer.filter {
+        let origin = layer.origin();
+        apply_filter(cmd_buf.filter(filter), &mut layer.image, origin, layer.tfx, filter_buffers);
     }
+
+    // the layer's image is contained in its parent's.
+    let (mut parent, offset) = layer_target(base, base_origin, layers, layer.origin());
+    composite_layer(&layer.image.img(), offset.as_u32(), layer.opacity, layer.blend, &mut parent);
+
+    layer_images.push(layer.image);
+}
+
+/// conservative bounds (in target pixels) of the group starting at `begin`.
+fn group_bounds(cmd_buf: &CmdBuf, begin: usize, tfx: Transform) -> Rect {
+    let mut bounds = Rect::MAX_MIN();
+    let mut blur_extent_sum = 0.0;
+
+    let mut tfx_stack = Vec::new();
+    let mut tfx = tfx;
+    let mut depth = 0;
+
+    for i in begin + 1 .. cmd_buf.num_cmds() {
+        let aabb = match *cmd_buf.cmd(i) {
+            Cmd::FillPath { path, .. } => {
+                path.aabb()
+            }
+
+            Cmd::StrokePath { path, style, .. } => {
+                path.aabb().grow(F32x2::splat(style.extent()))
+            }
+
+            Cmd::DropShadow { path, offset, blur_radius, .. } => {
+                let radii = gaussian_box_radii(blur_radius/2.0 * tfx_scale(tfx));
+                let aabb = tfx.aabb_transform(path.aabb()).grow(F32x2::splat(blur_extent(radii) as f32));
+                let offset = tfx.mul_normal(offset);
+                bounds.include(aabb.min + offset);
+                bounds.include(aabb.max + offset);
+                continue;
+            }
+
+            // blurs spread the group's contents.
+            Cmd::BlurLayer { blur_radius } => {
+                let radii = gaussian_box_radii(blur_radius/2.0 * tfx_scale(tfx));
+                blur_extent_sum += blur_extent(radii) as f32;
+                continue;
+            }
+
+            Cmd::PushTransform { tfx: cmd_tfx } => {
+                tfx_stack.push(tfx);
+                tfx = tfx * cmd_tfx;
+                continue;
+            }
+
+            Cmd::PopTransform => {
+                if let Some(old) = tfx_stack.pop() {
+                    tfx = old;
+                }
+                continue;
+            }
+
+            // clips can only shrink the bounds.
+            Cmd::PushClipPath { .. } |
+            Cmd::PopClip => continue,
+
+            // see `BeginGroup` in `render`.
+            Cmd::BeginGroup { filter: Some(_), .. } => {
+                return Rect { min: F32x2::splat(f32::MIN), max: F32x2::splat(f32::MAX) };
+            }
+
+            Cmd::BeginGroup { filter: None, .. } => {
+                depth += 1;
+                continue;
+            }
+
+            Cmd::EndGroup => {
+                if depth == 0 { break }
+                depth -= 1;
+                continue;
+            }
+        };
+
+        let aabb = tfx.aabb_transform(aabb);
+        bounds.include(aabb.min);
+        bounds.include(aabb.max);
+    }
+
+    if bounds.valid() { bounds.grow(F32x2::splat(blur_extent_sum)) } else { Rect::ZERO() }
+}
+
+/// the average scale factor of `tfx`.
+pub(crate) fn tfx_scale(tfx: Transform) -> f32 {
+    let [a, b] = *tfx.columns[0];
+    let [c, d] = *tfx.columns[1];
+    (a*d - b*c).abs().sqrt()
 }
 
 
@@ -232,6 +1219,7 @@ pub struct RenderTarget {
     user_clip: Rect,
     net_clip: Rect, // `clip` clipped to `image`
     raster_cache: Image<f32>,
+    gradient_stop_cache: Vec<GradientStopF32>,
 }
 
 impl RenderTarget {
@@ -243,6 +1231,7 @@ impl RenderTarget {
             user_clip: Rect { min: F32x2::ZERO(), max: F32x2::MAX() },
             net_clip: Rect::ZERO(),
             raster_cache: Image::new([0, 0]),
+            gradient_stop_cache: Vec::new(),
         }
     }
 
@@ -321,17 +1310,27 @@ impl RenderTarget {
     }
     */
 
-    pub fn fill_path(&mut self, path: Path, color: u32) {
-        fill_path_solid(path, color, self.tfx, self.net_clip, &mut self.raster_cache, &mut self.image.img_mut());
+    /// - gradient and image ids in `paint` refer to `resources`.
+    pub fn fill_path(&mut self, path: Path, fill_rule: FillRule, paint: Paint, opacity: f32, blend: BlendMode, resources: &CmdBuf) {
+        fill_path(
+            path, fill_rule, paint, opacity, blend, resources,
+            self.tfx, self.net_clip,
+            &mut self.raster_cache, &mut self.gradient_stop_cache,
+            &mut self.image.img_mut());
     }
 
-    pub fn stroke_path(&mut self, path: Path, width: f32, color: u32) {
-        stroke_path_solid(path, width, color, self.tfx, self.net_clip, &mut self.raster_cache, &mut self.image.img_mut());
+    /// - gradient and image ids in `paint` refer to `resources`.
+    pub fn stroke_path(&mut self, path: Path, style: &StrokeStyle, paint: Paint, opacity: f32, blend: BlendMode, resources: &CmdBuf) {
+        stroke_path(
+            path, style, paint, opacity, blend, resources,
+            self.tfx, self.net_clip,
+            &mut self.raster_cache, &mut self.gradient_stop_cache,
+            &mut self.image.img_mut());
     }
 
 
-    pub fn write_to_image(&self, dst: &mut ImgMut<u32>) {
-        dst.copy_expand(&self.image.img(), I32x2::ZERO(), |c| *abgr_u8x_pack(c));
+    pub fn write_to_image<'a>(&self, dst: impl Into<DynImgMut<'a>>) {
+        write_back(&self.image, I32x2::ZERO(), &mut dst.into());
     }
 }
 
@@ -358,47 +1357,65 @@ pub fn raster_rect_for(rect: Rect, clip: Rect, align: u32) -> (U32x2, F32x2, U32
 }
 
 
-pub fn fill_path_solid<const N: usize>(
+/// - gradient and image ids in `paint` refer to `resources`.
+pub fn fill_path<const N: usize>(
     path: Path,
-    color: u32,
+    fill_rule: FillRule,
+    paint: Paint,
+    opacity: f32,
+    blend: BlendMode,
+    resources: &CmdBuf,
     tfx: Transform,
     clip: Rect,
     raster_image_cache: &mut Image<f32>,
+    gradient_stop_cache: &mut Vec<GradientStopF32>,
     target: &mut ImgMut<[F32x<N>; 4]>)
 where (): SimdLanes<N>
 {
-    let aabb = tfx.aabb_transform(path.aabb());
-
-    let (raster_size, raster_origin, blit_offset) =
-        raster_rect_for(aabb, clip, 4);
-
-    if raster_size.eq(U32x2::ZERO()).any() { return }
-
-    let mut tfx = tfx;
-    tfx.columns[2] -= raster_origin;
-
-    let mut r = Rasterizer::new(raster_image_cache, *raster_size);
-    r.fill_path(path, &tfx);
-    let mask = r.accumulate();
+    fill_path_with_bounds(
+        path, fill_rule, path.aabb(),
+        paint, opacity, blend, resources,
+        tfx, clip, raster_image_cache, gradient_stop_cache, target);
+}
 
-    let color = argb_unpack_premultiply(color);
+/// - gradient and image ids in `paint` refer to `resources`.
+pub fn stroke_path<const N: usize>(
+    path: Path,
+    style: &StrokeStyle,
+    paint: Paint,
+    opacity: f32,
+    blend: BlendMode,
+    resources: &CmdBuf,
+    tfx: Transform,
+    clip: Rect,
+    raster_image_cache: &mut Image<f32>,
+    gradient_stop_cache: &mut Vec<GradientStopF32>,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let stroke = crate::stroke::stroke(path, style);
 
-    fill_mask_solid(&mask.img(), blit_offset, color, target);
+    fill_path_with_bounds(
+        stroke.path(), FillRule::NonZero, path.aabb(),
+        paint, opacity, blend, resources,
+        tfx, clip, raster_image_cache, gradient_stop_cache, target);
 }
 
-pub fn stroke_path_solid<const N: usize>(
+fn fill_path_with_bounds<const N: usize>(
     path: Path,
-    width: f32,
-    color: u32,
+    fill_rule: FillRule,
+    bounds: Rect,
+    paint: Paint,
+    opacity: f32,
+    blend: BlendMode,
+    resources: &CmdBuf,
     tfx: Transform,
     clip: Rect,
     raster_image_cache: &mut Image<f32>,
+    gradient_stop_cache: &mut Vec<GradientStopF32>,
     target: &mut ImgMut<[F32x<N>; 4]>)
 where (): SimdLanes<N>
 {
-    let stroke = crate::stroke::stroke(path, width);
-    let path = stroke.path();
-
     let aabb = tfx.aabb_transform(path.aabb());
 
     let (raster_size, raster_origin, blit_offset) =
@@ -406,34 +1423,50 @@ where (): SimdLanes<N>
 
     if raster_size.eq(U32x2::ZERO()).any() { return }
 
-    let mut tfx = tfx;
-    tfx.columns[2] -= raster_origin;
+    let mut raster_tfx = tfx;
+    raster_tfx.columns[2] -= raster_origin;
 
     let mut r = Rasterizer::new(raster_image_cache, *raster_size);
-    r.fill_path(path, &tfx);
+    r.fill_rule = fill_rule;
+    r.fill_path(path, &raster_tfx);
     let mask = r.accumulate();
 
-    let color = argb_unpack_premultiply(color);
-
-    fill_mask_solid(&mask.img(), blit_offset, color, target);
+    fill_mask_paint(
+        resources, paint, opacity, blend,
+        tfx, bounds, raster_origin, 0,
+        &mask.img(), blit_offset.as_i32(),
+        gradient_stop_cache, target);
 }
 
 
 /// - input pre-multiplied alpha: yes.
 pub fn fill_mask_solid<const N: usize>(
     mask: &Img<f32>,
-    offset: U32x2,
+    offset: I32x2,
     color: F32x4,
+    blend: BlendMode,
     target: &mut ImgMut<[F32x<N>; 4]>)
 where (): SimdLanes<N>
 {
-    let n = N as u32;
+    with_blend_mode!(blend, B => fill_mask_solid_impl::<N, B>(mask, offset, color, target))
+}
+
+fn fill_mask_solid_impl<const N: usize, B: StaticBlend>(
+    mask: &Img<f32>,
+    offset: I32x2,
+    color: F32x4,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let blend = B::MODE;
+
+    let n = N as i32;
 
-    let size = U32x2::new(n*target.width(), target.height());
+    let size = U32x2::new(N as u32 * target.width(), target.height()).as_i32();
 
     let begin = offset;
-    let end   = (offset + mask.size()).min(size);
-    if begin.eq(end).any() {
+    let end   = (offset + mask.size().as_i32()).min(size);
+    if end.x() <= begin.x().max(0) || end.y() <= begin.y().max(0) {
         return;
     }
 
@@ -452,37 +1485,311 @@ where (): SimdLanes<N>
 
             let p = (u as usize, y as usize);
 
-            if coverage.lt(F32x::splat(0.5/255.0)).all() {
+            if x < 0 || y < 0 || coverage.lt(F32x::splat(0.5/255.0)).all() {
                 continue;
             }
-            if color[3] == 1.0 && coverage.gt(F32x::splat(254.5/255.0)).all() {
+            if (blend == BlendMode::Src || blend == BlendMode::SrcOver && color[3] == 1.0)
+            && coverage.gt(F32x::splat(254.5/255.0)).all() {
                 target[p] = [
                     F32x::splat(color[0]),
                     F32x::splat(color[1]),
                     F32x::splat(color[2]),
-                    F32x::splat(1.0),
+                    F32x::splat(color[3]),
                 ];
                 continue;
             }
 
-            let [tr, tg, tb, ta] = target[p];
+            let src = [
+                F32x::splat(color[0]),
+                F32x::splat(color[1]),
+                F32x::splat(color[2]),
+                F32x::splat(color[3]),
+            ];
+            target[p] = composite(blend, src, target[p], coverage);
+        }
+    }
+}
 
-            let sr = F32x::splat(color[0]) * coverage;
-            let sg = F32x::splat(color[1]) * coverage;
-            let sb = F32x::splat(color[2]) * coverage;
-            let sa = F32x::splat(color[3]) * coverage;
 
-            let one = F32x::splat(1.0);
-            target[p] = [
-                sr + (one - sa)*tr,
-                sg + (one - sa)*tg,
-                sb + (one - sa)*tb,
-                sa + (one - sa)*ta,
-            ];
+
+/// - input pre-multiplied alpha: yes.
+/// - `offset` is in pixels and must be aligned to `N`.
+pub fn composite_layer<const N: usize>(
+    layer: &Img<[F32x<N>; 4]>,
+    offset: U32x2,
+    opacity: f32,
+    blend: BlendMode,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    with_blend_mode!(blend, B => composite_layer_impl::<N, B>(layer, offset, opacity, target))
+}
+
+fn composite_layer_impl<const N: usize, B: StaticBlend>(
+    layer: &Img<[F32x<N>; 4]>,
+    offset: U32x2,
+    opacity: f32,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let blend = B::MODE;
+
+    let n = N as u32;
+
+    let u0 = offset.x() / n;
+    let v0 = offset.y();
+    assert!(u0 * n == offset.x());
+
+    let w = layer.width() .min(target.width() .saturating_sub(u0));
+    let h = layer.height().min(target.height().saturating_sub(v0));
+
+    let opacity = F32x::splat(opacity);
+
+    for y in 0..h {
+        for u in 0..w {
+            let [sr, sg, sb, sa] = layer[(u as usize, y as usize)];
+
+            let sa = sa * opacity;
+            if !blend.is_unbounded() && sa.lt(F32x::splat(0.5/255.0)).all() {
+                continue;
+            }
+
+            let src = [sr * opacity, sg * opacity, sb * opacity, sa];
+
+            let p = ((u0 + u) as usize, (v0 + y) as usize);
+            target[p] = composite(blend, src, target[p], F32x::ONE());
+        }
+    }
+}
+
+
+
+/// - input pre-multiplied alpha: no.
+/// - `inv_tfx` maps target pixel coordinates to image pixel coordinates.
+/// - `mask` starts at row `first_row` of the raster rect at `raster_origin`.
+pub fn fill_mask_image<const N: usize>(
+    raster_origin: F32x2,
+    inv_tfx: Transform,
+    image: &ImageSource,
+    sampling: ImageSampling,
+    extend: ImageExtend,
+    opacity: f32,
+    blend: BlendMode,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    with_blend_mode!(blend, B => fill_mask_image_impl::<N, B>(raster_origin, inv_tfx, image, sampling, extend, opacity, first_row, mask, offset, target))
+}
+
+fn fill_mask_image_impl<const N: usize, B: StaticBlend>(
+    raster_origin: F32x2,
+    inv_tfx: Transform,
+    image: &ImageSource,
+    sampling: ImageSampling,
+    extend: ImageExtend,
+    opacity: f32,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let blend = B::MODE;
+
+    let n = N as i32;
+
+    let size = U32x2::new(N as u32 * target.width(), target.height()).as_i32();
+
+    let begin = offset;
+    let end   = (offset + mask.size().as_i32()).min(size);
+    if end.x() <= begin.x().max(0) || end.y() <= begin.y().max(0) {
+        return;
+    }
+
+    if image.size[0] == 0 || image.size[1] == 0 {
+        return;
+    }
+
+    let u0 = begin.x() / n;
+    let u1 = end.x()   / n;
+    assert!(u0 * n == begin.x());
+    assert!(u1 * n == end.x());
+
+    let start = inv_tfx * (raster_origin + F32x2::new(0.5, 0.5));
+    let x_hat = inv_tfx.mul_normal(F32x2::new(1.0, 0.0));
+    let y_hat = inv_tfx.mul_normal(F32x2::new(0.0, 1.0));
+
+    let mut pp = start;
+    for _ in 0..first_row {
+        pp += y_hat;
+    }
+
+    for y in begin.y() .. end.y() {
+        for u in u0..u1 {
+            let x = u * n;
+            let mask_x = (x - begin.x()) as usize;
+            let mask_y = (y - begin.y()) as usize;
+
+            let coverage = F32x::from_array(mask.read_n(mask_x, mask_y));
+
+            let p = (u as usize, y as usize);
+
+            if x < 0 || y < 0 || coverage.lt(F32x::splat(0.5/255.0)).all() {
+                continue;
+            }
+
+            // sampling is scalar, there's no gather.
+            let mut src = [[0.0; N]; 4];
+            for i in 0..N {
+                let q = pp + (mask_x + i) as f32 * x_hat;
+                let c = sample_image(image, q, sampling, extend);
+                for (k, s) in src.iter_mut().enumerate() {
+                    s[i] = c[k] * opacity;
+                }
+            }
+            let src = src.map(F32x::from_array);
+
+            target[p] = composite(blend, src, target[p], coverage);
         }
+
+        pp += y_hat;
     }
 }
 
+/// returns the pre-multiplied color of `image` at `p` (in image pixels).
+pub fn sample_image(image: &ImageSource, p: F32x2, sampling: ImageSampling, extend: ImageExtend) -> F32x4 {
+    let w = image.size[0] as i32;
+    let h = image.size[1] as i32;
+
+    let texel = |x: i32, y: i32| -> F32x4 {
+        let x = extend_coord(x, w, extend);
+        let y = extend_coord(y, h, extend);
+        argb_unpack_premultiply(image.pixels[(y*w + x) as usize])
+    };
+
+    // keep the integer coordinates (and their neighbors) in range.
+    let limit = (1 << 24) as f32;
+    let p = p.clamp(F32x2::splat(-limit), F32x2::splat(limit));
+
+    match sampling {
+        ImageSampling::Nearest => {
+            texel(p.x().ffloor() as i32, p.y().ffloor() as i32)
+        }
+
+        ImageSampling::Bilinear => {
+            let px = p.x() - 0.5;
+            let py = p.y() - 0.5;
+            let x0 = px.ffloor();
+            let y0 = py.ffloor();
+            let tx = F32x4::splat(px - x0);
+            let ty = F32x4::splat(py - y0);
+            let x0 = x0 as i32;
+            let y0 = y0 as i32;
+
+            let one = F32x4::splat(1.0);
+            let c0 = (one - tx)*texel(x0, y0    ) + tx*texel(x0 + 1, y0    );
+            let c1 = (one - tx)*texel(x0, y0 + 1) + tx*texel(x0 + 1, y0 + 1);
+            (one - ty)*c0 + ty*c1
+        }
+
+        ImageSampling::Bicubic => {
+            let px = p.x() - 0.5;
+            let py = p.y() - 0.5;
+            let x0 = px.ffloor();
+            let y0 = py.ffloor();
+            let wx = catmull_rom_weights(px - x0);
+            let wy = catmull_rom_weights(py - y0);
+            let x0 = x0 as i32;
+            let y0 = y0 as i32;
+
+            let mut c = F32x4::ZERO();
+            for (j, wy) in wy.into_iter().enumerate() {
+                let mut row = F32x4::ZERO();
+                for (i, wx) in wx.into_iter().enumerate() {
+                    row += F32x4::splat(wx) * texel(x0 - 1 + i as i32, y0 - 1 + j as i32);
+                }
+                c += F32x4::splat(wy) * row;
+            }
+
+            // catmull-rom overshoots, keep the color pre-multiplied.
+            let a = c[3].clamp(0.0, 1.0);
+            let rgb = c.clamp(F32x4::ZERO(), F32x4::splat(a));
+            F32x4::new(rgb[0], rgb[1], rgb[2], a)
+        }
+    }
+}
+
+#[inline(always)]
+fn extend_coord(i: i32, n: i32, extend: ImageExtend) -> i32 {
+    match extend {
+        ImageExtend::Pad => i.clamp(0, n - 1),
+
+        ImageExtend::Repeat => i.rem_euclid(n),
+
+        ImageExtend::Reflect => {
+            let m = i.rem_euclid(2*n);
+            if m < n { m } else { 2*n - 1 - m }
+        }
+    }
+}
+
+#[inline(always)]
+fn catmull_rom_weights(t: f32) -> [f32; 4] {
+    [((-0.5*t + 1.0)*t - 0.5)*t,
+     (1.5*t - 2.5)*t*t + 1.0,
+     ((-1.5*t + 2.0)*t + 0.5)*t,
+     (0.5*t - 0.5)*t*t]
+}
+
+
+
+/// returns the transform from gradient to path coordinates.
+/// - `Relative` gradients are specified in the unit square of `aabb`,
+///   `tfx` applies within that square (like svg's `objectBoundingBox`).
+/// - returns `None` for `Relative` gradients on degenerate bounds.
+pub fn gradient_tfx(units: GradientUnits, tfx: Transform, aabb: Rect) -> Option<Transform> {
+    match units {
+        GradientUnits::Absolute => Some(tfx),
+
+        GradientUnits::Relative => {
+            let size = aabb.size();
+            if !(size.x() > 0.0 && size.y() > 0.0) {
+                return None;
+            }
+            Some((Transform::translate(aabb.min) * Transform::scale(size)) * tfx)
+        }
+    }
+}
+
+/// maps the gradient parameter `t` according to `spread`.
+/// - `Pad` is left to the stop lookup, which clamps.
+#[inline(always)]
+fn spread_gradient_t<const N: usize>(t: F32x<N>, spread: SpreadMethod) -> F32x<N>
+where (): SimdLanes<N>
+{
+    // keeps the integer conversion in range.
+    let floor = |v: F32x<N>| {
+        let limit = (1 << 22) as f32;
+        let v = v.clamp(F32x::splat(-limit), F32x::splat(limit));
+        let i = v.to_i32_unck().to_f32();
+        i - i.gt(v).select(F32x::ONE(), F32x::ZERO())
+    };
+
+    match spread {
+        SpreadMethod::Pad => t,
+
+        SpreadMethod::Repeat => t - floor(t),
+
+        SpreadMethod::Reflect => {
+            let two = F32x::splat(2.0);
+            let u = t - two*floor(t*F32x::splat(0.5));
+            u.gt(F32x::ONE()).select(two - u, u)
+        }
+    }
+}
 
 
 #[derive(Clone, Copy, Debug)]
@@ -491,25 +1798,127 @@ pub struct GradientStopF32 {
     pub color:   F32x4,
 }
 
+/// the color of the gradient with `stops` at `pt`.
+/// - `stops` must not be empty.
+/// - output pre-multiplied alpha: no.
+#[inline(always)]
+fn gradient_stops_color<const N: usize>(pt: F32x<N>, stops: &[GradientStopF32]) -> [F32x<N>; 4]
+where (): SimdLanes<N>
+{
+    let stop_0 = stops[0];
+    let stop_n = stops[stops.len() - 1];
+
+    let (mut sr, mut sg, mut sb, mut sa);
+
+    let le_0 = pt.le(F32x::splat(stop_0.offset));
+    let ge_n = pt.ge(F32x::splat(stop_n.offset));
+
+    if le_0.all() {
+        sr = F32x::splat(stop_0.color[0]);
+        sg = F32x::splat(stop_0.color[1]);
+        sb = F32x::splat(stop_0.color[2]);
+        sa = F32x::splat(stop_0.color[3]);
+    }
+    else if ge_n.all() {
+        sr = F32x::splat(stop_n.color[0]);
+        sg = F32x::splat(stop_n.color[1]);
+        sb = F32x::splat(stop_n.color[2]);
+        sa = F32x::splat(stop_n.color[3]);
+    }
+    else {
+        debug_assert!(stops.len() > 1);
+
+        // handle ge_n case.
+        sr = F32x::splat(stop_n.color[0]);
+        sg = F32x::splat(stop_n.color[1]);
+        sb = F32x::splat(stop_n.color[2]);
+        sa = F32x::splat(stop_n.color[3]);
+
+        let mut has_color = ge_n;
+
+        for i in 0..stops.len() - 1 {
+            let curr = stops[i];
+            let next = stops[i + 1];
+
+            let lt_next = pt.lt(F32x::splat(next.offset));
+            let was_new = !has_color & lt_next;
+
+            if was_new.any() {
+                let scale = 1.0.safe_div(next.offset - curr.offset, 1_000_000.0);
+
+                let t = (pt - F32x::splat(curr.offset)) * scale;
+                let t = t.clamp(F32x::ZERO(), F32x::ONE());
+
+                let r = (F32x::ONE() - t)*curr.color[0] + t*next.color[0];
+                let g = (F32x::ONE() - t)*curr.color[1] + t*next.color[1];
+                let b = (F32x::ONE() - t)*curr.color[2] + t*next.color[2];
+                let a = (F32x::ONE() - t)*curr.color[3] + t*next.color[3];
+
+                sr = was_new.select(r, sr);
+                sg = was_new.select(g, sg);
+                sb = was_new.select(b, sb);
+                sa = was_new.select(a, sa);
+
+                has_color |= was_new;
+                if has_color.all() {
+                    break;
+                }
+            }
+        }
+
+        debug_assert!(has_color.all());
+    }
+
+    [sr, sg, sb, sa]
+}
+
+
 /// - input pre-multiplied alpha: no.
+/// - `mask` starts at row `first_row` of the raster rect, that `p0` and `p1` are relative to.
 pub fn fill_mask_linear_gradient_2<const N: usize>(
     p0: F32x2,
     p1: F32x2,
-    color_0: F32x4,
-    color_1: F32x4,
+    stop_0: GradientStopF32,
+    stop_1: GradientStopF32,
+    spread: SpreadMethod,
+    opacity: f32,
+    blend: BlendMode,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    with_blend_mode!(blend, B => fill_mask_linear_gradient_2_impl::<N, B>(p0, p1, stop_0, stop_1, spread, opacity, first_row, mask, offset, target))
+}
+
+fn fill_mask_linear_gradient_2_impl<const N: usize, B: StaticBlend>(
+    p0: F32x2,
+    p1: F32x2,
+    stop_0: GradientStopF32,
+    stop_1: GradientStopF32,
+    spread: SpreadMethod,
     opacity: f32,
+    first_row: u32,
     mask: &Img<f32>,
-    offset: U32x2,
+    offset: I32x2,
     target: &mut ImgMut<[F32x<N>; 4]>)
 where (): SimdLanes<N>
 {
-    let n = N as u32;
+    let blend = B::MODE;
+
+    let n = N as i32;
+
+    let GradientStopF32 { color: color_0, offset: offset_0 } = stop_0;
+    let GradientStopF32 { color: color_1, offset: offset_1 } = stop_1;
+
+    let step_scale = 1.0.safe_div(offset_1 - offset_0, 1_000_000.0);
 
-    let size = U32x2::new(n*target.width(), target.height());
+    let size = U32x2::new(N as u32 * target.width(), target.height()).as_i32();
 
     let begin = offset;
-    let end   = (offset + mask.size()).min(size);
-    if begin.eq(end).any() {
+    let end   = (offset + mask.size().as_i32()).min(size);
+    if end.x() <= begin.x().max(0) || end.y() <= begin.y().max(0) {
         return;
     }
 
@@ -520,7 +1929,8 @@ where (): SimdLanes<N>
 
     let px0 = F32x::from_array(core::array::from_fn(|i| i as f32 + 0.5));
 
-    let mut py = F32x::splat(0.5);
+    // `py` steps by whole pixels, so this is exact.
+    let mut py = F32x::splat(0.5 + first_row as f32);
 
     for y in begin.y() .. end.y() {
         let mut px = px0;
@@ -534,7 +1944,7 @@ where (): SimdLanes<N>
 
             let p = (u as usize, y as usize);
 
-            if coverage.lt(F32x::splat(0.5/255.0)).all() {
+            if x < 0 || y < 0 || coverage.lt(F32x::splat(0.5/255.0)).all() {
                 px += F32x::splat(n as f32);
                 continue;
             }
@@ -549,22 +1959,17 @@ where (): SimdLanes<N>
             let d1y = F32x::splat((p1 - p0)[1]);
             let pt = (dpx*d1x + dpy*d1y) / (d1x*d1x + d1y*d1y);
 
+            let pt = spread_gradient_t(pt, spread);
+            let pt = (pt - F32x::splat(offset_0)) * step_scale;
+
             let pt = pt.clamp(F32x::ZERO(), F32x::ONE());
 
             let sr =  (F32x::ONE() - pt)*color_0[0] + pt*color_1[0];
             let sg =  (F32x::ONE() - pt)*color_0[1] + pt*color_1[1];
             let sb =  (F32x::ONE() - pt)*color_0[2] + pt*color_1[2];
-            let sa = ((F32x::ONE() - pt)*color_0[3] + pt*color_1[3]) * coverage * opacity;
+            let sa = ((F32x::ONE() - pt)*color_0[3] + pt*color_1[3]) * opacity;
 
-            let [tr, tg, tb, ta] = target[p];
-
-            let one = F32x::splat(1.0);
-            target[p] = [
-                sa*sr + (one - sa)*tr,
-                sa*sg + (one - sa)*tg,
-                sa*sb + (one - sa)*tb,
-                sa    + (one - sa)*ta,
-            ];
+            target[p] = composite(blend, [sa*sr, sa*sg, sa*sb, sa], target[p], coverage);
 
             px += F32x::splat(n as f32);
         }
@@ -574,26 +1979,44 @@ where (): SimdLanes<N>
 }
 
 /// - input pre-multiplied alpha: no.
+/// - `mask` starts at row `first_row` of the raster rect, that `p0` and `p1` are relative to.
 pub fn fill_mask_linear_gradient_n<const N: usize>(
     p0: F32x2,
     p1: F32x2,
     stops: &[GradientStopF32],
+    spread: SpreadMethod,
     opacity: f32,
+    blend: BlendMode,
+    first_row: u32,
     mask: &Img<f32>,
-    offset: U32x2,
+    offset: I32x2,
     target: &mut ImgMut<[F32x<N>; 4]>)
 where (): SimdLanes<N>
 {
-    let n = N as u32;
+    with_blend_mode!(blend, B => fill_mask_linear_gradient_n_impl::<N, B>(p0, p1, stops, spread, opacity, first_row, mask, offset, target))
+}
 
-    let stop_0 = stops[0];
-    let stop_n = stops[stops.len() - 1];
+fn fill_mask_linear_gradient_n_impl<const N: usize, B: StaticBlend>(
+    p0: F32x2,
+    p1: F32x2,
+    stops: &[GradientStopF32],
+    spread: SpreadMethod,
+    opacity: f32,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let blend = B::MODE;
+
+    let n = N as i32;
 
-    let size = U32x2::new(n*target.width(), target.height());
+    let size = U32x2::new(N as u32 * target.width(), target.height()).as_i32();
 
     let begin = offset;
-    let end   = (offset + mask.size()).min(size);
-    if begin.eq(end).any() {
+    let end   = (offset + mask.size().as_i32()).min(size);
+    if end.x() <= begin.x().max(0) || end.y() <= begin.y().max(0) {
         return;
     }
 
@@ -604,7 +2027,8 @@ where (): SimdLanes<N>
 
     let px0 = F32x::from_array(core::array::from_fn(|i| i as f32 + 0.5));
 
-    let mut py = F32x::splat(0.5);
+    // `py` steps by whole pixels, so this is exact.
+    let mut py = F32x::splat(0.5 + first_row as f32);
 
     for y in begin.y() .. end.y() {
         let mut px = px0;
@@ -618,7 +2042,7 @@ where (): SimdLanes<N>
 
             let p = (u as usize, y as usize);
 
-            if coverage.lt(F32x::splat(0.5/255.0)).all() {
+            if x < 0 || y < 0 || coverage.lt(F32x::splat(0.5/255.0)).all() {
                 px += F32x::splat(n as f32);
                 continue;
             }
@@ -630,80 +2054,12 @@ where (): SimdLanes<N>
             let d1y = F32x::splat((p1 - p0)[1]);
             let pt = (dpx*d1x + dpy*d1y) / (d1x*d1x + d1y*d1y);
 
+            let pt = spread_gradient_t(pt, spread);
 
-            let (mut sr, mut sg, mut sb, mut sa);
-
-            let le_0 = pt.le(F32x::splat(stop_0.offset));
-            let ge_n = pt.ge(F32x::splat(stop_n.offset));
-
-            if le_0.all() {
-                sr = F32x::splat(stop_0.color[0]);
-                sg = F32x::splat(stop_0.color[1]);
-                sb = F32x::splat(stop_0.color[2]);
-                sa = F32x::splat(stop_0.color[3]);
-            }
-            else if ge_n.all() {
-                sr = F32x::splat(stop_n.color[0]);
-                sg = F32x::splat(stop_n.color[1]);
-                sb = F32x::splat(stop_n.color[2]);
-                sa = F32x::splat(stop_n.color[3]);
-            }
-            else {
-                debug_assert!(stops.len() > 1);
-
-                // handle ge_n case.
-                sr = F32x::splat(stop_n.color[0]);
-                sg = F32x::splat(stop_n.color[1]);
-                sb = F32x::splat(stop_n.color[2]);
-                sa = F32x::splat(stop_n.color[3]);
-
-                let mut has_color = ge_n;
+            let [sr, sg, sb, sa] = gradient_stops_color(pt, stops);
+            let sa = sa * opacity;
 
-                for i in 0..stops.len() - 1 {
-                    let curr = stops[i];
-                    let next = stops[i + 1];
-
-                    let lt_next = pt.lt(F32x::splat(next.offset));
-                    let was_new = !has_color & lt_next;
-
-                    if was_new.any() {
-                        let scale = 1.0.safe_div(next.offset - curr.offset, 1_000_000.0);
-
-                        let t = (pt - F32x::splat(curr.offset)) * scale;
-                        let t = t.clamp(F32x::ZERO(), F32x::ONE());
-
-                        let r = (F32x::ONE() - t)*curr.color[0] + t*next.color[0];
-                        let g = (F32x::ONE() - t)*curr.color[1] + t*next.color[1];
-                        let b = (F32x::ONE() - t)*curr.color[2] + t*next.color[2];
-                        let a = (F32x::ONE() - t)*curr.color[3] + t*next.color[3];
-
-                        sr = was_new.select(r, sr);
-                        sg = was_new.select(g, sg);
-                        sb = was_new.select(b, sb);
-                        sa = was_new.select(a, sa);
-
-                        has_color |= was_new;
-                        if has_color.all() {
-                            break;
-                        }
-                    }
-                }
-
-                debug_assert!(has_color.all());
-            }
-
-            let sa = sa * coverage * opacity;
-
-
-            let [tr, tg, tb, ta] = target[p];
-
-            let one = F32x::splat(1.0);
-            target[p] = [
-                sa*sr + (one - sa)*tr,
-                sa*sg + (one - sa)*tg,
-                sa*sb + (one - sa)*tb,
-                sa    + (one - sa)*ta,
-            ];
+            target[p] = composite(blend, [sa*sr, sa*sg, sa*sb, sa], target[p], coverage);
 
             px += F32x::splat(n as f32);
         }
@@ -714,6 +2070,7 @@ where (): SimdLanes<N>
 
 
 /// - input pre-multiplied alpha: no.
+/// - `mask` starts at row `first_row` of the raster rect at `raster_origin`.
 pub fn fill_mask_radial_gradient_2<const N: usize>(
     raster_origin: F32x2,
     inv_tfx: Transform,
@@ -722,12 +2079,33 @@ pub fn fill_mask_radial_gradient_2<const N: usize>(
     stop_0: GradientStopF32,
     stop_1: GradientStopF32,
     opacity: f32,
+    blend: BlendMode,
+    first_row: u32,
     mask: &Img<f32>,
-    offset: U32x2,
+    offset: I32x2,
     target: &mut ImgMut<[F32x<N>; 4]>)
 where (): SimdLanes<N>
 {
-    let n = N as u32;
+    with_blend_mode!(blend, B => fill_mask_radial_gradient_2_impl::<N, B>(raster_origin, inv_tfx, inv_grad_tfx, gradient, stop_0, stop_1, opacity, first_row, mask, offset, target))
+}
+
+fn fill_mask_radial_gradient_2_impl<const N: usize, B: StaticBlend>(
+    raster_origin: F32x2,
+    inv_tfx: Transform,
+    inv_grad_tfx: Transform,
+    gradient: &RadialGradient,
+    stop_0: GradientStopF32,
+    stop_1: GradientStopF32,
+    opacity: f32,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let blend = B::MODE;
+
+    let n = N as i32;
 
     let GradientStopF32 { color: color_0, offset: offset_0 } = stop_0;
     let GradientStopF32 { color: color_1, offset: offset_1 } = stop_1;
@@ -739,11 +2117,11 @@ where (): SimdLanes<N>
 
     let step_scale = 1.0.safe_div(offset_1 - offset_0, 1_000_000.0);
 
-    let size = U32x2::new(n*target.width(), target.height());
+    let size = U32x2::new(N as u32 * target.width(), target.height()).as_i32();
 
     let begin = offset;
-    let end   = (offset + mask.size()).min(size);
-    if begin.eq(end).any() {
+    let end   = (offset + mask.size().as_i32()).min(size);
+    if end.x() <= begin.x().max(0) || end.y() <= begin.y().max(0) {
         return;
     }
 
@@ -762,6 +2140,9 @@ where (): SimdLanes<N>
     //let x_offsets_y = F32x::new((0.0*x_hat)[1], (1.0*x_hat)[1], (2.0*x_hat)[1], (3.0*x_hat)[1]);
 
     let mut pp = start;
+    for _ in 0..first_row {
+        pp += y_hat;
+    }
 
     for y in begin.y() .. end.y() {
         let mut px = F32x::splat(pp[0]) + x_offsets_x;
@@ -776,7 +2157,7 @@ where (): SimdLanes<N>
 
             let p = (u as usize, y as usize);
 
-            if coverage.lt(F32x::splat(0.5/255.0)).all() {
+            if x < 0 || y < 0 || coverage.lt(F32x::splat(0.5/255.0)).all() {
                 px += F32x::splat(n as f32 * x_hat[0]);
                 py += F32x::splat(n as f32 * x_hat[1]);
                 continue;
@@ -802,6 +2183,7 @@ where (): SimdLanes<N>
             let fr = F32x::splat(fr);
             let pt = (l - fr) / (k*l - fr);
 
+            let pt = spread_gradient_t(pt, gradient.spread);
             let pt = (pt - F32x::splat(offset_0)) * step_scale;
 
             let pt = pt.clamp(F32x::ZERO(), F32x::ONE());
@@ -809,17 +2191,9 @@ where (): SimdLanes<N>
             let sr =  (F32x::ONE() - pt)*color_0[0] + pt*color_1[0];
             let sg =  (F32x::ONE() - pt)*color_0[1] + pt*color_1[1];
             let sb =  (F32x::ONE() - pt)*color_0[2] + pt*color_1[2];
-            let sa = ((F32x::ONE() - pt)*color_0[3] + pt*color_1[3]) * coverage * opacity;
+            let sa = ((F32x::ONE() - pt)*color_0[3] + pt*color_1[3]) * opacity;
 
-            let [tr, tg, tb, ta] = target[p];
-
-            let one = F32x::splat(1.0);
-            target[p] = [
-                sa*sr + (one - sa)*tr,
-                sa*sg + (one - sa)*tg,
-                sa*sb + (one - sa)*tb,
-                sa    + (one - sa)*ta,
-            ];
+            target[p] = composite(blend, [sa*sr, sa*sg, sa*sb, sa], target[p], coverage);
 
             px += F32x::splat(n as f32 * x_hat[0]);
             py += F32x::splat(n as f32 * x_hat[1]);
@@ -830,6 +2204,7 @@ where (): SimdLanes<N>
 }
 
 /// - input pre-multiplied alpha: no.
+/// - `mask` starts at row `first_row` of the raster rect at `raster_origin`.
 pub fn fill_mask_radial_gradient_n<const N: usize>(
     raster_origin: F32x2,
     inv_tfx: Transform,
@@ -837,26 +2212,43 @@ pub fn fill_mask_radial_gradient_n<const N: usize>(
     gradient: &RadialGradient,
     stops: &[GradientStopF32],
     opacity: f32,
+    blend: BlendMode,
+    first_row: u32,
     mask: &Img<f32>,
-    offset: U32x2,
+    offset: I32x2,
     target: &mut ImgMut<[F32x<N>; 4]>)
 where (): SimdLanes<N>
 {
-    let n = 4;
+    with_blend_mode!(blend, B => fill_mask_radial_gradient_n_impl::<N, B>(raster_origin, inv_tfx, inv_grad_tfx, gradient, stops, opacity, first_row, mask, offset, target))
+}
 
-    let stop_0 = stops[0];
-    let stop_n = stops[stops.len() - 1];
+fn fill_mask_radial_gradient_n_impl<const N: usize, B: StaticBlend>(
+    raster_origin: F32x2,
+    inv_tfx: Transform,
+    inv_grad_tfx: Transform,
+    gradient: &RadialGradient,
+    stops: &[GradientStopF32],
+    opacity: f32,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let blend = B::MODE;
+
+    let n = 4;
 
     let cp = gradient.cp;
     let cr = gradient.cr;
     let fp = gradient.fp;
     let fr = gradient.fr;
 
-    let size = U32x2::new(n*target.width(), target.height());
+    let size = U32x2::new(N as u32 * target.width(), target.height()).as_i32();
 
     let begin = offset;
-    let end   = (offset + mask.size()).min(size);
-    if begin.eq(end).any() {
+    let end   = (offset + mask.size().as_i32()).min(size);
+    if end.x() <= begin.x().max(0) || end.y() <= begin.y().max(0) {
         return;
     }
 
@@ -875,6 +2267,9 @@ where (): SimdLanes<N>
     //let x_offsets_y = F32x::new((0.0*x_hat)[1], (1.0*x_hat)[1], (2.0*x_hat)[1], (3.0*x_hat)[1]);
 
     let mut pp = start;
+    for _ in 0..first_row {
+        pp += y_hat;
+    }
 
     for y in begin.y() .. end.y() {
         let mut px = F32x::splat(pp[0]) + x_offsets_x;
@@ -889,7 +2284,7 @@ where (): SimdLanes<N>
 
             let p = (u as usize, y as usize);
 
-            if coverage.lt(F32x::splat(0.5/255.0)).all() {
+            if x < 0 || y < 0 || coverage.lt(F32x::splat(0.5/255.0)).all() {
                 px += F32x::splat(n as f32 * x_hat[0]);
                 py += F32x::splat(n as f32 * x_hat[1]);
                 continue;
@@ -915,80 +2310,220 @@ where (): SimdLanes<N>
             let fr = F32x::splat(fr);
             let pt = (l - fr) / (k*l - fr);
 
+            let pt = spread_gradient_t(pt, gradient.spread);
 
-            let (mut sr, mut sg, mut sb, mut sa);
+            let [sr, sg, sb, sa] = gradient_stops_color(pt, stops);
+            let sa = sa * opacity;
 
-            let le_0 = pt.le(F32x::splat(stop_0.offset));
-            let ge_n = pt.ge(F32x::splat(stop_n.offset));
+            target[p] = composite(blend, [sa*sr, sa*sg, sa*sb, sa], target[p], coverage);
 
-            if le_0.all() {
-                sr = F32x::splat(stop_0.color[0]);
-                sg = F32x::splat(stop_0.color[1]);
-                sb = F32x::splat(stop_0.color[2]);
-                sa = F32x::splat(stop_0.color[3]);
-            }
-            else if ge_n.all() {
-                sr = F32x::splat(stop_n.color[0]);
-                sg = F32x::splat(stop_n.color[1]);
-                sb = F32x::splat(stop_n.color[2]);
-                sa = F32x::splat(stop_n.color[3]);
+            px += F32x::splat(n as f32 * x_hat[0]);
+            py += F32x::splat(n as f32 * x_hat[1]);
+        }
+
+        pp += y_hat;
+    }
+}
+
+
+
+/// - input pre-multiplied alpha: no.
+/// - `mask` starts at row `first_row` of the raster rect at `raster_origin`.
+pub fn fill_mask_conic_gradient_2<const N: usize>(
+    raster_origin: F32x2,
+    inv_tfx: Transform,
+    inv_grad_tfx: Transform,
+    gradient: &ConicGradient,
+    stop_0: GradientStopF32,
+    stop_1: GradientStopF32,
+    opacity: f32,
+    blend: BlendMode,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    with_blend_mode!(blend, B => fill_mask_conic_gradient_2_impl::<N, B>(raster_origin, inv_tfx, inv_grad_tfx, gradient, stop_0, stop_1, opacity, first_row, mask, offset, target))
+}
+
+fn fill_mask_conic_gradient_2_impl<const N: usize, B: StaticBlend>(
+    raster_origin: F32x2,
+    inv_tfx: Transform,
+    inv_grad_tfx: Transform,
+    gradient: &ConicGradient,
+    stop_0: GradientStopF32,
+    stop_1: GradientStopF32,
+    opacity: f32,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let blend = B::MODE;
+
+    let n = N as i32;
+
+    let GradientStopF32 { color: color_0, offset: offset_0 } = stop_0;
+    let GradientStopF32 { color: color_1, offset: offset_1 } = stop_1;
+
+    let step_scale = 1.0.safe_div(offset_1 - offset_0, 1_000_000.0);
+
+    let size = U32x2::new(N as u32 * target.width(), target.height()).as_i32();
+
+    let begin = offset;
+    let end   = (offset + mask.size().as_i32()).min(size);
+    if end.x() <= begin.x().max(0) || end.y() <= begin.y().max(0) {
+        return;
+    }
+
+    let u0 = begin.x() / n;
+    let u1 = end.x()   / n;
+    assert!(u0 * n == begin.x());
+    assert!(u1 * n == end.x());
+
+    let start = (inv_grad_tfx * inv_tfx) * (raster_origin + F32x2::new(0.5, 0.5));
+    let x_hat = (inv_grad_tfx * inv_tfx).mul_normal(F32x2::new(1.0, 0.0));
+    let y_hat = (inv_grad_tfx * inv_tfx).mul_normal(F32x2::new(0.0, 1.0));
+
+    let x_offsets_x = F32x::from_array(core::array::from_fn(|i| i as f32)) * x_hat[0];
+    let x_offsets_y = F32x::from_array(core::array::from_fn(|i| i as f32)) * x_hat[1];
+
+    let mut pp = start - gradient.center;
+    for _ in 0..first_row {
+        pp += y_hat;
+    }
+
+    for y in begin.y() .. end.y() {
+        let mut px = F32x::splat(pp[0]) + x_offsets_x;
+        let mut py = F32x::splat(pp[1]) + x_offsets_y;
+
+        for u in u0..u1 {
+            let x = u * n;
+            let mask_x = (x - begin.x()) as usize;
+            let mask_y = (y - begin.y()) as usize;
+
+            let coverage = F32x::from_array(mask.read_n(mask_x, mask_y));
+
+            let p = (u as usize, y as usize);
+
+            if x < 0 || y < 0 || coverage.lt(F32x::splat(0.5/255.0)).all() {
+                px += F32x::splat(n as f32 * x_hat[0]);
+                py += F32x::splat(n as f32 * x_hat[1]);
+                continue;
             }
-            else {
-                debug_assert!(stops.len() > 1);
 
-                // handle ge_n case.
-                sr = F32x::splat(stop_n.color[0]);
-                sg = F32x::splat(stop_n.color[1]);
-                sb = F32x::splat(stop_n.color[2]);
-                sa = F32x::splat(stop_n.color[3]);
+            let pt = conic_gradient_t(px, py, gradient.start_angle, gradient.angle_range);
 
-                let mut has_color = ge_n;
+            let pt = spread_gradient_t(pt, gradient.spread);
+            let pt = (pt - F32x::splat(offset_0)) * step_scale;
 
-                for i in 0..stops.len() - 1 {
-                    let curr = stops[i];
-                    let next = stops[i + 1];
+            let pt = pt.clamp(F32x::ZERO(), F32x::ONE());
 
-                    let lt_next = pt.lt(F32x::splat(next.offset));
-                    let was_new = !has_color & lt_next;
+            let sr =  (F32x::ONE() - pt)*color_0[0] + pt*color_1[0];
+            let sg =  (F32x::ONE() - pt)*color_0[1] + pt*color_1[1];
+            let sb =  (F32x::ONE() - pt)*color_0[2] + pt*color_1[2];
+            let sa = ((F32x::ONE() - pt)*color_0[3] + pt*color_1[3]) * opacity;
 
-                    if was_new.any() {
-                        let scale = 1.0.safe_div(next.offset - curr.offset, 1_000_000.0);
+            target[p] = composite(blend, [sa*sr, sa*sg, sa*sb, sa], target[p], coverage);
 
-                        let t = (pt - F32x::splat(curr.offset)) * scale;
-                        let t = t.clamp(F32x::ZERO(), F32x::ONE());
+            px += F32x::splat(n as f32 * x_hat[0]);
+            py += F32x::splat(n as f32 * x_hat[1]);
+        }
 
-                        let r = (F32x::ONE() - t)*curr.color[0] + t*next.color[0];
-                        let g = (F32x::ONE() - t)*curr.color[1] + t*next.color[1];
-                        let b = (F32x::ONE() - t)*curr.color[2] + t*next.color[2];
-                        let a = (F32x::ONE() - t)*curr.color[3] + t*next.color[3];
+        pp += y_hat;
+    }
+}
 
-                        sr = was_new.select(r, sr);
-                        sg = was_new.select(g, sg);
-                        sb = was_new.select(b, sb);
-                        sa = was_new.select(a, sa);
+/// - input pre-multiplied alpha: no.
+/// - `mask` starts at row `first_row` of the raster rect at `raster_origin`.
+pub fn fill_mask_conic_gradient_n<const N: usize>(
+    raster_origin: F32x2,
+    inv_tfx: Transform,
+    inv_grad_tfx: Transform,
+    gradient: &ConicGradient,
+    stops: &[GradientStopF32],
+    opacity: f32,
+    blend: BlendMode,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    with_blend_mode!(blend, B => fill_mask_conic_gradient_n_impl::<N, B>(raster_origin, inv_tfx, inv_grad_tfx, gradient, stops, opacity, first_row, mask, offset, target))
+}
 
-                        has_color |= was_new;
-                        if has_color.all() {
-                            break;
-                        }
-                    }
-                }
+fn fill_mask_conic_gradient_n_impl<const N: usize, B: StaticBlend>(
+    raster_origin: F32x2,
+    inv_tfx: Transform,
+    inv_grad_tfx: Transform,
+    gradient: &ConicGradient,
+    stops: &[GradientStopF32],
+    opacity: f32,
+    first_row: u32,
+    mask: &Img<f32>,
+    offset: I32x2,
+    target: &mut ImgMut<[F32x<N>; 4]>)
+where (): SimdLanes<N>
+{
+    let blend = B::MODE;
+
+    let n = N as i32;
+
+    let size = U32x2::new(N as u32 * target.width(), target.height()).as_i32();
+
+    let begin = offset;
+    let end   = (offset + mask.size().as_i32()).min(size);
+    if end.x() <= begin.x().max(0) || end.y() <= begin.y().max(0) {
+        return;
+    }
+
+    let u0 = begin.x() / n;
+    let u1 = end.x()   / n;
+    assert!(u0 * n == begin.x());
+    assert!(u1 * n == end.x());
+
+    let start = (inv_grad_tfx * inv_tfx) * (raster_origin + F32x2::new(0.5, 0.5));
+    let x_hat = (inv_grad_tfx * inv_tfx).mul_normal(F32x2::new(1.0, 0.0));
+    let y_hat = (inv_grad_tfx * inv_tfx).mul_normal(F32x2::new(0.0, 1.0));
+
+    let x_offsets_x = F32x::from_array(core::array::from_fn(|i| i as f32)) * x_hat[0];
+    let x_offsets_y = F32x::from_array(core::array::from_fn(|i| i as f32)) * x_hat[1];
+
+    let mut pp = start - gradient.center;
+    for _ in 0..first_row {
+        pp += y_hat;
+    }
+
+    for y in begin.y() .. end.y() {
+        let mut px = F32x::splat(pp[0]) + x_offsets_x;
+        let mut py = F32x::splat(pp[1]) + x_offsets_y;
+
+        for u in u0..u1 {
+            let x = u * n;
+            let mask_x = (x - begin.x()) as usize;
+            let mask_y = (y - begin.y()) as usize;
+
+            let coverage = F32x::from_array(mask.read_n(mask_x, mask_y));
+
+            let p = (u as usize, y as usize);
 
-                debug_assert!(has_color.all());
+            if x < 0 || y < 0 || coverage.lt(F32x::splat(0.5/255.0)).all() {
+                px += F32x::splat(n as f32 * x_hat[0]);
+                py += F32x::splat(n as f32 * x_hat[1]);
+                continue;
             }
 
-            let sa = sa * coverage * opacity;
+            let pt = conic_gradient_t(px, py, gradient.start_angle, gradient.angle_range);
 
+            let pt = spread_gradient_t(pt, gradient.spread);
 
-            let [tr, tg, tb, ta] = target[p];
+            let [sr, sg, sb, sa] = gradient_stops_color(pt, stops);
+            let sa = sa * opacity;
 
-            let one = F32x::splat(1.0);
-            target[p] = [
-                sa*sr + (one - sa)*tr,
-                sa*sg + (one - sa)*tg,
-                sa*sb + (one - sa)*tb,
-                sa    + (one - sa)*ta,
-            ];
+            target[p] = composite(blend, [sa*sr, sa*sg, sa*sb, sa], target[p], coverage);
 
             px += F32x::splat(n as f32 * x_hat[0]);
             py += F32x::splat(n as f32 * x_hat[1]);
@@ -998,3 +2533,200 @@ where (): SimdLanes<N>
     }
 }
 
+/// the conic gradient parameter of the point `(x, y)` relative to the center.
+/// - `t` is in `[0, 1)` for a full turn in the direction of `angle_range`.
+#[inline(always)]
+fn conic_gradient_t<const N: usize>(x: F32x<N>, y: F32x<N>, start_angle: f32, angle_range: f32) -> F32x<N>
+where (): SimdLanes<N>
+{
+    use core::f32::consts::{PI, TAU};
+
+    let zero = F32x::ZERO();
+
+    // atan2 approximation, max error ~2e-4 radians.
+    let ax = x.abs();
+    let ay = y.abs();
+    let lo = ax.min(ay);
+    let hi = ax.max(ay);
+    let a = hi.gt(zero).select(lo / hi, zero);
+    let s = a*a;
+    let r = ((F32x::splat(-0.046_496_475)*s + F32x::splat(0.159_314_22))*s - F32x::splat(0.327_622_76))*s*a + a;
+    let r = ay.gt(ax).select(F32x::splat(PI/2.0) - r, r);
+    let r = x.lt(zero).select(F32x::splat(PI) - r, r);
+    let angle = y.lt(zero).select(-r, r);
+
+    // relative angle in `[0, tau)`.
+    let angle = angle - F32x::splat(start_angle);
+    let angle = angle - F32x::splat(TAU) * (angle * F32x::splat(1.0/TAU)).to_i32_unck().to_f32();
+    let angle = angle.lt(zero).select(angle + F32x::splat(TAU), angle);
+
+    // clockwise sweeps measure the angle backwards.
+    let angle =
+        if angle_range < 0.0 { angle.gt(zero).select(angle - F32x::splat(TAU), angle) }
+        else { angle };
+
+    angle * F32x::splat(1.0.safe_div(angle_range, 0.0))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiled_matches_untiled() {
+        let cmd_buf = CmdBuf::new(|cb| {
+            let blob = cb.build_path(|pb| {
+                pb.move_to([20.0, 30.0]);
+                pb.cubic_to([180.0, -40.0], [320.0, 120.0], [250.0, 240.0]);
+                pb.quad_to([120.0, 300.0], [30.0, 200.0]);
+                pb.close_path();
+            });
+            let clip = cb.build_path(|pb| {
+                pb.move_to([10.5, 140.25]);
+                pb.quad_to([150.0, -60.0], [290.0, 140.0]);
+                pb.quad_to([150.0, 330.0], [10.5, 140.25]);
+                pb.close_path();
+            });
+
+            let stops = cb.build_gradient_stops(|stops| {
+                stops.push(GradientStop { offset: 0.0, color: 0xffff8000 });
+                stops.push(GradientStop { offset: 0.5, color: 0x8000c0ff });
+                stops.push(GradientStop { offset: 1.0, color: 0xff202020 });
+            });
+            let gradient = cb.push_radial_gradient(RadialGradient {
+                cp: F32x2::new(0.5, 0.5),
+                cr: 0.5,
+                fp: F32x2::new(0.4, 0.3),
+                fr: 0.0,
+                spread: SpreadMethod::Pad,
+                units:  GradientUnits::Relative,
+                tfx:    Transform::ID(),
+                stops,
+            });
+
+            cb.push(Cmd::PushTransform { tfx: Transform::translate(F32x2::new(3.3, 7.7)) * Transform::scale1(1.1) });
+            cb.push(Cmd::DropShadow { path: blob, offset: F32x2::new(6.0, 9.0), blur_radius: 8.0, color: 0x80000000 });
+            cb.push(Cmd::PushClipPath { path: clip, fill_rule: FillRule::NonZero });
+            cb.push(Cmd::BeginGroup { opacity: 0.75, blend: BlendMode::Multiply, filter: None });
+            cb.push(Cmd::FillPath { path: blob, fill_rule: FillRule::EvenOdd, paint: Paint::RadialGradient(gradient), opacity: 1.0, blend: BlendMode::SrcOver });
+            cb.push(Cmd::StrokePath { path: blob, style: StrokeStyle::new(7.0), paint: Paint::Solid(0xff2060c0), opacity: 0.9, blend: BlendMode::SrcOver });
+            cb.push(Cmd::EndGroup);
+            cb.push(Cmd::PopClip);
+            cb.push(Cmd::PopTransform);
+        });
+
+        let params = RenderParams { clear: 0xffffffff, tfx: Transform::ID(), load: false };
+
+        // spans several tiles, and isn't a multiple of the tile size.
+        let size = [301, 283];
+
+        let mut expected = Image::<u32>::new(size);
+        render(&cmd_buf, &params, &mut expected.img_mut());
+        assert!(expected.data().iter().any(|p| *p != 0xffffffff));
+
+        // the second render reuses the tiles & worker buffers.
+        let mut renderer = Renderer::new();
+        for _ in 0..2 {
+            let mut tiled = Image::<u32>::new(size);
+            renderer.render_tiled(&cmd_buf, &params, &mut tiled.img_mut());
+            assert!(tiled.data() == expected.data());
+        }
+    }
+
+    /// pre-multiplied pixels with alpha 0, 0.5, 1, and 0.5.
+    fn alpha_pixels() -> Image<[F32x4; 4]> {
+        let pixels = [
+            [0.0, 0.0,  0.0,  0.0],
+            [0.5, 0.25, 0.0,  0.5],
+            [1.0, 0.5,  0.25, 1.0],
+            [0.0, 0.5,  0.25, 0.5],
+        ];
+        let mut image = Image::with_clear([1, 1], [F32x4::ZERO(); 4]);
+        image[(0, 0)] = core::array::from_fn(|c| F32x4::from_array(pixels.map(|p| p[c])));
+        image
+    }
+
+    #[test]
+    fn write_back_formats() {
+        use AlphaMode::*;
+
+        let image = alpha_pixels();
+        let to = I32x2::ZERO();
+
+        let mut rgba8 = Image::<u32>::new([4, 1]);
+        write_back(&image, to, &mut DynImgMut::Rgba8(rgba8.img_mut(), Straight));
+        assert_eq!(rgba8.data(), &[0x00000000, 0x800080ff, 0xff4080ff, 0x8080ff00]);
+        write_back(&image, to, &mut DynImgMut::Rgba8(rgba8.img_mut(), Premultiplied));
+        assert_eq!(rgba8.data(), &[0x00000000, 0x80004080, 0xff4080ff, 0x80408000]);
+
+        let mut bgra8 = Image::<u32>::new([4, 1]);
+        write_back(&image, to, &mut DynImgMut::Bgra8(bgra8.img_mut(), Straight));
+        assert_eq!(bgra8.data(), &[0x00000000, 0x80ff8000, 0xffff8040, 0x8000ff80]);
+        write_back(&image, to, &mut DynImgMut::Bgra8(bgra8.img_mut(), Premultiplied));
+        assert_eq!(bgra8.data(), &[0x00000000, 0x80804000, 0xffff8040, 0x80008040]);
+
+        let mut rgb565 = Image::<u16>::new([4, 1]);
+        write_back(&image, to, &mut DynImgMut::Rgb565(rgb565.img_mut(), Straight));
+        assert_eq!(rgb565.data(), &[0x0000, 0xfc00, 0xfc08, 0x07f0]);
+        write_back(&image, to, &mut DynImgMut::Rgb565(rgb565.img_mut(), Premultiplied));
+        assert_eq!(rgb565.data(), &[0x0000, 0x8200, 0xfc08, 0x0408]);
+
+        let mut a8 = Image::<u8>::new([4, 1]);
+        write_back(&image, to, &mut DynImgMut::A8(a8.img_mut()));
+        assert_eq!(a8.data(), &[0, 128, 255, 128]);
+
+        let mut rgba16 = Image::<[u16; 4]>::new([4, 1]);
+        write_back(&image, to, &mut DynImgMut::Rgba16(rgba16.img_mut(), Straight));
+        assert_eq!(rgba16.data(), &[
+            [0, 0, 0, 0],
+            [65535, 32768, 0, 32768],
+            [65535, 32768, 16384, 65535],
+            [0, 65535, 32768, 32768],
+        ]);
+        write_back(&image, to, &mut DynImgMut::Rgba16(rgba16.img_mut(), Premultiplied));
+        assert_eq!(rgba16.data(), &[
+            [0, 0, 0, 0],
+            [32768, 16384, 0, 32768],
+            [65535, 32768, 16384, 65535],
+            [0, 32768, 16384, 32768],
+        ]);
+
+        let mut rgba_f32 = Image::<[f32; 4]>::new([4, 1]);
+        write_back(&image, to, &mut DynImgMut::RgbaF32(rgba_f32.img_mut(), Straight));
+        assert_eq!(rgba_f32.data(), &[
+            [0.0, 0.0, 0.0,  0.0],
+            [1.0, 0.5, 0.0,  0.5],
+            [1.0, 0.5, 0.25, 1.0],
+            [0.0, 1.0, 0.5,  0.5],
+        ]);
+        write_back(&image, to, &mut DynImgMut::RgbaF32(rgba_f32.img_mut(), Premultiplied));
+        assert_eq!(rgba_f32.data(), &[
+            [0.0, 0.0,  0.0,  0.0],
+            [0.5, 0.25, 0.0,  0.5],
+            [1.0, 0.5,  0.25, 1.0],
+            [0.0, 0.5,  0.25, 0.5],
+        ]);
+    }
+
+    #[test]
+    fn load_round_trip() {
+        // opaque pixels survive straight alpha exactly.
+        let cmd_buf = CmdBuf::new(|_| {});
+        let params = RenderParams { clear: 0, tfx: Transform::ID(), load: true };
+
+        let mut image = Image::<u32>::new([256, 3]);
+        for y in 0..3 {
+            for x in 0..256 {
+                image[(x, y)] = 0xff000000 | (x as u32) << (8*y) | (255 - x as u32) << (8*((y + 1) % 3));
+            }
+        }
+        let expected = image.data().to_vec();
+
+        render(&cmd_buf, &params, DynImgMut::Rgba8(image.img_mut(), AlphaMode::Straight));
+        assert!(image.data() == &expected[..]);
+
+        render(&cmd_buf, &params, DynImgMut::Bgra8(image.img_mut(), AlphaMode::Straight));
+        assert!(image.data() == &expected[..]);
+    }
+}
@@ -0,0 +1,1021 @@
+use sti::arena::Arena;
+use sti::simd::*;
+
+use std::collections::HashMap;
+
+use crate::geometry::Transform;
+use crate::path::{Path, Verb};
+use crate::stroke::{StrokeStyle, LineCap, LineJoin};
+use crate::rasterizer::FillRule;
+use crate::blend::BlendMode;
+use crate::filter::*;
+use crate::cmd::*;
+
+
+/*  binary format for command buffers.
+
+    used to capture frames, and replay them in tests & benchmarks.
+
+    all values are little endian.
+    lengths & ids are u32. enums are u8.
+
+    layout:
+        magic   "RUGC"
+        version u32
+        paths                   count, (verbs: count, u8s)  (points: count, f32x2s)
+        images                  count, (size: u32x2)  (pixels: u32s)
+        linear gradients        count, ...
+        radial gradients        count, ...
+        conic gradients         count, ...
+        filters                 count, (primitives: count, ...)
+        commands                count, (tag: u8) ...
+
+    paths shared between commands are stored once.
+    commands refer to paths & resources by their index.
+*/
+
+
+pub const MAGIC: [u8; 4] = *b"RUGC";
+pub const VERSION: u32 = 1;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecodeError {
+    BadMagic,
+    UnsupportedVersion(u32),
+    UnexpectedEnd,
+    Invalid,
+}
+
+
+pub fn encode(cmd_buf: &CmdBuf) -> Vec<u8> {
+    // commands first, to collect the paths.
+    let mut paths: Vec<Path> = Vec::new();
+    let mut path_indices: HashMap<*const Verb, u32> = HashMap::new();
+    let mut path_index = |path: Path| -> u32 {
+        *path_indices.entry(path.verbs().as_ptr()).or_insert_with(|| {
+            paths.push(path);
+            (paths.len() - 1) as u32
+        })
+    };
+
+    let mut cmds = Writer::new();
+    cmds.len(cmd_buf.num_cmds());
+    for i in 0..cmd_buf.num_cmds() {
+        match *cmd_buf.cmd(i) {
+            Cmd::FillPath { path, fill_rule, paint, opacity, blend } => {
+                cmds.u8(0);
+                cmds.u32(path_index(path));
+                cmds.fill_rule(fill_rule);
+                cmds.paint(paint);
+                cmds.f32(opacity);
+                cmds.blend(blend);
+            }
+
+            Cmd::StrokePath { path, style, paint, opacity, blend } => {
+                cmds.u8(1);
+                cmds.u32(path_index(path));
+                cmds.f32(style.width);
+                cmds.u8(match style.cap {
+                    LineCap::Butt   => 0,
+                    LineCap::Round  => 1,
+                    LineCap::Square => 2,
+                });
+                cmds.u8(match style.join {
+                    LineJoin::Miter => 0,
+                    LineJoin::Round => 1,
+                    LineJoin::Bevel => 2,
+                });
+                cmds.f32(style.miter_limit);
+                cmds.f32s(style.dashes);
+                cmds.f32(style.dash_offset);
+                cmds.paint(paint);
+                cmds.f32(opacity);
+                cmds.blend(blend);
+            }
+
+            Cmd::DropShadow { path, offset, blur_radius, color } => {
+                cmds.u8(2);
+                cmds.u32(path_index(path));
+                cmds.f32x2(offset);
+                cmds.f32(blur_radius);
+                cmds.u32(color);
+            }
+
+            Cmd::BlurLayer { blur_radius } => {
+                cmds.u8(3);
+                cmds.f32(blur_radius);
+            }
+
+            Cmd::PushTransform { tfx } => {
+                cmds.u8(4);
+                cmds.tfx(tfx);
+            }
+
+            Cmd::PopTransform => {
+                cmds.u8(5);
+            }
+
+            Cmd::PushClipPath { path, fill_rule } => {
+                cmds.u8(6);
+                cmds.u32(path_index(path));
+                cmds.fill_rule(fill_rule);
+            }
+
+            Cmd::PopClip => {
+                cmds.u8(7);
+            }
+
+            Cmd::BeginGroup { opacity, blend, filter } => {
+                cmds.u8(8);
+                cmds.f32(opacity);
+                cmds.blend(blend);
+                // 0 is no filter.
+                cmds.u32(filter.map_or(0, |id| id.usize() as u32 + 1));
+            }
+
+            Cmd::EndGroup => {
+                cmds.u8(9);
+            }
+        }
+    }
+
+
+    let mut w = Writer::new();
+    w.out.extend_from_slice(&MAGIC);
+    w.u32(VERSION);
+
+    w.len(paths.len());
+    for path in &paths {
+        w.len(path.verbs().len());
+        for verb in path.verbs() {
+            w.u8(*verb as u8);
+        }
+
+        w.len(path.points().len());
+        for p in path.points() {
+            w.f32x2(*p);
+        }
+    }
+
+    let images = cmd_buf.images();
+    w.len(images.len());
+    for image in images {
+        w.u32(image.size[0]);
+        w.u32(image.size[1]);
+        for pixel in image.pixels {
+            w.u32(*pixel);
+        }
+    }
+
+    let linear_gradients = cmd_buf.linear_gradients();
+    w.len(linear_gradients.len());
+    for gradient in linear_gradients {
+        w.f32x2(gradient.p0);
+        w.f32x2(gradient.p1);
+        w.gradient_common(gradient.spread, gradient.units, gradient.tfx, gradient.stops);
+    }
+
+    let radial_gradients = cmd_buf.radial_gradients();
+    w.len(radial_gradients.len());
+    for gradient in radial_gradients {
+        w.f32x2(gradient.cp);
+        w.f32(gradient.cr);
+        w.f32x2(gradient.fp);
+        w.f32(gradient.fr);
+        w.gradient_common(gradient.spread, gradient.units, gradient.tfx, gradient.stops);
+    }
+
+    let conic_gradients = cmd_buf.conic_gradients();
+    w.len(conic_gradients.len());
+    for gradient in conic_gradients {
+        w.f32x2(gradient.center);
+        w.f32(gradient.start_angle);
+        w.f32(gradient.angle_range);
+        w.gradient_common(gradient.spread, gradient.units, gradient.tfx, gradient.stops);
+    }
+
+    let filters = cmd_buf.filters();
+    w.len(filters.len());
+    for filter in filters {
+        w.len(filter.primitives.len());
+        for primitive in filter.primitives {
+            w.filter_primitive(primitive);
+        }
+    }
+
+    w.out.extend_from_slice(&cmds.out);
+    w.out
+}
+
+
+pub fn decode(data: &[u8]) -> Result<CmdBuf, DecodeError> {
+    let mut r = Reader { data, pos: 0 };
+
+    if r.bytes(4)? != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+
+    let version = r.u32()?;
+    if version != VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    let mut result = Ok(());
+    let cmd_buf = CmdBuf::new(|cb| {
+        result = decode_into(&mut r, cb);
+    });
+    result?;
+
+    if r.pos != data.len() {
+        return Err(DecodeError::Invalid);
+    }
+
+    Ok(cmd_buf)
+}
+
+fn decode_into<'a>(r: &mut Reader, cb: &mut CmdBufBuilder<'a>) -> Result<(), DecodeError> {
+    let num_paths = r.len(8)?;
+    let mut paths = Vec::with_capacity(num_paths);
+    for _ in 0..num_paths {
+        let num_verbs = r.len(1)?;
+        let mut verbs = Vec::with_capacity(num_verbs);
+        for _ in 0..num_verbs {
+            verbs.push(r.u8()?);
+        }
+
+        let num_points = r.len(8)?;
+        let mut points = Vec::with_capacity(num_points);
+        for _ in 0..num_points {
+            points.push(r.f32x2()?);
+        }
+
+        let mut valid = true;
+        let path = cb.build_path(|pb| {
+            valid = decode_path(&verbs, &points, pb).is_some();
+        });
+        if !valid {
+            return Err(DecodeError::Invalid);
+        }
+        paths.push(path);
+    }
+
+    let num_images = r.len(8)?;
+    let mut images = Vec::with_capacity(num_images);
+    for _ in 0..num_images {
+        let size = [r.u32()?, r.u32()?];
+        let num_pixels = (size[0] as usize).checked_mul(size[1] as usize).ok_or(DecodeError::Invalid)?;
+        if r.remaining() / 4 < num_pixels {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+
+        let mut pixels = Vec::with_capacity(num_pixels);
+        for _ in 0..num_pixels {
+            pixels.push(r.u32()?);
+        }
+        let pixels = cb.build_image_pixels(|buf| {
+            for pixel in &pixels {
+                buf.push(*pixel);
+            }
+        });
+        images.push(cb.push_image(ImageSource { size, pixels }));
+    }
+
+    let num_linear = r.len(1)?;
+    let mut linear_gradients = Vec::with_capacity(num_linear);
+    for _ in 0..num_linear {
+        let p0 = r.f32x2()?;
+        let p1 = r.f32x2()?;
+        let (spread, units, tfx, stops) = r.gradient_common(cb)?;
+        linear_gradients.push(cb.push_linear_gradient(LinearGradient { p0, p1, spread, units, tfx, stops }));
+    }
+
+    let num_radial = r.len(1)?;
+    let mut radial_gradients = Vec::with_capacity(num_radial);
+    for _ in 0..num_radial {
+        let cp = r.f32x2()?;
+        let cr = r.f32()?;
+        let fp = r.f32x2()?;
+        let fr = r.f32()?;
+        let (spread, units, tfx, stops) = r.gradient_common(cb)?;
+        radial_gradients.push(cb.push_radial_gradient(RadialGradient { cp, cr, fp, fr, spread, units, tfx, stops }));
+    }
+
+    let num_conic = r.len(1)?;
+    let mut conic_gradients = Vec::with_capacity(num_conic);
+    for _ in 0..num_conic {
+        let center      = r.f32x2()?;
+        let start_angle = r.f32()?;
+        let angle_range = r.f32()?;
+        let (spread, units, tfx, stops) = r.gradient_common(cb)?;
+        conic_gradients.push(cb.push_conic_gradient(ConicGradient { center, start_angle, angle_range, spread, units, tfx, stops }));
+    }
+
+    let num_filters = r.len(1)?;
+    let mut filters = Vec::with_capacity(num_filters);
+    for _ in 0..num_filters {
+        let num_primitives = r.len(1)?;
+        let mut primitives = Vec::with_capacity(num_primitives);
+        for i in 0..num_primitives {
+            let primitive = r.filter_primitive(cb.alloc())?;
+
+            // `push_filter` asserts this.
+            let mut valid = true;
+            primitive.for_each_input(|input| {
+                if let FilterInput::Result(j) = input {
+                    valid &= (j as usize) < i;
+                }
+            });
+            if !valid {
+                return Err(DecodeError::Invalid);
+            }
+
+            primitives.push(primitive);
+        }
+
+        let primitives = cb.build_filter_primitives(|buf| {
+            for primitive in &primitives {
+                buf.push(*primitive);
+            }
+        });
+        filters.push(cb.push_filter(Filter { primitives }));
+    }
+
+    let resources = Resources { paths, images, linear_gradients, radial_gradients, conic_gradients };
+
+    let num_cmds = r.len(1)?;
+    for _ in 0..num_cmds {
+        let cmd = match r.u8()? {
+            0 => Cmd::FillPath {
+                path:      resources.path(r.u32()?)?,
+                fill_rule: r.fill_rule()?,
+                paint:     r.paint(&resources)?,
+                opacity:   r.f32()?,
+                blend:     r.blend()?,
+            },
+
+            1 => {
+                let path = resources.path(r.u32()?)?;
+                let width = r.f32()?;
+                let cap = match r.u8()? {
+                    0 => LineCap::Butt,
+                    1 => LineCap::Round,
+                    2 => LineCap::Square,
+                    _ => return Err(DecodeError::Invalid),
+                };
+                let join = match r.u8()? {
+                    0 => LineJoin::Miter,
+                    1 => LineJoin::Round,
+                    2 => LineJoin::Bevel,
+                    _ => return Err(DecodeError::Invalid),
+                };
+                let miter_limit = r.f32()?;
+                let dashes = r.f32s(cb.alloc())?;
+                let dash_offset = r.f32()?;
+
+                Cmd::StrokePath {
+                    path,
+                    style:   StrokeStyle { width, cap, join, miter_limit, dashes, dash_offset },
+                    paint:   r.paint(&resources)?,
+                    opacity: r.f32()?,
+                    blend:   r.blend()?,
+                }
+            }
+
+            2 => Cmd::DropShadow {
+                path:        resources.path(r.u32()?)?,
+                offset:      r.f32x2()?,
+                blur_radius: r.f32()?,
+                color:       r.u32()?,
+            },
+
+            3 => Cmd::BlurLayer { blur_radius: r.f32()? },
+
+            4 => Cmd::PushTransform { tfx: r.tfx()? },
+
+            5 => Cmd::PopTransform,
+
+            6 => Cmd::PushClipPath {
+                path:      resources.path(r.u32()?)?,
+                fill_rule: r.fill_rule()?,
+            },
+
+            7 => Cmd::PopClip,
+
+            8 => Cmd::BeginGroup {
+                opacity: r.f32()?,
+                blend:   r.blend()?,
+                filter:  match r.u32()? {
+                    0 => None,
+                    i => Some(*filters.get(i as usize - 1).ok_or(DecodeError::Invalid)?),
+                },
+            },
+
+            9 => Cmd::EndGroup,
+
+            _ => return Err(DecodeError::Invalid),
+        };
+        cb.push(cmd);
+    }
+
+    Ok(())
+}
+
+fn decode_path(verbs: &[u8], points: &[F32x2], pb: &mut crate::path::PathBuilder) -> Option<()> {
+    let mut points = points.iter().copied();
+    let mut next = || points.next();
+
+    let mut in_path = false;
+    for verb in verbs {
+        match *verb {
+            // begin open, begin closed.
+            0 | 1 => {
+                if in_path { return None }
+                pb.move_to(next()?);
+                in_path = true;
+            }
+
+            2 => {
+                if !in_path { return None }
+                pb.line_to(next()?);
+            }
+
+            3 => {
+                if !in_path { return None }
+                pb.quad_to(next()?, next()?);
+            }
+
+            4 => {
+                if !in_path { return None }
+                pb.cubic_to(next()?, next()?, next()?);
+            }
+
+            // end open.
+            5 => {
+                if !in_path { return None }
+                in_path = false;
+            }
+
+            // end closed.
+            6 => {
+                if !in_path { return None }
+                pb.close_path();
+                in_path = false;
+            }
+
+            _ => return None,
+        }
+    }
+
+    if in_path || next().is_some() {
+        return None;
+    }
+    Some(())
+}
+
+
+struct Resources<'a> {
+    paths:            Vec<Path<'a>>,
+    images:           Vec<ImageId>,
+    linear_gradients: Vec<LinearGradientId>,
+    radial_gradients: Vec<RadialGradientId>,
+    conic_gradients:  Vec<ConicGradientId>,
+}
+
+impl<'a> Resources<'a> {
+    fn path(&self, i: u32) -> Result<Path<'a>, DecodeError> {
+        self.paths.get(i as usize).copied().ok_or(DecodeError::Invalid)
+    }
+}
+
+
+const BLEND_MODES: [BlendMode; 28] = {
+    use BlendMode::*;
+    [Clear, Src, Dst, SrcOver, DstOver, SrcIn, DstIn, SrcOut, DstOut, SrcAtop, DstAtop, Xor, Plus,
+     Multiply, Screen, Overlay, Darken, Lighten, ColorDodge, ColorBurn, HardLight, SoftLight, Difference, Exclusion,
+     Hue, Saturation, Color, Luminosity]
+};
+
+
+struct Writer {
+    out: Vec<u8>,
+}
+
+impl Writer {
+    fn new() -> Self {
+        Writer { out: Vec::new() }
+    }
+
+    #[inline(always)]
+    fn u8(&mut self, v: u8) {
+        self.out.push(v);
+    }
+
+    #[inline(always)]
+    fn u32(&mut self, v: u32) {
+        self.out.extend_from_slice(&v.to_le_bytes());
+    }
+
+    #[inline(always)]
+    fn len(&mut self, len: usize) {
+        self.u32(len.try_into().unwrap());
+    }
+
+    #[inline(always)]
+    fn f32(&mut self, v: f32) {
+        self.u32(v.to_bits());
+    }
+
+    #[inline(always)]
+    fn f32x2(&mut self, v: F32x2) {
+        self.f32(v.x());
+        self.f32(v.y());
+    }
+
+    fn f32s(&mut self, vs: &[f32]) {
+        self.len(vs.len());
+        for v in vs {
+            self.f32(*v);
+        }
+    }
+
+    fn tfx(&mut self, tfx: Transform) {
+        for column in tfx.columns {
+            self.f32x2(column);
+        }
+    }
+
+    fn fill_rule(&mut self, fill_rule: FillRule) {
+        self.u8(match fill_rule {
+            FillRule::NonZero => 0,
+            FillRule::EvenOdd => 1,
+        });
+    }
+
+    fn blend(&mut self, blend: BlendMode) {
+        self.u8(blend as u8);
+    }
+
+    fn paint(&mut self, paint: Paint) {
+        match paint {
+            Paint::Solid(color) => {
+                self.u8(0);
+                self.u32(color);
+            }
+
+            Paint::LinearGradient(id) => {
+                self.u8(1);
+                self.u32(id.usize() as u32);
+            }
+
+            Paint::RadialGradient(id) => {
+                self.u8(2);
+                self.u32(id.usize() as u32);
+            }
+
+            Paint::ConicGradient(id) => {
+                self.u8(3);
+                self.u32(id.usize() as u32);
+            }
+
+            Paint::Image { image, tfx, sampling, extend } => {
+                self.u8(4);
+                self.u32(image.usize() as u32);
+                self.tfx(tfx);
+                self.u8(match sampling {
+                    ImageSampling::Nearest  => 0,
+                    ImageSampling::Bilinear => 1,
+                    ImageSampling::Bicubic  => 2,
+                });
+                self.u8(match extend {
+                    ImageExtend::Pad     => 0,
+                    ImageExtend::Repeat  => 1,
+                    ImageExtend::Reflect => 2,
+                });
+            }
+        }
+    }
+
+    fn gradient_common(&mut self, spread: SpreadMethod, units: GradientUnits, tfx: Transform, stops: &[GradientStop]) {
+        self.u8(match spread {
+            SpreadMethod::Pad     => 0,
+            SpreadMethod::Reflect => 1,
+            SpreadMethod::Repeat  => 2,
+        });
+        self.u8(match units {
+            GradientUnits::Absolute => 0,
+            GradientUnits::Relative => 1,
+        });
+        self.tfx(tfx);
+
+        self.len(stops.len());
+        for stop in stops {
+            self.f32(stop.offset);
+            self.u32(stop.color);
+        }
+    }
+
+    fn filter_input(&mut self, input: FilterInput) {
+        // results start at 2.
+        self.u32(match input {
+            FilterInput::Source      => 0,
+            FilterInput::SourceAlpha => 1,
+            FilterInput::Result(i)   => i + 2,
+        });
+    }
+
+    fn filter_primitive(&mut self, primitive: &FilterPrimitive) {
+        match *primitive {
+            FilterPrimitive::ColorMatrix { input, matrix } => {
+                self.u8(0);
+                self.filter_input(input);
+                for v in matrix {
+                    self.f32(v);
+                }
+            }
+
+            FilterPrimitive::ComponentTransfer { input, functions } => {
+                self.u8(1);
+                self.filter_input(input);
+                for function in functions {
+                    match function {
+                        TransferFunction::Identity => {
+                            self.u8(0);
+                        }
+
+                        TransferFunction::Table(values) => {
+                            self.u8(1);
+                            self.f32s(values);
+                        }
+
+                        TransferFunction::Discrete(values) => {
+                            self.u8(2);
+                            self.f32s(values);
+                        }
+
+                        TransferFunction::Linear { slope, intercept } => {
+                            self.u8(3);
+                            self.f32(slope);
+                            self.f32(intercept);
+                        }
+
+                        TransferFunction::Gamma { amplitude, exponent, offset } => {
+                            self.u8(4);
+                            self.f32(amplitude);
+                            self.f32(exponent);
+                            self.f32(offset);
+                        }
+                    }
+                }
+            }
+
+            FilterPrimitive::Composite { input, input2, blend } => {
+                self.u8(2);
+                self.filter_input(input);
+                self.filter_input(input2);
+                self.blend(blend);
+            }
+
+            FilterPrimitive::Arithmetic { input, input2, k } => {
+                self.u8(3);
+                self.filter_input(input);
+                self.filter_input(input2);
+                for v in k {
+                    self.f32(v);
+                }
+            }
+
+            FilterPrimitive::Morphology { input, op, radius } => {
+                self.u8(4);
+                self.filter_input(input);
+                self.u8(match op {
+                    MorphologyOp::Erode  => 0,
+                    MorphologyOp::Dilate => 1,
+                });
+                self.f32x2(radius);
+            }
+
+            FilterPrimitive::Offset { input, offset } => {
+                self.u8(5);
+                self.filter_input(input);
+                self.f32x2(offset);
+            }
+
+            FilterPrimitive::GaussianBlur { input, std_deviation } => {
+                self.u8(6);
+                self.filter_input(input);
+                self.f32(std_deviation);
+            }
+
+            FilterPrimitive::Merge { inputs } => {
+                self.u8(7);
+                self.len(inputs.len());
+                for input in inputs {
+                    self.filter_input(*input);
+                }
+            }
+
+            FilterPrimitive::Flood { color } => {
+                self.u8(8);
+                self.u32(color);
+            }
+
+            FilterPrimitive::Turbulence { base_frequency, num_octaves, seed, kind } => {
+                self.u8(9);
+                self.f32x2(base_frequency);
+                self.u32(num_octaves);
+                self.u32(seed as u32);
+                self.u8(match kind {
+                    TurbulenceKind::FractalNoise => 0,
+                    TurbulenceKind::Turbulence   => 1,
+                });
+            }
+        }
+    }
+}
+
+
+struct Reader<'d> {
+    data: &'d [u8],
+    pos:  usize,
+}
+
+impl<'d> Reader<'d> {
+    #[inline(always)]
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    #[inline(always)]
+    fn bytes(&mut self, n: usize) -> Result<&'d [u8], DecodeError> {
+        if self.remaining() < n {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let bytes = &self.data[self.pos .. self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    #[inline(always)]
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    #[inline(always)]
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// a count of items, that take at least `min_item_size` bytes each.
+    /// - fails early on lengths, that exceed the remaining data.
+    fn len(&mut self, min_item_size: usize) -> Result<usize, DecodeError> {
+        let len = self.u32()? as usize;
+        if len.saturating_mul(min_item_size) > self.remaining() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        Ok(len)
+    }
+
+    #[inline(always)]
+    fn f32(&mut self) -> Result<f32, DecodeError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    #[inline(always)]
+    fn f32x2(&mut self) -> Result<F32x2, DecodeError> {
+        Ok(F32x2::new(self.f32()?, self.f32()?))
+    }
+
+    fn f32s<'a>(&mut self, arena: &'a Arena) -> Result<&'a [f32], DecodeError> {
+        let len = self.len(4)?;
+        let mut vs = sti::vec::Vec::new_in(arena);
+        for _ in 0..len {
+            vs.push(self.f32()?);
+        }
+        Ok(sti::vec::Vec::leak(vs))
+    }
+
+    fn tfx(&mut self) -> Result<Transform, DecodeError> {
+        Ok(Transform { columns: [self.f32x2()?, self.f32x2()?, self.f32x2()?] })
+    }
+
+    fn fill_rule(&mut self) -> Result<FillRule, DecodeError> {
+        match self.u8()? {
+            0 => Ok(FillRule::NonZero),
+            1 => Ok(FillRule::EvenOdd),
+            _ => Err(DecodeError::Invalid),
+        }
+    }
+
+    fn blend(&mut self) -> Result<BlendMode, DecodeError> {
+        BLEND_MODES.get(self.u8()? as usize).copied().ok_or(DecodeError::Invalid)
+    }
+
+    fn paint(&mut self, resources: &Resources) -> Result<Paint, DecodeError> {
+        fn get<T: Copy>(ids: &[T], i: u32) -> Result<T, DecodeError> {
+            ids.get(i as usize).copied().ok_or(DecodeError::Invalid)
+        }
+
+        Ok(match self.u8()? {
+            0 => Paint::Solid(self.u32()?),
+            1 => Paint::LinearGradient(get(&resources.linear_gradients, self.u32()?)?),
+            2 => Paint::RadialGradient(get(&resources.radial_gradients, self.u32()?)?),
+            3 => Paint::ConicGradient(get(&resources.conic_gradients, self.u32()?)?),
+
+            4 => Paint::Image {
+                image: get(&resources.images, self.u32()?)?,
+                tfx:   self.tfx()?,
+                sampling: match self.u8()? {
+                    0 => ImageSampling::Nearest,
+                    1 => ImageSampling::Bilinear,
+                    2 => ImageSampling::Bicubic,
+                    _ => return Err(DecodeError::Invalid),
+                },
+                extend: match self.u8()? {
+                    0 => ImageExtend::Pad,
+                    1 => ImageExtend::Repeat,
+                    2 => ImageExtend::Reflect,
+                    _ => return Err(DecodeError::Invalid),
+                },
+            },
+
+            _ => return Err(DecodeError::Invalid),
+        })
+    }
+
+    fn gradient_common<'a>(&mut self, cb: &mut CmdBufBuilder<'a>)
+        -> Result<(SpreadMethod, GradientUnits, Transform, &'a [GradientStop]), DecodeError>
+    {
+        let spread = match self.u8()? {
+            0 => SpreadMethod::Pad,
+            1 => SpreadMethod::Reflect,
+            2 => SpreadMethod::Repeat,
+            _ => return Err(DecodeError::Invalid),
+        };
+        let units = match self.u8()? {
+            0 => GradientUnits::Absolute,
+            1 => GradientUnits::Relative,
+            _ => return Err(DecodeError::Invalid),
+        };
+        let tfx = self.tfx()?;
+
+        let num_stops = self.len(8)?;
+        let mut stops = Vec::with_capacity(num_stops);
+        for _ in 0..num_stops {
+            stops.push(GradientStop { offset: self.f32()?, color: self.u32()? });
+        }
+        let stops = cb.build_gradient_stops(|buf| {
+            for stop in &stops {
+                buf.push(*stop);
+            }
+        });
+
+        Ok((spread, units, tfx, stops))
+    }
+
+    fn filter_input(&mut self) -> Result<FilterInput, DecodeError> {
+        Ok(match self.u32()? {
+            0 => FilterInput::Source,
+            1 => FilterInput::SourceAlpha,
+            i => FilterInput::Result(i - 2),
+        })
+    }
+
+    fn filter_primitive<'a>(&mut self, arena: &'a Arena) -> Result<FilterPrimitive<'a>, DecodeError> {
+        Ok(match self.u8()? {
+            0 => {
+                let input = self.filter_input()?;
+                let mut matrix = [0.0; 20];
+                for v in &mut matrix {
+                    *v = self.f32()?;
+                }
+                FilterPrimitive::ColorMatrix { input, matrix }
+            }
+
+            1 => {
+                let input = self.filter_input()?;
+                let mut functions = [TransferFunction::Identity; 4];
+                for function in &mut functions {
+                    *function = match self.u8()? {
+                        0 => TransferFunction::Identity,
+                        1 => TransferFunction::Table(self.f32s(arena)?),
+                        2 => TransferFunction::Discrete(self.f32s(arena)?),
+                        3 => TransferFunction::Linear { slope: self.f32()?, intercept: self.f32()? },
+                        4 => TransferFunction::Gamma { amplitude: self.f32()?, exponent: self.f32()?, offset: self.f32()? },
+                        _ => return Err(DecodeError::Invalid),
+                    };
+                }
+                FilterPrimitive::ComponentTransfer { input, functions }
+            }
+
+            2 => FilterPrimitive::Composite {
+                input:  self.filter_input()?,
+                input2: self.filter_input()?,
+                blend:  self.blend()?,
+            },
+
+            3 => FilterPrimitive::Arithmetic {
+                input:  self.filter_input()?,
+                input2: self.filter_input()?,
+                k: [self.f32()?, self.f32()?, self.f32()?, self.f32()?],
+            },
+
+            4 => FilterPrimitive::Morphology {
+                input: self.filter_input()?,
+                op: match self.u8()? {
+                    0 => MorphologyOp::Erode,
+                    1 => MorphologyOp::Dilate,
+                    _ => return Err(DecodeError::Invalid),
+                },
+                radius: self.f32x2()?,
+            },
+
+            5 => FilterPrimitive::Offset {
+                input:  self.filter_input()?,
+                offset: self.f32x2()?,
+            },
+
+            6 => FilterPrimitive::GaussianBlur {
+                input:         self.filter_input()?,
+                std_deviation: self.f32()?,
+            },
+
+            7 => {
+                let len = self.len(4)?;
+                let mut inputs = sti::vec::Vec::new_in(arena);
+                for _ in 0..len {
+                    inputs.push(self.filter_input()?);
+                }
+                FilterPrimitive::Merge { inputs: sti::vec::Vec::leak(inputs) }
+            }
+
+            8 => FilterPrimitive::Flood { color: self.u32()? },
+
+            9 => FilterPrimitive::Turbulence {
+                base_frequency: self.f32x2()?,
+                num_octaves:    self.u32()?,
+                seed:           self.u32()? as i32,
+                kind: match self.u8()? {
+                    0 => TurbulenceKind::FractalNoise,
+                    1 => TurbulenceKind::Turbulence,
+                    _ => return Err(DecodeError::Invalid),
+                },
+            },
+
+            _ => return Err(DecodeError::Invalid),
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let cmd_buf = CmdBuf::new(|cb| {
+            let path = cb.build_path(|pb| {
+                pb.move_to([0.0, 0.0]);
+                pb.quad_to([5.0, 0.0], [10.0, 10.0]);
+                pb.close_path();
+                pb.move_to([20.0, 0.0]);
+                pb.cubic_to([25.0, 5.0], [30.0, 5.0], [35.0, 0.0]);
+            });
+
+            let stops = cb.build_gradient_stops(|stops| {
+                stops.push(GradientStop { offset: 0.0, color: 0xff000000 });
+                stops.push(GradientStop { offset: 1.0, color: 0xffffffff });
+            });
+            let gradient = cb.push_linear_gradient(LinearGradient {
+                p0: F32x2::new(0.0, 0.0),
+                p1: F32x2::new(10.0, 0.0),
+                spread: SpreadMethod::Reflect,
+                units:  GradientUnits::Relative,
+                tfx:    Transform::ID(),
+                stops,
+            });
+
+            let dashes = cb.build_dashes(|dashes| {
+                dashes.push(1.0);
+                dashes.push(2.0);
+            });
+
+            cb.push(Cmd::PushTransform { tfx: Transform::scale1(2.0) });
+            cb.push(Cmd::FillPath { path, fill_rule: FillRule::EvenOdd, paint: Paint::LinearGradient(gradient), opacity: 0.5, blend: BlendMode::Multiply });
+            cb.push(Cmd::StrokePath { path, style: StrokeStyle { dashes, ..StrokeStyle::new(3.0) }, paint: Paint::Solid(0xff00ff00), opacity: 1.0, blend: BlendMode::SrcOver });
+            cb.push(Cmd::PopTransform);
+        });
+
+        let data = encode(&cmd_buf);
+        let decoded = decode(&data).unwrap();
+        assert_eq!(decoded.num_cmds(), 4);
+        assert_eq!(encode(&decoded), data);
+
+        assert_eq!(decode(&data[..data.len() - 1]).err(), Some(DecodeError::UnexpectedEnd));
+        assert_eq!(decode(b"nope").err(), Some(DecodeError::BadMagic));
+    }
+}
+
@@ -1,38 +1,476 @@
 use sti::simd::*;
+use sti::float::F32Ext;
 
 use crate::geometry::*;
 use crate::path::*;
 use crate::rasterizer::ZERO_TOLERANCE_SQ;
 
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct StrokeStyle<'a> {
+    pub width: f32,
+    pub cap:   LineCap,
+    pub join:  LineJoin,
+    /// max ratio of miter length to stroke width.
+    /// miter joins exceeding the limit are drawn as bevel joins.
+    pub miter_limit: f32,
+    /// alternating dash & gap lengths, empty for solid strokes.
+    /// odd length patterns are repeated (like in svg).
+    pub dashes: &'a [f32],
+    /// distance into the dash pattern at the start of each sub path.
+    pub dash_offset: f32,
+}
+
+impl<'a> StrokeStyle<'a> {
+    /// svg's defaults: butt caps, miter joins, miter limit 4, no dashes.
+    #[inline(always)]
+    pub fn new(width: f32) -> StrokeStyle<'a> {
+        StrokeStyle {
+            width,
+            cap:  LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: 4.0,
+            dashes: &[],
+            dash_offset: 0.0,
+        }
+    }
+}
+
+
+impl<'a> StrokeStyle<'a> {
+    /// max distance of the stroke outline from the path.
+    pub fn extent(&self) -> f32 {
+        let mut scale: f32 = 1.0;
+        if self.cap == LineCap::Square {
+            scale = core::f32::consts::SQRT_2;
+        }
+        if self.join == LineJoin::Miter {
+            scale = scale.max(self.miter_limit);
+        }
+        self.width/2.0 * scale
+    }
+}
+
+
 // @temp
-pub fn stroke(path: Path, width: f32) -> PathBuf {
+pub fn stroke(path: Path, style: &StrokeStyle) -> PathBuf {
+    let mut buffers = StrokeBuffers::new();
+    stroke_core(path, style, &mut buffers);
+    return buffers.outline.build();
+}
+
+/// like `stroke`, but reuses the allocations of `buffers`.
+/// - the result is valid until the next use of `buffers`.
+pub fn stroke_with<'b>(path: Path, style: &StrokeStyle, buffers: &'b mut StrokeBuffers) -> Path<'b> {
+    stroke_core(path, style, buffers);
+    return buffers.stroke.store(&mut buffers.outline);
+}
+
+fn stroke_core(path: Path, style: &StrokeStyle, b: &mut StrokeBuffers) {
     //spall::trace_scope!("rug::stroke");
-    return Stroker::stroke(path, width/2.0, width/2.0);
+    if style.dashes.len() > 0 {
+        if dash_core(path, style.dashes, style.dash_offset, &mut b.dashes, &mut b.first_dash, &mut b.dots) {
+            let dashed = b.dashed.store(&mut b.dashes);
+            Stroker::stroke(dashed, &b.dots, style.width/2.0, style.width/2.0, style, &mut b.outline, &mut b.left, &mut b.right);
+            return;
+        }
+    }
+    Stroker::stroke(path, &[], style.width/2.0, style.width/2.0, style, &mut b.outline, &mut b.left, &mut b.right);
+}
+
+
+/// the allocations of `stroke_with`.
+pub struct StrokeBuffers {
+    dashes:     PathBuilder,
+    first_dash: Vec<Segment>,
+    dots:       Vec<(F32x2, F32x2)>,
+    dashed:     PathStorage,
+
+    outline: PathBuilder,
+    left:    RawPathBuilder,
+    right:   RawPathBuilder,
+    stroke:  PathStorage,
+}
+
+impl StrokeBuffers {
+    pub fn new() -> Self {
+        StrokeBuffers {
+            dashes:     PathBuilder::new(),
+            first_dash: Vec::new(),
+            dots:       Vec::new(),
+            dashed:     PathStorage::new(),
+            outline: PathBuilder::new(),
+            left:    RawPathBuilder::new(),
+            right:   RawPathBuilder::new(),
+            stroke:  PathStorage::new(),
+        }
+    }
+
+    /// the number of bytes allocated.
+    pub fn memory(&self) -> usize {
+        self.dashes.memory()
+        + self.first_dash.capacity() * core::mem::size_of::<Segment>()
+        + self.dots.capacity() * core::mem::size_of::<(F32x2, F32x2)>()
+        + self.dashed.memory()
+        + self.outline.memory()
+        + self.left.memory()
+        + self.right.memory()
+        + self.stroke.memory()
+    }
+}
+
+
+/// cuts the sub paths of `path` into open dashes.
+/// - the pattern restarts at each sub path.
+/// - for closed sub paths, the last and first dash are joined,
+///   if the pattern is "on" at the start point.
+/// - returns `None` for invalid patterns (negative lengths, or zero total length).
+///   svg renders those as solid strokes.
+pub fn dash(path: Path, dashes: &[f32], offset: f32) -> Option<PathBuf> {
+    let mut pb = PathBuilder::new();
+    if !dash_core(path, dashes, offset, &mut pb, &mut Vec::new(), &mut Vec::new()) {
+        return None;
+    }
+    return Some(pb.build());
+}
+
+/// dashes `path` into `pb`, see `dash`.
+/// - zero length dashes are also added to `dots` as `(point, tangent)`,
+///   as only their caps are visible.
+/// - returns `false` for invalid patterns.
+fn dash_core(path: Path, dashes: &[f32], offset: f32, pb: &mut PathBuilder, first_dash: &mut Vec<Segment>, dots: &mut Vec<(F32x2, F32x2)>) -> bool {
+    //spall::trace_scope!("rug::dash");
+
+    if dashes.iter().any(|d| !(*d >= 0.0)) {
+        return false;
+    }
+
+    let total: f32 = dashes.iter().sum();
+    if !(total > 0.0) {
+        return false;
+    }
+
+    pb.clear();
+    first_dash.clear();
+    dots.clear();
+
+    // odd length patterns are repeated.
+    let len    = if dashes.len() % 2 == 1 { 2*dashes.len() } else { dashes.len() };
+    let period = if dashes.len() % 2 == 1 { 2.0*total      } else { total        };
+
+    let mut d = Dasher {
+        dashes, len,
+        start_index: 0,
+        start_rem:   0.0,
+        index: 0,
+        rem:   0.0,
+        in_dash:   false,
+        buffering: false,
+        first_dash,
+        first_dot: None,
+        dots,
+        pb,
+    };
+
+    // find the initial dash.
+    let mut phase = offset % period;
+    if phase < 0.0 {
+        phase += period;
+    }
+    while phase >= d.dash_len(d.start_index) {
+        phase -= d.dash_len(d.start_index);
+        d.start_index = (d.start_index + 1) % len;
+    }
+    d.start_rem = d.dash_len(d.start_index) - phase;
+
+    for e in path.iter() {
+        match e {
+            IterEvent::Begin(_, closed) => d.begin_sub_path(closed),
+
+            IterEvent::Line (line)  => d.segment(Segment::Line(line)),
+            IterEvent::Quad (quad)  => d.segment(Segment::Quad(quad)),
+            IterEvent::Cubic(cubic) => d.segment(Segment::Cubic(cubic)),
+
+            IterEvent::End(_, closed) => d.end_sub_path(closed),
+        }
+    }
+
+    return true;
+}
+
+
+struct Dasher<'d> {
+    dashes: &'d [f32],
+    len:    usize,
+    start_index: usize,
+    start_rem:   f32,
+
+    index: usize,
+    rem:   f32,
+
+    // whether `pb` has an open dash, we can append to.
+    in_dash: bool,
+
+    // the first dash of a closed sub path is buffered,
+    // so it can be appended to the last dash.
+    buffering:  bool,
+    first_dash: &'d mut Vec<Segment>,
+    // the first dash has zero length.
+    first_dot:  Option<(F32x2, F32x2)>,
+
+    dots: &'d mut Vec<(F32x2, F32x2)>,
+    pb:   &'d mut PathBuilder,
+}
+
+impl<'d> Dasher<'d> {
+    #[inline(always)]
+    fn dash_len(&self, index: usize) -> f32 {
+        self.dashes[index % self.dashes.len()]
+    }
+
+    #[inline(always)]
+    fn is_on(&self) -> bool {
+        self.index % 2 == 0
+    }
+
+
+    fn begin_sub_path(&mut self, closed: bool) {
+        self.index = self.start_index;
+        self.rem   = self.start_rem;
+        self.in_dash   = false;
+        self.buffering = closed && self.is_on();
+        self.first_dash.clear();
+        self.first_dot = None;
+    }
+
+    fn segment(&mut self, segment: Segment) {
+        let lengths = segment.lengths();
+        let length  = lengths[DASH_LENGTH_STEPS];
+
+        let mut t0  = 0.0;
+        let mut pos = 0.0;
+        while length - pos > self.rem {
+            let dash_len = self.rem;
+            pos += self.rem;
+
+            let t1 = Segment::t_at(&lengths, pos);
+            if self.is_on() {
+                if dash_len == 0.0 && !self.in_dash {
+                    let dot = (segment.eval(t1), segment.tangent(t1));
+                    if self.buffering { self.first_dot = Some(dot) }
+                    else { self.dots.push(dot) }
+                }
+                self.emit(segment.sub(t0, t1));
+                self.in_dash   = false;
+                self.buffering = false;
+            }
+            t0 = t1;
+
+            self.index = (self.index + 1) % self.len;
+            self.rem   = self.dash_len(self.index);
+        }
+        self.rem -= length - pos;
+
+        if self.is_on() && t0 < 1.0 {
+            self.emit(segment.sub(t0, 1.0));
+        }
+    }
+
+    fn emit(&mut self, segment: Segment) {
+        if self.buffering {
+            self.first_dash.push(segment);
+            return;
+        }
+
+        if !self.in_dash {
+            self.pb.move_to(segment.p0());
+            self.in_dash = true;
+        }
+        segment.append(self.pb);
+    }
+
+    fn end_sub_path(&mut self, closed: bool) {
+        if closed && self.buffering {
+            // the pattern never turned off.
+            if let Some(first) = self.first_dash.first() {
+                self.pb.move_to(first.p0());
+                for segment in self.first_dash.iter() {
+                    segment.append(self.pb);
+                }
+                self.pb.close_path();
+            }
+        }
+        else if let Some(first) = self.first_dash.first() {
+            // otherwise, the first dash continues the last one.
+            if !self.in_dash {
+                self.pb.move_to(first.p0());
+                self.dots.extend(self.first_dot);
+            }
+            for segment in self.first_dash.iter() {
+                segment.append(self.pb);
+            }
+        }
+
+        self.in_dash   = false;
+        self.buffering = false;
+        self.first_dash.clear();
+        self.first_dot = None;
+    }
+}
+
+
+const DASH_LENGTH_STEPS: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+enum Segment {
+    Line (Line),
+    Quad (Quad),
+    Cubic(Cubic),
+}
+
+impl Segment {
+    #[inline(always)]
+    fn p0(self) -> F32x2 {
+        match self {
+            Segment::Line (l) => l.p0,
+            Segment::Quad (q) => q.p0,
+            Segment::Cubic(c) => c.p0,
+        }
+    }
+
+    #[inline(always)]
+    fn eval(self, t: f32) -> F32x2 {
+        match self {
+            Segment::Line (l) => l.p0.lerp(l.p1, t),
+            Segment::Quad (q) => q.eval(t),
+            Segment::Cubic(c) => c.eval(t),
+        }
+    }
+
+    /// the (approximate) unit direction at `t`.
+    fn tangent(self, t: f32) -> F32x2 {
+        let h = 1.0 / DASH_LENGTH_STEPS as f32;
+        let d = self.eval((t + h).min(1.0)) - self.eval((t - h).max(0.0));
+        if d.length_sq() > 0.0 { d.normalized() } else { F32x2::new(1.0, 0.0) }
+    }
+
+    fn split(self, t: f32) -> (Segment, Segment) {
+        match self {
+            Segment::Line(l) => {
+                let m = l.p0.lerp(l.p1, t);
+                (Segment::Line(line(l.p0, m)), Segment::Line(line(m, l.p1)))
+            }
+            Segment::Quad(q) => {
+                let (a, b) = q.split(t);
+                (Segment::Quad(a), Segment::Quad(b))
+            }
+            Segment::Cubic(c) => {
+                let (a, b) = c.split(t);
+                (Segment::Cubic(a), Segment::Cubic(b))
+            }
+        }
+    }
+
+    /// the part of the segment between `t0` and `t1`.
+    fn sub(self, t0: f32, t1: f32) -> Segment {
+        let s = if t0 > 0.0 { self.split(t0).1 } else { self };
+        if t1 < 1.0 { s.split((t1 - t0) / (1.0 - t0)).0 } else { s }
+    }
+
+    fn append(self, pb: &mut PathBuilder) {
+        match self {
+            Segment::Line (l) => pb.line_to(l.p1),
+            Segment::Quad (q) => pb.quad_to(q.p1, q.p2),
+            Segment::Cubic(c) => pb.cubic_to(c.p1, c.p2, c.p3),
+        }
+    }
+
+    /// arc lengths at uniformly spaced parameters (approximated by a polyline).
+    fn lengths(self) -> [f32; DASH_LENGTH_STEPS + 1] {
+        let mut result = [0.0; DASH_LENGTH_STEPS + 1];
+
+        let mut prev = self.p0();
+        for i in 1..=DASH_LENGTH_STEPS {
+            let p = self.eval(i as f32 / DASH_LENGTH_STEPS as f32);
+            result[i] = result[i - 1] + (p - prev).length();
+            prev = p;
+        }
+        result
+    }
+
+    /// the (approximate) parameter at arc length `s`.
+    fn t_at(lengths: &[f32; DASH_LENGTH_STEPS + 1], s: f32) -> f32 {
+        let mut i = 0;
+        while i + 1 < DASH_LENGTH_STEPS && lengths[i + 1] < s {
+            i += 1;
+        }
+
+        let l0 = lengths[i];
+        let l1 = lengths[i + 1];
+        let f = (s - l0).safe_div(l1 - l0, 0.0).clamp(0.0, 1.0);
+        ((i as f32 + f) / DASH_LENGTH_STEPS as f32).clamp(0.0, 1.0)
+    }
 }
 
 
-struct Stroker {
+// joins between (nearly) collinear segments are skipped.
+const JOIN_TOLERANCE: f32 = 0.0001;
+
+struct Stroker<'s> {
     left:   f32,
     right:  f32,
     tol_sq:  f32,
     max_rec: u32,
 
-    pb:  PathBuilder,
-    pbl: RawPathBuilder,
-    pbr: RawPathBuilder,
+    cap:  LineCap,
+    join: LineJoin,
+    miter_limit: f32,
+
+    // (point, normal) at the start/end of the current sub path.
+    first: Option<(F32x2, F32x2)>,
+    last:  Option<(F32x2, F32x2)>,
+
+    pb:  &'s mut PathBuilder,
+    pbl: &'s mut RawPathBuilder,
+    pbr: &'s mut RawPathBuilder,
 }
 
-impl Stroker {
-    fn stroke(path: Path, left: f32, right: f32) -> PathBuf {
+impl<'s> Stroker<'s> {
+    /// strokes `path` into `pb`.
+    /// - `dots` are the `(point, tangent)` of zero length dashes, see `dash_core`.
+    /// - `pbl` and `pbr` are scratch buffers.
+    fn stroke(path: Path, dots: &[(F32x2, F32x2)], left: f32, right: f32, style: &StrokeStyle,
+        pb: &'s mut PathBuilder, pbl: &'s mut RawPathBuilder, pbr: &'s mut RawPathBuilder)
+    {
+        pb.clear();
+        pbl.clear();
+        pbr.clear();
+
         let mut s = Stroker {
             left,
             right: -right,
             tol_sq:  0.05 * 0.05,
             max_rec: 16,
-            pb:  PathBuilder::new(),
-            pbl: RawPathBuilder::new(),
-            pbr: RawPathBuilder::new(),
+            cap:  style.cap,
+            join: style.join,
+            miter_limit: style.miter_limit,
+            first: None,
+            last:  None,
+            pb, pbl, pbr,
         };
 
         let mut iter = path.iter();
@@ -45,20 +483,143 @@ impl Stroker {
                 IterEvent::Cubic(cubic) => s.cubic(cubic),
 
                 IterEvent::End(_, closed) => {
+                    if closed {
+                        if let (Some((p, n1)), Some((_, n0))) = (s.first, s.last) {
+                            s.add_join(p, n0, n1);
+                        }
+                    }
                     s.build_stroke(closed);
                 }
             }
         }
+
+        for &(p, tangent) in dots {
+            s.dot(p, tangent);
+        }
         debug_assert!(s.pbl.verbs .is_empty());
         debug_assert!(s.pbl.points.is_empty());
         debug_assert!(s.pbr.verbs .is_empty());
         debug_assert!(s.pbr.points.is_empty());
+    }
+
+
+    fn begin_segment(&mut self, p0: F32x2, n0: F32x2) {
+        if let Some((_, n)) = self.last {
+            self.add_join(p0, n, n0);
+        }
+        else {
+            self.first = Some((p0, n0));
+        }
+    }
+
+    #[inline(always)]
+    fn end_segment(&mut self, p1: F32x2, n1: F32x2) {
+        self.last = Some((p1, n1));
+    }
+
+
+    /// joins are only added on the outer side.
+    /// the inner side is connected with a bevel in `build_stroke`.
+    fn add_join(&mut self, p: F32x2, n0: F32x2, n1: F32x2) {
+        let dot   = n0.dot(n1);
+        let cross = n0.x()*n1.y() - n0.y()*n1.x();
+
+        if dot >= 1.0 - JOIN_TOLERANCE {
+            return;
+        }
+
+        // the outer side is the one the path turns away from.
+        let left_outer = cross <= 0.0;
+
+        let (d, pb) =
+            if left_outer { (self.left,  &mut *self.pbl) }
+            else          { (self.right, &mut *self.pbr) };
+
+        let a = p + d*n0;
+        let b = p + d*n1;
+
+        match self.join {
+            LineJoin::Bevel => {}
+
+            LineJoin::Miter => {
+                // miter length / width = 1/cos(θ/2) = sqrt(2/(1 + cos θ)).
+                if 2.0 <= self.miter_limit*self.miter_limit*(1.0 + dot) {
+                    let m = p + d*((n0 + n1) / (1.0 + dot));
+
+                    pb.verbs.push(Verb::Line);
+                    pb.points.push(a);
+                    pb.points.push(m);
+
+                    pb.verbs.push(Verb::Line);
+                    pb.points.push(m);
+                    pb.points.push(b);
+                }
+            }
+
+            LineJoin::Round => {
+                let angle = cross.abs().atan2(dot);
+                let angle = if left_outer { -angle } else { angle };
+
+                arc(p, d.abs(), (a - p) / d.abs(), angle, b, &mut |q| {
+                    pb.verbs.push(Verb::Quad);
+                    pb.points.push(q.p0);
+                    pb.points.push(q.p1);
+                    pb.points.push(q.p2);
+                });
+            }
+        }
+    }
+
+    /// adds the cap from the left to the right side of `p`.
+    /// (or right to left, if `backward`.)
+    /// returns the last point of the cap.
+    fn add_cap(&mut self, p: F32x2, normal: F32x2, backward: bool) -> F32x2 {
+        let half = (self.left - self.right) / 2.0;
+        let tangent = if backward { -normal.rotated_cw() } else { normal.rotated_cw() };
+
+        let (d0, d1) = if backward { (self.right, self.left) } else { (self.left, self.right) };
+        let p0 = p + d0*normal;
+        let p1 = p + d1*normal;
+
+        match self.cap {
+            LineCap::Butt => p0,
+
+            LineCap::Square => {
+                self.pb.line_to(p0 + half*tangent);
+                self.pb.line_to(p1 + half*tangent);
+                p1 + half*tangent
+            }
+
+            LineCap::Round => {
+                let center = p + ((self.left + self.right) / 2.0)*normal;
+                let pb = &mut *self.pb;
+                arc(center, half, (p0 - center) / half, -core::f32::consts::PI, p1, &mut |q| {
+                    pb.quad_to(q.p1, q.p2);
+                });
+                p1
+            }
+        }
+    }
+
+
+    /// the caps of a zero length sub path.
+    /// - butt caps draw nothing, like in svg.
+    fn dot(&mut self, p: F32x2, tangent: F32x2) {
+        if self.cap == LineCap::Butt {
+            return;
+        }
 
-        return s.pb.build();
+        let normal = tangent.rotated_acw();
+        self.pb.move_to(p + self.left*normal);
+        self.add_cap(p, normal, false);
+        self.add_cap(p, normal, true);
+        self.pb.close_path();
     }
 
 
     fn push_line(&mut self, line: Line, normal: F32x2) {
+        self.begin_segment(line.p0, normal);
+
         let l = line.offset(normal, self.left);
         self.pbl.verbs.push(Verb::Line);
         self.pbl.points.push(l.p0);
@@ -68,6 +629,8 @@ impl Stroker {
         self.pbr.verbs.push(Verb::Line);
         self.pbr.points.push(r.p0);
         self.pbr.points.push(r.p1);
+
+        self.end_segment(line.p1, normal);
     }
 
     fn line(&mut self, line: Line) {
@@ -87,6 +650,8 @@ impl Stroker {
 
         match quad.normals(ZERO_TOLERANCE_SQ) {
             (Some(n0), Some(n1)) => {
+                self.begin_segment(p0, n0);
+
                 quad.offset(n0, n1, self.left, tol_sq, max_rec, &mut |q, _| {
                     self.pbl.verbs.push(Verb::Quad);
                     self.pbl.points.push(q.p0);
@@ -100,6 +665,8 @@ impl Stroker {
                     self.pbr.points.push(q.p1);
                     self.pbr.points.push(q.p2);
                 });
+
+                self.end_segment(p2, n1);
             },
 
             (Some(n0), None) => {
@@ -198,6 +765,9 @@ impl Stroker {
             self.pb.close_path();
             prev = None;
         }
+        else if let Some((p, n)) = self.last {
+            prev = Some(self.add_cap(p, n, false));
+        }
 
 
         let points = &*self.pbr.points;
@@ -256,12 +826,20 @@ impl Stroker {
         }
         debug_assert_eq!(p, 0);
 
+        if !closed {
+            if let Some((p, n)) = self.first {
+                self.add_cap(p, n, true);
+            }
+        }
+
         if self.pbl.verbs.len() > 0 || self.pbr.verbs.len() > 0 {
             self.pb.close_path();
         }
 
         self.pbl.clear();
         self.pbr.clear();
+        self.first = None;
+        self.last  = None;
     }
 
 
@@ -299,3 +877,260 @@ impl Stroker {
 }
 
 
+
+
+/// approximates the arc of radius `r` around `center`,
+/// starting in (unit) direction `u0`, by quads.
+/// the last quad ends exactly at `end`.
+fn arc<F: FnMut(Quad)>(center: F32x2, r: f32, u0: F32x2, angle: f32, end: F32x2, f: &mut F) {
+    let segments = (angle.abs() / core::f32::consts::FRAC_PI_4).ceil().max(1.0) as u32;
+
+    let step = angle / segments as f32;
+    let (sin, cos) = step.sin_cos();
+
+    let mut u = u0;
+    let mut p = center + r*u;
+    for i in 0..segments {
+        let un = F32x2::new(cos*u.x() - sin*u.y(), sin*u.x() + cos*u.y());
+
+        let p1 = center + r*((u + un) / (1.0 + cos));
+        let p2 = if i + 1 == segments { end } else { center + r*un };
+        f(quad(p, p1, p2));
+
+        u = un;
+        p = p2;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sub_paths(path: Path) -> std::vec::Vec<(F32x2, F32x2, bool)> {
+        let mut result = std::vec::Vec::new();
+        let mut begin = F32x2::ZERO();
+        for e in path.iter() {
+            match e {
+                IterEvent::Begin(p0, _)     => begin = p0,
+                IterEvent::End(p1, closed)  => result.push((begin, p1, closed)),
+                _ => (),
+            }
+        }
+        result
+    }
+
+    fn approx_eq(a: F32x2, b: [f32; 2]) -> bool {
+        (a - F32x2::from_array(b)).length() < 0.001
+    }
+
+    fn polyline(points: &[[f32; 2]]) -> PathBuf {
+        let mut pb = PathBuilder::new();
+        pb.move_to(points[0]);
+        for p in &points[1..] {
+            pb.line_to(*p);
+        }
+        pb.build()
+    }
+
+    fn has_point(path: Path, p: [f32; 2]) -> bool {
+        path.points().iter().any(|q| approx_eq(*q, p))
+    }
+
+    #[test]
+    fn caps() {
+        let path = polyline(&[[0.0, 0.0], [10.0, 0.0]]);
+        let mut style = StrokeStyle::new(2.0);
+
+        style.cap = LineCap::Butt;
+        let outline = stroke(path.path(), &style);
+        let aabb = outline.path().aabb();
+        assert!(approx_eq(aabb.min, [0.0, -1.0]) && approx_eq(aabb.max, [10.0, 1.0]));
+
+        style.cap = LineCap::Square;
+        let outline = stroke(path.path(), &style);
+        let aabb = outline.path().aabb();
+        assert!(approx_eq(aabb.min, [-1.0, -1.0]) && approx_eq(aabb.max, [11.0, 1.0]));
+        for corner in [[-1.0, -1.0], [-1.0, 1.0], [11.0, -1.0], [11.0, 1.0]] {
+            assert!(has_point(outline.path(), corner));
+        }
+
+        // reaches as far as square caps, but stays near the end points.
+        style.cap = LineCap::Round;
+        let outline = stroke(path.path(), &style);
+        let aabb = outline.path().aabb();
+        assert!(approx_eq(aabb.min, [-1.0, -1.0]) && approx_eq(aabb.max, [11.0, 1.0]));
+        for p in outline.path().points() {
+            let end = if p.x() < 0.0 { F32x2::new(0.0, 0.0) } else if p.x() > 10.0 { F32x2::new(10.0, 0.0) } else { continue };
+            assert!((*p - end).length() < 1.1);
+        }
+
+        assert_eq!(StrokeStyle { cap: LineCap::Butt,   join: LineJoin::Bevel, ..style }.extent(), 1.0);
+        assert_eq!(StrokeStyle { cap: LineCap::Round,  join: LineJoin::Round, ..style }.extent(), 1.0);
+        assert_eq!(StrokeStyle { cap: LineCap::Square, join: LineJoin::Bevel, ..style }.extent(), core::f32::consts::SQRT_2);
+    }
+
+    #[test]
+    fn miter_limit() {
+        let right_angle = polyline(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]);
+
+        let mut style = StrokeStyle::new(2.0);
+        assert!(has_point(stroke(right_angle.path(), &style).path(), [11.0, -1.0]));
+
+        style.join = LineJoin::Bevel;
+        assert!(!has_point(stroke(right_angle.path(), &style).path(), [11.0, -1.0]));
+
+        // the miter is ~20 times the width.
+        let sharp = polyline(&[[0.0, 0.0], [10.0, 0.0], [0.0, 1.0]]);
+
+        let mut style = StrokeStyle::new(2.0);
+        assert!(stroke(sharp.path(), &style).path().aabb().max.x() < 11.5);
+
+        style.miter_limit = 100.0;
+        assert!(stroke(sharp.path(), &style).path().aabb().max.x() > 25.0);
+    }
+
+    #[test]
+    fn round_join() {
+        let path = polyline(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]);
+
+        let mut style = StrokeStyle::new(2.0);
+        style.join = LineJoin::Round;
+        let outline = stroke(path.path(), &style);
+
+        // the outer side bulges around the corner.
+        let corner = F32x2::new(10.0, 0.0);
+        let outer: std::vec::Vec<F32x2> = outline.path().points().iter().copied()
+            .filter(|p| p.x() > 10.001 && p.y() < -0.001)
+            .collect();
+        assert!(!outer.is_empty());
+        for p in &outer {
+            let d = (*p - corner).length();
+            assert!(d > 0.99 && d < 1.1);
+        }
+
+        // the inner side is a bevel.
+        assert!(has_point(outline.path(), [10.0, 1.0]) && has_point(outline.path(), [9.0, 0.0]));
+        assert!(outline.path().points().iter().all(|p|
+            !(p.x() > 9.001 && p.x() < 9.999 && p.y() > 0.001 && p.y() < 0.999)));
+    }
+
+    #[test]
+    fn dash_line() {
+        let path = {
+            let mut pb = PathBuilder::new();
+            pb.move_to([0.0, 0.0]);
+            pb.line_to([10.0, 0.0]);
+            pb.build()
+        };
+
+        let dashed = dash(path.path(), &[2.0, 3.0], 0.0).unwrap();
+        let subs = sub_paths(dashed.path());
+        assert_eq!(subs.len(), 2);
+        assert!(approx_eq(subs[0].0, [0.0, 0.0]) && approx_eq(subs[0].1, [2.0, 0.0]));
+        assert!(approx_eq(subs[1].0, [5.0, 0.0]) && approx_eq(subs[1].1, [7.0, 0.0]));
+        assert!(!subs[0].2 && !subs[1].2);
+
+        assert!(dash(path.path(), &[0.0, 0.0], 0.0).is_none());
+        assert!(dash(path.path(), &[1.0, -1.0], 0.0).is_none());
+    }
+
+    #[test]
+    fn dash_closed() {
+        let path = {
+            let mut pb = PathBuilder::new();
+            pb.move_to([0.0, 0.0]);
+            pb.line_to([10.0, 0.0]);
+            pb.line_to([10.0, 10.0]);
+            pb.line_to([0.0, 10.0]);
+            pb.close_path();
+            pb.build()
+        };
+
+        // on: 0-3, 7-13, 17-23, 27-33, 37-40.
+        // the last dash continues into the first one.
+        let dashed = dash(path.path(), &[6.0, 4.0], 3.0).unwrap();
+        let subs = sub_paths(dashed.path());
+        assert_eq!(subs.len(), 4);
+        assert!(approx_eq(subs[0].0, [7.0, 0.0]));
+        assert!(approx_eq(subs[3].0, [0.0, 3.0]) && approx_eq(subs[3].1, [3.0, 0.0]));
+        assert!(subs.iter().all(|s| !s.2));
+
+        // the pattern never turns off.
+        let dashed = dash(path.path(), &[100.0, 1.0], 0.0).unwrap();
+        let subs = sub_paths(dashed.path());
+        assert_eq!(subs.len(), 1);
+        assert!(subs[0].2);
+    }
+
+    #[test]
+    fn dash_dots() {
+        let line_path = |p1: [f32; 2]| {
+            let mut pb = PathBuilder::new();
+            pb.move_to([0.0, 0.0]);
+            pb.line_to(p1);
+            pb.build()
+        };
+
+        let mut style = StrokeStyle::new(2.0);
+        style.dashes = &[0.0, 4.0];
+
+        // butt caps draw nothing.
+        let path = line_path([10.0, 0.0]);
+        assert!(stroke(path.path(), &style).path().verbs().is_empty());
+
+        // dots at 0, 4 and 8.
+        style.cap = LineCap::Square;
+        let outline = stroke(path.path(), &style);
+        let subs = sub_paths(outline.path());
+        assert_eq!(subs.len(), 3);
+        assert!(subs.iter().all(|s| s.2));
+        let aabb = outline.path().aabb();
+        assert!(approx_eq(aabb.min, [-1.0, -1.0]) && approx_eq(aabb.max, [9.0, 1.0]));
+
+        // the caps follow the direction of the path.
+        style.dashes = &[0.0, 20.0];
+        let outline = stroke(line_path([6.0, 8.0]).path(), &style);
+        assert_eq!(sub_paths(outline.path()).len(), 1);
+        let aabb = outline.path().aabb();
+        assert!(approx_eq(aabb.min, [-1.4, -1.4]) && approx_eq(aabb.max, [1.4, 1.4]));
+
+        // the dot at the start of a closed path isn't doubled.
+        let square = {
+            let mut pb = PathBuilder::new();
+            pb.move_to([0.0, 0.0]);
+            pb.line_to([10.0, 0.0]);
+            pb.line_to([10.0, 10.0]);
+            pb.line_to([0.0, 10.0]);
+            pb.close_path();
+            pb.build()
+        };
+        style.dashes = &[0.0, 4.0];
+        style.cap = LineCap::Round;
+        let outline = stroke(square.path(), &style);
+        assert_eq!(sub_paths(outline.path()).len(), 10);
+    }
+
+    #[test]
+    fn stroke_with_reuse() {
+        let path = {
+            let mut pb = PathBuilder::new();
+            pb.move_to([0.0, 0.0]);
+            pb.quad_to([5.0, 10.0], [10.0, 0.0]);
+            pb.line_to([20.0, 5.0]);
+            pb.build()
+        };
+
+        let mut dashed = StrokeStyle::new(2.0);
+        dashed.dashes = &[3.0, 1.0];
+
+        let mut buffers = StrokeBuffers::new();
+        for style in [StrokeStyle::new(4.0), dashed, StrokeStyle::new(1.0)] {
+            let expected = stroke(path.path(), &style);
+            let result = stroke_with(path.path(), &style, &mut buffers);
+            assert_eq!(result.verbs(),  expected.path().verbs());
+            assert_eq!(result.points(), expected.path().points());
+            assert_eq!(result.aabb(),   expected.path().aabb());
+        }
+    }
+}
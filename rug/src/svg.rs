@@ -0,0 +1,680 @@
+use sti::simd::*;
+
+use core::fmt::Write;
+
+use crate::geometry::{Transform, Rect};
+use crate::path::{Path, Verb};
+use crate::stroke::{StrokeStyle, LineCap, LineJoin};
+use crate::rasterizer::FillRule;
+use crate::blend::BlendMode;
+use crate::filter::*;
+use crate::cmd::*;
+use crate::renderer::RenderParams;
+
+
+/*  svg export.
+
+    transforms, clips and groups become nested `<g>` elements,
+    so pushes and pops must be properly nested.
+
+    not representable in svg:
+        - `BlurLayer`. emitted as a comment.
+        - conic gradients. filled with the color of their first stop.
+        - porter-duff operators other than `SrcOver`, outside of filters.
+          drawn with `SrcOver`.
+        - the `Pad` & `Reflect` extend modes of image paints.
+          images are always repeated.
+*/
+
+
+/// converts `cmd_buf` into an svg document of `size` pixels.
+pub fn to_svg(cmd_buf: &CmdBuf, params: &RenderParams, size: [u32; 2]) -> String {
+    let mut w = SvgWriter {
+        cmd_buf,
+        size,
+        defs: String::new(),
+        body: String::new(),
+        depth: 1,
+        next_id: 0,
+        tfx: params.tfx,
+        tfx_stack: Vec::new(),
+    };
+
+    for (i, image) in cmd_buf.images().iter().enumerate() {
+        w.image_def(i, image);
+    }
+
+    let clear = params.clear;
+    if !params.load && clear >> 24 != 0 {
+        let _ = writeln!(w.body, "  <rect width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"{}\"/>",
+            size[0], size[1], rgb(clear), alpha(clear));
+    }
+
+    // the root transform.
+    w.open_g(&format!("transform=\"{}\"", matrix(params.tfx)));
+
+    for i in 0..cmd_buf.num_cmds() {
+        w.cmd(cmd_buf.cmd(i));
+    }
+
+    // close unbalanced pushes.
+    while w.depth > 1 {
+        w.close_g();
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        size[0], size[1], size[0], size[1]);
+    if !w.defs.is_empty() {
+        out.push_str("  <defs>\n");
+        out.push_str(&w.defs);
+        out.push_str("  </defs>\n");
+    }
+    out.push_str(&w.body);
+    out.push_str("</svg>\n");
+    out
+}
+
+
+struct SvgWriter<'c> {
+    cmd_buf: &'c CmdBuf,
+    size:    [u32; 2],
+
+    defs: String,
+    body: String,
+    depth: usize,
+    next_id: u32,
+
+    tfx: Transform,
+    tfx_stack: Vec<Transform>,
+}
+
+impl<'c> SvgWriter<'c> {
+    fn new_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}{}", prefix, self.next_id)
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.body.push_str("  ");
+        }
+    }
+
+    fn open_g(&mut self, attributes: &str) {
+        self.indent();
+        let _ = writeln!(self.body, "<g {}>", attributes);
+        self.depth += 1;
+    }
+
+    fn close_g(&mut self) {
+        self.depth -= 1;
+        self.indent();
+        self.body.push_str("</g>\n");
+    }
+
+    fn cmd(&mut self, cmd: &Cmd) {
+        match *cmd {
+            Cmd::FillPath { path, fill_rule, paint, opacity, blend } => {
+                let paint = self.paint(paint, opacity);
+                self.indent();
+                let _ = writeln!(self.body, "<path d=\"{}\" fill=\"{}\" fill-opacity=\"{}\"{}{}/>",
+                    path_data(path), paint.0, paint.1,
+                    fill_rule_attribute("fill-rule", fill_rule),
+                    blend_style(blend));
+            }
+
+            Cmd::StrokePath { path, style, paint, opacity, blend } => {
+                let paint = self.paint(paint, opacity);
+                self.indent();
+                let _ = writeln!(self.body, "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-opacity=\"{}\"{}{}/>",
+                    path_data(path), paint.0, paint.1,
+                    stroke_attributes(&style),
+                    blend_style(blend));
+            }
+
+            Cmd::DropShadow { path, offset, blur_radius, color } => {
+                let id = self.new_id("shadow");
+
+                // the filter region must include the blur.
+                let sigma = blur_radius / 2.0;
+                let aabb = path.aabb().grow(F32x2::splat(3.0*sigma));
+                let _ = writeln!(self.defs,
+                    "    <filter id=\"{}\" filterUnits=\"userSpaceOnUse\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\">\
+                    <feGaussianBlur stdDeviation=\"{}\"/></filter>",
+                    id, aabb.min.x(), aabb.min.y(), aabb.width(), aabb.height(), sigma);
+
+                self.indent();
+                let _ = writeln!(self.body, "<path d=\"{}\" transform=\"translate({} {})\" fill=\"{}\" fill-opacity=\"{}\" filter=\"url(#{})\"/>",
+                    path_data(path), offset.x(), offset.y(), rgb(color), alpha(color), id);
+            }
+
+            Cmd::BlurLayer { blur_radius } => {
+                self.indent();
+                let _ = writeln!(self.body, "<!-- unsupported: BlurLayer {{ blur_radius: {} }} -->", blur_radius);
+            }
+
+            Cmd::PushTransform { tfx } => {
+                self.tfx_stack.push(self.tfx);
+                self.tfx = self.tfx * tfx;
+                self.open_g(&format!("transform=\"{}\"", matrix(tfx)));
+            }
+
+            Cmd::PushClipPath { path, fill_rule } => {
+                let id = self.new_id("clip");
+                let _ = writeln!(self.defs, "    <clipPath id=\"{}\"><path d=\"{}\"{}/></clipPath>",
+                    id, path_data(path), fill_rule_attribute("clip-rule", fill_rule));
+                self.tfx_stack.push(self.tfx);
+                self.open_g(&format!("clip-path=\"url(#{})\"", id));
+            }
+
+            Cmd::BeginGroup { opacity, blend, filter } => {
+                let mut attributes = format!("opacity=\"{}\"", opacity);
+                attributes.push_str(&blend_style(blend));
+                if let Some(filter) = filter {
+                    let cmd_buf = self.cmd_buf;
+                    let id = self.filter_def(cmd_buf.filter(filter));
+                    let _ = write!(attributes, " filter=\"url(#{})\"", id);
+                }
+                self.tfx_stack.push(self.tfx);
+                self.open_g(&attributes);
+            }
+
+            Cmd::PopTransform |
+            Cmd::PopClip |
+            Cmd::EndGroup => {
+                // unbalanced pops are ignored.
+                if self.depth > 2 {
+                    self.tfx = self.tfx_stack.pop().unwrap_or(self.tfx);
+                    self.close_g();
+                }
+            }
+        }
+    }
+
+    /// returns the paint's `(color or url, opacity)`.
+    fn paint(&mut self, paint: Paint, opacity: f32) -> (String, f32) {
+        match paint {
+            Paint::Solid(color) => (rgb(color), alpha(color) * opacity),
+
+            Paint::LinearGradient(id) => {
+                let g = self.cmd_buf.linear_gradient(id);
+                let id = self.new_id("linear");
+                let _ = writeln!(self.defs,
+                    "    <linearGradient id=\"{}\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"{}>",
+                    id, g.p0.x(), g.p0.y(), g.p1.x(), g.p1.y(),
+                    gradient_attributes(g.spread, g.units, g.tfx));
+                write_stops(&mut self.defs, g.stops);
+                self.defs.push_str("    </linearGradient>\n");
+                (format!("url(#{})", id), opacity)
+            }
+
+            Paint::RadialGradient(id) => {
+                let g = self.cmd_buf.radial_gradient(id);
+                let id = self.new_id("radial");
+                let _ = writeln!(self.defs,
+                    "    <radialGradient id=\"{}\" cx=\"{}\" cy=\"{}\" r=\"{}\" fx=\"{}\" fy=\"{}\" fr=\"{}\"{}>",
+                    id, g.cp.x(), g.cp.y(), g.cr, g.fp.x(), g.fp.y(), g.fr,
+                    gradient_attributes(g.spread, g.units, g.tfx));
+                write_stops(&mut self.defs, g.stops);
+                self.defs.push_str("    </radialGradient>\n");
+                (format!("url(#{})", id), opacity)
+            }
+
+            Paint::ConicGradient(id) => {
+                let g = self.cmd_buf.conic_gradient(id);
+                let color = g.stops.first().map_or(0, |stop| stop.color);
+                (rgb(color), alpha(color) * opacity)
+            }
+
+            Paint::Image { image, tfx, sampling, extend: _ } => {
+                let source = self.cmd_buf.image(image);
+                let [w, h] = source.size;
+                let id = self.new_id("pattern");
+                let rendering =
+                    if sampling == ImageSampling::Nearest { " image-rendering=\"pixelated\"" }
+                    else { "" };
+                let _ = writeln!(self.defs,
+                    "    <pattern id=\"{}\" patternUnits=\"userSpaceOnUse\" width=\"{}\" height=\"{}\" patternTransform=\"{}\">\
+                    <use href=\"#image{}\"{}/></pattern>",
+                    id, w, h, matrix(tfx), image.usize(), rendering);
+                (format!("url(#{})", id), opacity)
+            }
+        }
+    }
+
+    fn image_def(&mut self, index: usize, image: &ImageSource) {
+        let png = encode_png(image);
+        let _ = writeln!(self.defs,
+            "    <image id=\"image{}\" width=\"{}\" height=\"{}\" href=\"data:image/png;base64,{}\"/>",
+            index, image.size[0], image.size[1], base64(&png));
+    }
+
+    fn filter_def(&mut self, filter: &Filter) -> String {
+        let id = self.new_id("filter");
+
+        // filtered groups cover the entire viewport.
+        let viewport = Rect { min: F32x2::ZERO(), max: F32x2::new(self.size[0] as f32, self.size[1] as f32) };
+        let region = match self.tfx.invert(0.00001) {
+            Some(inv) => inv.aabb_transform(viewport),
+            None => viewport,
+        };
+
+        let _ = writeln!(self.defs,
+            "    <filter id=\"{}\" filterUnits=\"userSpaceOnUse\" primitiveUnits=\"userSpaceOnUse\" \
+            x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" color-interpolation-filters=\"sRGB\">",
+            id, region.min.x(), region.min.y(), region.width(), region.height());
+
+        for (i, primitive) in filter.primitives.iter().enumerate() {
+            let d = &mut self.defs;
+            d.push_str("      ");
+            match *primitive {
+                FilterPrimitive::ColorMatrix { input, matrix } => {
+                    let values: Vec<String> = matrix.iter().map(|v| v.to_string()).collect();
+                    let _ = write!(d, "<feColorMatrix in=\"{}\" type=\"matrix\" values=\"{}\"",
+                        filter_input(input), values.join(" "));
+                }
+
+                FilterPrimitive::ComponentTransfer { input, functions } => {
+                    let _ = write!(d, "<feComponentTransfer in=\"{}\" result=\"r{}\">", filter_input(input), i);
+                    for (function, channel) in functions.iter().zip(["R", "G", "B", "A"]) {
+                        let _ = write!(d, "<feFunc{} ", channel);
+                        let _ = match *function {
+                            TransferFunction::Identity =>
+                                write!(d, "type=\"identity\""),
+                            TransferFunction::Table(values) =>
+                                write!(d, "type=\"table\" tableValues=\"{}\"", number_list(values)),
+                            TransferFunction::Discrete(values) =>
+                                write!(d, "type=\"discrete\" tableValues=\"{}\"", number_list(values)),
+                            TransferFunction::Linear { slope, intercept } =>
+                                write!(d, "type=\"linear\" slope=\"{}\" intercept=\"{}\"", slope, intercept),
+                            TransferFunction::Gamma { amplitude, exponent, offset } =>
+                                write!(d, "type=\"gamma\" amplitude=\"{}\" exponent=\"{}\" offset=\"{}\"", amplitude, exponent, offset),
+                        };
+                        d.push_str("/>");
+                    }
+                    d.push_str("</feComponentTransfer>\n");
+                    continue;
+                }
+
+                FilterPrimitive::Composite { input, input2, blend } => {
+                    let (i1, i2) = (filter_input(input), filter_input(input2));
+                    let operator = match blend {
+                        BlendMode::SrcOver => Some("over"),
+                        BlendMode::SrcIn   => Some("in"),
+                        BlendMode::SrcOut  => Some("out"),
+                        BlendMode::SrcAtop => Some("atop"),
+                        BlendMode::Xor     => Some("xor"),
+                        _ => None,
+                    };
+                    let _ = match (operator, blend_name(blend)) {
+                        (Some(operator), _) =>
+                            write!(d, "<feComposite in=\"{}\" in2=\"{}\" operator=\"{}\"", i1, i2, operator),
+                        (None, Some(mode)) =>
+                            write!(d, "<feBlend in=\"{}\" in2=\"{}\" mode=\"{}\"", i1, i2, mode),
+                        (None, None) if blend == BlendMode::Plus =>
+                            write!(d, "<feComposite in=\"{}\" in2=\"{}\" operator=\"arithmetic\" k2=\"1\" k3=\"1\"", i1, i2),
+                        // unsupported operators.
+                        (None, None) =>
+                            write!(d, "<feComposite in=\"{}\" in2=\"{}\" operator=\"over\"", i1, i2),
+                    };
+                }
+
+                FilterPrimitive::Arithmetic { input, input2, k } => {
+                    let _ = write!(d, "<feComposite in=\"{}\" in2=\"{}\" operator=\"arithmetic\" k1=\"{}\" k2=\"{}\" k3=\"{}\" k4=\"{}\"",
+                        filter_input(input), filter_input(input2), k[0], k[1], k[2], k[3]);
+                }
+
+                FilterPrimitive::Morphology { input, op, radius } => {
+                    let operator = match op {
+                        MorphologyOp::Erode  => "erode",
+                        MorphologyOp::Dilate => "dilate",
+                    };
+                    let _ = write!(d, "<feMorphology in=\"{}\" operator=\"{}\" radius=\"{} {}\"",
+                        filter_input(input), operator, radius.x(), radius.y());
+                }
+
+                FilterPrimitive::Offset { input, offset } => {
+                    let _ = write!(d, "<feOffset in=\"{}\" dx=\"{}\" dy=\"{}\"",
+                        filter_input(input), offset.x(), offset.y());
+                }
+
+                FilterPrimitive::GaussianBlur { input, std_deviation } => {
+                    let _ = write!(d, "<feGaussianBlur in=\"{}\" stdDeviation=\"{}\"",
+                        filter_input(input), std_deviation);
+                }
+
+                FilterPrimitive::Merge { inputs } => {
+                    let _ = write!(d, "<feMerge result=\"r{}\">", i);
+                    for input in inputs {
+                        let _ = write!(d, "<feMergeNode in=\"{}\"/>", filter_input(*input));
+                    }
+                    d.push_str("</feMerge>\n");
+                    continue;
+                }
+
+                FilterPrimitive::Flood { color } => {
+                    let _ = write!(d, "<feFlood flood-color=\"{}\" flood-opacity=\"{}\"", rgb(color), alpha(color));
+                }
+
+                FilterPrimitive::Turbulence { base_frequency, num_octaves, seed, kind } => {
+                    let kind = match kind {
+                        TurbulenceKind::FractalNoise => "fractalNoise",
+                        TurbulenceKind::Turbulence   => "turbulence",
+                    };
+                    let _ = write!(d, "<feTurbulence baseFrequency=\"{} {}\" numOctaves=\"{}\" seed=\"{}\" type=\"{}\"",
+                        base_frequency.x(), base_frequency.y(), num_octaves, seed, kind);
+                }
+            }
+            let _ = writeln!(d, " result=\"r{}\"/>", i);
+        }
+
+        self.defs.push_str("    </filter>\n");
+        id
+    }
+}
+
+
+fn path_data(path: Path) -> String {
+    let mut d = String::new();
+    let mut points = path.points().iter();
+    let mut point = |d: &mut String| {
+        let p = points.next().unwrap();
+        let _ = write!(d, " {} {}", p.x(), p.y());
+    };
+
+    for verb in path.verbs() {
+        match verb {
+            Verb::BeginOpen | Verb::BeginClosed => {
+                d.push_str(" M");
+                point(&mut d);
+            }
+
+            Verb::Line => {
+                d.push_str(" L");
+                point(&mut d);
+            }
+
+            Verb::Quad => {
+                d.push_str(" Q");
+                point(&mut d);
+                point(&mut d);
+            }
+
+            Verb::Cubic => {
+                d.push_str(" C");
+                point(&mut d);
+                point(&mut d);
+                point(&mut d);
+            }
+
+            Verb::EndClosed => d.push_str(" Z"),
+            Verb::EndOpen => (),
+        }
+    }
+
+    d.trim_start().to_string()
+}
+
+fn matrix(tfx: Transform) -> String {
+    let [a, b] = *tfx.columns[0];
+    let [c, d] = *tfx.columns[1];
+    let [e, f] = *tfx.columns[2];
+    format!("matrix({} {} {} {} {} {})", a, b, c, d, e, f)
+}
+
+#[inline]
+fn rgb(argb: u32) -> String {
+    format!("#{:06x}", argb & 0xffffff)
+}
+
+#[inline]
+fn alpha(argb: u32) -> f32 {
+    (argb >> 24) as f32 / 255.0
+}
+
+fn number_list(values: &[f32]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(" ")
+}
+
+fn fill_rule_attribute(name: &str, fill_rule: FillRule) -> String {
+    match fill_rule {
+        FillRule::NonZero => String::new(),
+        FillRule::EvenOdd => format!(" {}=\"evenodd\"", name),
+    }
+}
+
+fn stroke_attributes(style: &StrokeStyle) -> String {
+    let cap = match style.cap {
+        LineCap::Butt   => "butt",
+        LineCap::Round  => "round",
+        LineCap::Square => "square",
+    };
+    let join = match style.join {
+        LineJoin::Miter => "miter",
+        LineJoin::Round => "round",
+        LineJoin::Bevel => "bevel",
+    };
+
+    let mut result = format!(" stroke-width=\"{}\" stroke-linecap=\"{}\" stroke-linejoin=\"{}\" stroke-miterlimit=\"{}\"",
+        style.width, cap, join, style.miter_limit);
+    if !style.dashes.is_empty() {
+        let _ = write!(result, " stroke-dasharray=\"{}\" stroke-dashoffset=\"{}\"",
+            number_list(style.dashes), style.dash_offset);
+    }
+    result
+}
+
+fn blend_name(blend: BlendMode) -> Option<&'static str> {
+    use BlendMode::*;
+    Some(match blend {
+        SrcOver    => "normal",
+        Multiply   => "multiply",
+        Screen     => "screen",
+        Overlay    => "overlay",
+        Darken     => "darken",
+        Lighten    => "lighten",
+        ColorDodge => "color-dodge",
+        ColorBurn  => "color-burn",
+        HardLight  => "hard-light",
+        SoftLight  => "soft-light",
+        Difference => "difference",
+        Exclusion  => "exclusion",
+        Hue        => "hue",
+        Saturation => "saturation",
+        Color      => "color",
+        Luminosity => "luminosity",
+        _ => return None,
+    })
+}
+
+fn blend_style(blend: BlendMode) -> String {
+    match blend_name(blend) {
+        Some("normal") | None => String::new(),
+        Some(name) => format!(" style=\"mix-blend-mode:{}\"", name),
+    }
+}
+
+fn gradient_attributes(spread: SpreadMethod, units: GradientUnits, tfx: Transform) -> String {
+    let spread = match spread {
+        SpreadMethod::Pad     => "pad",
+        SpreadMethod::Reflect => "reflect",
+        SpreadMethod::Repeat  => "repeat",
+    };
+    let units = match units {
+        GradientUnits::Absolute => "userSpaceOnUse",
+        GradientUnits::Relative => "objectBoundingBox",
+    };
+    format!(" spreadMethod=\"{}\" gradientUnits=\"{}\" gradientTransform=\"{}\"", spread, units, matrix(tfx))
+}
+
+fn write_stops(out: &mut String, stops: &[GradientStop]) {
+    for stop in stops {
+        let _ = writeln!(out, "      <stop offset=\"{}\" stop-color=\"{}\" stop-opacity=\"{}\"/>",
+            stop.offset, rgb(stop.color), alpha(stop.color));
+    }
+}
+
+fn filter_input(input: FilterInput) -> String {
+    match input {
+        FilterInput::Source      => "SourceGraphic".into(),
+        FilterInput::SourceAlpha => "SourceAlpha".into(),
+        FilterInput::Result(i)   => format!("r{}", i),
+    }
+}
+
+
+/// rgba8 png with uncompressed deflate blocks.
+fn encode_png(image: &ImageSource) -> Vec<u8> {
+    let [w, h] = image.size.map(|s| s as usize);
+
+    let mut raw = Vec::with_capacity((4*w + 1)*h);
+    for row in image.pixels.chunks(w.max(1)).take(h) {
+        // filter type: none.
+        raw.push(0);
+        for p in row {
+            raw.extend_from_slice(&[(p >> 16) as u8, (p >> 8) as u8, *p as u8, (p >> 24) as u8]);
+        }
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+    if blocks.is_empty() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    for (i, block) in blocks.iter().enumerate() {
+        let last = i + 1 == blocks.len();
+        let len = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(w as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(h as u32).to_be_bytes());
+    // 8 bit rgba, deflate, no filter, no interlace.
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = vec![137, 80, 78, 71, 13, 10, 26, 10];
+    png_chunk(&mut png, b"IHDR", &ihdr);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let mut crc = !0u32;
+    for b in kind.iter().chain(data) {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    png.extend_from_slice(&(!crc).to_be_bytes());
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for v in data {
+        a = (a + *v as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let v = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(v >> (18 - 6*i)) as usize & 0x3f] as char);
+            }
+            else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export() {
+        let cmd_buf = CmdBuf::new(|cb| {
+            let path = cb.build_path(|pb| {
+                pb.move_to([0.0, 0.0]);
+                pb.line_to([10.0, 0.0]);
+                pb.line_to([10.0, 10.0]);
+                pb.close_path();
+            });
+
+            let clip = cb.build_path(|pb| {
+                pb.move_to([1.0, 1.0]);
+                pb.line_to([9.0, 1.0]);
+                pb.line_to([9.0, 9.0]);
+                pb.close_path();
+            });
+
+            let stops = cb.build_gradient_stops(|stops| {
+                stops.push(GradientStop { offset: 0.0, color: 0xffff0000 });
+                stops.push(GradientStop { offset: 1.0, color: 0xff0000ff });
+            });
+            let gradient = cb.push_linear_gradient(LinearGradient {
+                p0: F32x2::new(0.0, 0.0),
+                p1: F32x2::new(10.0, 0.0),
+                spread: SpreadMethod::Pad,
+                units:  GradientUnits::Absolute,
+                tfx:    Transform::ID(),
+                stops,
+            });
+
+            cb.push(Cmd::PushClipPath { path: clip, fill_rule: FillRule::EvenOdd });
+            cb.push(Cmd::BeginGroup { opacity: 0.5, blend: BlendMode::Multiply, filter: None });
+            cb.push(Cmd::FillPath { path, fill_rule: FillRule::NonZero, paint: Paint::LinearGradient(gradient), opacity: 1.0, blend: BlendMode::SrcOver });
+            cb.push(Cmd::StrokePath { path, style: StrokeStyle::new(2.0), paint: Paint::Solid(0xff00ff00), opacity: 0.5, blend: BlendMode::SrcOver });
+            cb.push(Cmd::EndGroup);
+            cb.push(Cmd::PopClip);
+        });
+
+        let params = RenderParams { clear: 0xffffffff, tfx: Transform::ID(), load: false };
+        let svg = to_svg(&cmd_buf, &params, [20, 10]);
+
+        assert_eq!(svg, r##"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10" viewBox="0 0 20 10">
+  <defs>
+    <clipPath id="clip1"><path d="M 1 1 L 9 1 L 9 9 L 1 1 Z" clip-rule="evenodd"/></clipPath>
+    <linearGradient id="linear2" x1="0" y1="0" x2="10" y2="0" spreadMethod="pad" gradientUnits="userSpaceOnUse" gradientTransform="matrix(1 0 0 1 0 0)">
+      <stop offset="0" stop-color="#ff0000" stop-opacity="1"/>
+      <stop offset="1" stop-color="#0000ff" stop-opacity="1"/>
+    </linearGradient>
+  </defs>
+  <rect width="20" height="10" fill="#ffffff" fill-opacity="1"/>
+  <g transform="matrix(1 0 0 1 0 0)">
+    <g clip-path="url(#clip1)">
+      <g opacity="0.5" style="mix-blend-mode:multiply">
+        <path d="M 0 0 L 10 0 L 10 10 L 0 0 Z" fill="url(#linear2)" fill-opacity="1"/>
+        <path d="M 0 0 L 10 0 L 10 10 L 0 0 Z" fill="none" stroke="#00ff00" stroke-opacity="0.5" stroke-width="2" stroke-linecap="butt" stroke-linejoin="miter" stroke-miterlimit="4"/>
+      </g>
+    </g>
+  </g>
+</svg>
+"##);
+    }
+}